@@ -0,0 +1,53 @@
+extern crate vec_collections;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use vec_collections::VecSet;
+
+/// the system allocator, counting allocations and reallocations
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static REALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        REALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn counted<T>(name: &str, f: impl FnOnce() -> T) -> T {
+    let a0 = ALLOCATIONS.load(Ordering::Relaxed);
+    let r0 = REALLOCATIONS.load(Ordering::Relaxed);
+    let t0 = std::time::Instant::now();
+    let res = f();
+    let dt = std::time::Instant::now() - t0;
+    let a = ALLOCATIONS.load(Ordering::Relaxed) - a0;
+    let r = REALLOCATIONS.load(Ordering::Relaxed) - r0;
+    println!("{} allocations {} reallocations {} {:?}", name, a, r, dt);
+    res
+}
+
+fn main() {
+    let n = 1000000;
+    let a: VecSet<u64> = (0..n).map(|x| x * 2).collect();
+    let b: VecSet<u64> = (0..n).map(|x| x * 2 + 1).collect();
+    let c: VecSet<u64> = (2 * n..3 * n).collect();
+    let r = counted("union interleaved", || a.union(&b));
+    assert_eq!(r.len() as u64, 2 * n);
+    let r = counted("union non overlapping", || a.union(&c));
+    assert_eq!(r.len() as u64, 2 * n);
+    let r = counted("union identical", || a.union(&a));
+    assert_eq!(r.len() as u64, n);
+}
@@ -1,34 +1,52 @@
 extern crate vec_collections;
 
 use std::collections::{BTreeMap, HashMap};
-use vec_collections::VecMap;
+use vec_collections::{Entry, VecMap, VecSet};
 
 type Element = i64;
 fn element(x: usize) -> Element {
     x as Element
 }
 
-fn creation_vecmap(name: &str, data: &Vec<(Element, Element)>) {
-    let elems = data.clone();
+fn creation_vecmap(name: &str, data: &[(Element, Element)]) {
+    let elems = data.to_vec();
     let t0 = std::time::Instant::now();
     let a: VecMap<Element, Element> = elems.into_iter().collect();
     let dt = std::time::Instant::now() - t0;
     println!("creation vecmap {} {} {:?}", a.len(), name, dt);
 }
-fn creation_btreemap(name: &str, data: &Vec<(Element, Element)>) {
-    let elems = data.clone();
+fn creation_btreemap(name: &str, data: &[(Element, Element)]) {
+    let elems = data.to_vec();
     let t0 = std::time::Instant::now();
     let a: BTreeMap<Element, Element> = elems.into_iter().collect();
     let dt = std::time::Instant::now() - t0;
     println!("creation btreemap {} {} {:?}", a.len(), name, dt);
 }
-fn creation_hashmap(name: &str, data: &Vec<(Element, Element)>) {
-    let elems = data.clone();
+fn creation_hashmap(name: &str, data: &[(Element, Element)]) {
+    let elems = data.to_vec();
     let t0 = std::time::Instant::now();
     let a: HashMap<Element, Element> = elems.into_iter().collect();
     let dt = std::time::Instant::now() - t0;
     println!("creation hashmap {} {} {:?}", a.len(), name, dt);
 }
+fn remove_all_vecmap(name: &str, map: &VecMap<Element, Element>, keys: &VecSet<Element>) {
+    let mut a = map.clone();
+    let t0 = std::time::Instant::now();
+    a.remove_all(keys);
+    let dt = std::time::Instant::now() - t0;
+    println!("remove_all vecmap {} {} {:?}", a.len(), name, dt);
+}
+fn remove_single_vecmap(name: &str, map: &VecMap<Element, Element>, keys: &VecSet<Element>) {
+    let mut a = map.clone();
+    let t0 = std::time::Instant::now();
+    for k in keys.iter() {
+        if let Entry::Occupied(e) = a.entry(*k) {
+            e.remove();
+        }
+    }
+    let dt = std::time::Instant::now() - t0;
+    println!("remove single vecmap {} {} {:?}", a.len(), name, dt);
+}
 fn main() {
     let mut x: Vec<(Element, Element)> = Vec::new();
     for i in 0..1000000 {
@@ -40,4 +58,11 @@ fn main() {
         creation_btreemap("mixed", &x);
         creation_hashmap("mixed", &x);
     }
+
+    let map: VecMap<Element, Element> = (0..100000).map(|i| (element(i), element(i))).collect();
+    let keys: VecSet<Element> = (0..10000).map(|i| element(i * 10)).collect();
+    for _ in 0..3 {
+        remove_all_vecmap("every 10th", &map, &keys);
+        remove_single_vecmap("every 10th", &map, &keys);
+    }
 }
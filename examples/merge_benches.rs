@@ -0,0 +1,89 @@
+extern crate vec_collections;
+
+use vec_collections::{MergeStrategy, VecSet};
+
+/// the union of a and b with the given strategy, printing the time and the number of comparisons
+fn union(a: &VecSet<i64>, b: &VecSet<i64>, strategy: MergeStrategy, name: &str) -> VecSet<i64> {
+    let t0 = std::time::Instant::now();
+    let r = a.union_with_strategy(b, strategy);
+    let dt = std::time::Instant::now() - t0;
+    let c = a.union_comparisons(b, strategy);
+    println!("union {:?} {} {} {:?}", strategy, name, c, dt);
+    r
+}
+
+fn adaptive() {
+    let n = 1_000_000;
+    for ratio in &[1, 2, 4, 8, 16, 100, 1000] {
+        let b: VecSet<i64> = (0..n).collect();
+        let a: VecSet<i64> = (0..n / ratio).map(|x| x * ratio + ratio / 2).collect();
+        let name = format!("1:{}", ratio);
+        let expected = union(&a, &b, MergeStrategy::Binary, &name);
+        assert_eq!(union(&a, &b, MergeStrategy::Adaptive, &name), expected);
+    }
+}
+
+/// `Iterative` always bisects the first set, `Binary` bisects the smaller one
+fn symmetric() {
+    let n = 1_000_000;
+    let large: VecSet<i64> = (0..n).collect();
+    let small: VecSet<i64> = (0..n / 1000).map(|x| x * 1000 + 500).collect();
+    for (name, a, b) in &[("1:1000", &small, &large), ("1000:1", &large, &small)] {
+        let expected = union(a, b, MergeStrategy::Iterative, name);
+        assert_eq!(union(a, b, MergeStrategy::Binary, name), expected);
+    }
+}
+
+/// `Binary` has a fast path for nearly identical sets, `Iterative` does not
+fn similar() {
+    let n = 1_000_000;
+    let a: VecSet<i64> = (0..n).map(|x| x * 2).collect();
+    // same length, first and last element, one element replaced in the middle
+    let b: VecSet<i64> = a
+        .iter()
+        .map(|x| if *x == (n / 3) * 2 { x + 1 } else { *x })
+        .collect();
+    for _ in 0..3 {
+        let expected = union(&a, &b, MergeStrategy::Iterative, "nearly identical");
+        assert_eq!(
+            union(&a, &b, MergeStrategy::Binary, "nearly identical"),
+            expected
+        );
+    }
+}
+
+fn galloping() {
+    let b: VecSet<i64> = (0..1_000_000).collect();
+    let spread: VecSet<i64> = (0..10).map(|x| x * 100_000 + 1).collect();
+    let clustered: VecSet<i64> = (0..10).map(|x| x * 3 + 1).collect();
+    for (name, a) in &[("10 spread", spread), ("10 clustered", clustered)] {
+        let expected = union(a, &b, MergeStrategy::Binary, name);
+        assert_eq!(union(a, &b, MergeStrategy::Galloping, name), expected);
+    }
+}
+
+fn copy() {
+    let n = 1_000_000;
+    let interleaved: VecSet<u64> = (0..n).map(|x| x * 2 + 1).collect();
+    let blocks: VecSet<u64> = (0..n).map(|x| (x / 100) * 200 + 100 + x % 100).collect();
+    let a: VecSet<u64> = (0..n).map(|x| x * 2).collect();
+    for (name, b) in &[("interleaved", interleaved), ("blocks", blocks)] {
+        let t0 = std::time::Instant::now();
+        let expected = a.union(b);
+        let dt = std::time::Instant::now() - t0;
+        println!("union generic {} {} {:?}", name, expected.len(), dt);
+        let t0 = std::time::Instant::now();
+        let r = a.union_copy(b);
+        let dt = std::time::Instant::now() - t0;
+        println!("union copy {} {} {:?}", name, r.len(), dt);
+        assert_eq!(r, expected);
+    }
+}
+
+fn main() {
+    adaptive();
+    symmetric();
+    similar();
+    galloping();
+    copy();
+}
@@ -1,7 +1,7 @@
 extern crate vec_collections;
 
 use std::collections::{BTreeSet, HashSet};
-use vec_collections::VecSet;
+use vec_collections::{assume_sorted_by_item, VecSet, VecSet2};
 
 // #[macro_use]
 // extern crate lazy_static;
@@ -55,6 +55,16 @@ impl TestData {
             b: (0..n).map(|x| element(2 * x + 2 * n)).collect(),
         }
     }
+
+    fn early_overlap(n: usize) -> TestData {
+        TestData {
+            params: format!("early_overlap {}", n),
+            a: (0..n).map(|x| element(2 * x)).collect(),
+            b: (0..n)
+                .map(|x| element(if x == 0 { 0 } else { 2 * x + 1 }))
+                .collect(),
+        }
+    }
 }
 
 fn union_arrayset(data: &TestData) {
@@ -137,32 +147,116 @@ fn is_disjoint_hashset(data: &TestData) {
     let dt = std::time::Instant::now() - t0;
     println!("is_disjoint hashset {} {} {:?}", _r, data.params, dt);
 }
-fn creation_arrayset(name: &str, data: &Vec<Element>) {
-    let elems = data.clone();
+fn creation_arrayset(name: &str, data: &[Element]) {
+    let elems = data.to_vec();
     let t0 = std::time::Instant::now();
     let a: VecSet<Element> = elems.into_iter().collect();
     let dt = std::time::Instant::now() - t0;
     println!("creation vecset {} {} {:?}", a.len(), name, dt);
 }
-fn creation_btreeset(name: &str, data: &Vec<Element>) {
-    let elems = data.clone();
+fn creation_btreeset(name: &str, data: &[Element]) {
+    let elems = data.to_vec();
     let t0 = std::time::Instant::now();
     let a: BTreeSet<Element> = elems.into_iter().collect();
     let dt = std::time::Instant::now() - t0;
     println!("creation btreeset {} {} {:?}", a.len(), name, dt);
 }
-fn creation_hashset(name: &str, data: &Vec<Element>) {
-    let elems = data.clone();
+fn creation_hashset(name: &str, data: &[Element]) {
+    let elems = data.to_vec();
     let t0 = std::time::Instant::now();
     let a: HashSet<Element> = elems.into_iter().collect();
     let dt = std::time::Instant::now() - t0;
     println!("creation hashset {} {} {:?}", a.len(), name, dt);
 }
+fn small_sets(n: usize) -> Vec<Vec<Element>> {
+    (0..n)
+        .map(|i| (0..4).map(|j| element(i + 3 * j)).collect())
+        .collect()
+}
+fn small_union_arrayset(data: &[Vec<Element>]) {
+    let sets: Vec<VecSet<Element>> = data.iter().map(|x| x.clone().into()).collect();
+    let t0 = std::time::Instant::now();
+    let mut total = 0;
+    for w in sets.windows(2) {
+        total += (&w[0] | &w[1]).len();
+    }
+    let dt = std::time::Instant::now() - t0;
+    println!("small union vecset {} {} {:?}", sets.len(), total, dt);
+}
+fn small_union_smallvec(data: &[Vec<Element>]) {
+    // room for 8 elements, so the union of two 4 element sets is also stored inline
+    let sets: Vec<VecSet2<Element, [Element; 8]>> = data.iter().map(|x| x.clone().into()).collect();
+    let t0 = std::time::Instant::now();
+    let mut total = 0;
+    for w in sets.windows(2) {
+        total += (&w[0] | &w[1]).len();
+    }
+    let dt = std::time::Instant::now() - t0;
+    println!("small union vecset2 {} {} {:?}", sets.len(), total, dt);
+}
+fn extend_batch_arrayset(data: &[Element]) {
+    let mut a: VecSet<Element> = VecSet::empty();
+    let t0 = std::time::Instant::now();
+    a.extend(data.iter().cloned());
+    let dt = std::time::Instant::now() - t0;
+    println!("extend batch vecset {} {:?}", a.len(), dt);
+}
+fn extend_single_arrayset(data: &[Element]) {
+    let mut a: VecSet<Element> = VecSet::empty();
+    let t0 = std::time::Instant::now();
+    for x in data.iter().cloned() {
+        a.insert(x);
+    }
+    let dt = std::time::Instant::now() - t0;
+    println!("extend single vecset {} {:?}", a.len(), dt);
+}
+fn union_all_arrayset(data: &[Vec<Element>]) {
+    let sets: Vec<VecSet<Element>> = data.iter().map(|x| x.clone().into()).collect();
+    let t0 = std::time::Instant::now();
+    let r = VecSet::union_all(sets.clone());
+    let dt = std::time::Instant::now() - t0;
+    println!(
+        "union all balanced vecset {} {} {:?}",
+        sets.len(),
+        r.len(),
+        dt
+    );
+    let t0 = std::time::Instant::now();
+    let r = sets.into_iter().fold(VecSet::empty(), |mut a, b| {
+        a |= b;
+        a
+    });
+    let dt = std::time::Instant::now() - t0;
+    println!("union all fold vecset {} {:?}", r.len(), dt);
+}
+fn collect_sorted_arrayset(data: &[Element]) {
+    let t0 = std::time::Instant::now();
+    let a = assume_sorted_by_item(data.iter().cloned()).collect_sorted();
+    let dt = std::time::Instant::now() - t0;
+    println!("collect sorted vecset {} {:?}", a.len(), dt);
+    let t0 = std::time::Instant::now();
+    let a: VecSet<Element> = data.iter().cloned().collect();
+    let dt = std::time::Instant::now() - t0;
+    println!("collect vecset {} {:?}", a.len(), dt);
+}
+fn contains_clustered_arrayset(set: &VecSet<Element>, queries: &[Element]) {
+    let t0 = std::time::Instant::now();
+    let found = queries.iter().filter(|x| set.contains(x)).count();
+    let dt = std::time::Instant::now() - t0;
+    println!("contains clustered vecset {} {:?}", found, dt);
+    let t0 = std::time::Instant::now();
+    let mut cursor = set.contains_cursor();
+    let found = queries.iter().filter(|x| cursor.contains(x)).count();
+    let dt = std::time::Instant::now() - t0;
+    println!("contains clustered cursor vecset {} {:?}", found, dt);
+}
 fn main() {
     let interleaved = TestData::interleaved(10000);
 
     let non_overlapping = TestData::non_overlapping(10000);
 
+    let early_overlap = TestData::early_overlap(1000000);
+
     let mut x: Vec<Element> = Vec::new();
     for i in 0..1000000 {
         x.push(element(i * 3 % 10000));
@@ -192,7 +286,31 @@ fn main() {
     is_disjoint_btreeset(&non_overlapping);
     is_disjoint_hashset(&non_overlapping);
 
+    is_disjoint_arrayset(&early_overlap);
+    is_disjoint_btreeset(&early_overlap);
+    is_disjoint_hashset(&early_overlap);
+
     creation_arrayset("mixed", &x);
     creation_btreeset("mixed", &x);
     creation_hashset("mixed", &x);
+
+    let batch: Vec<Element> = (0..100000).map(|i| element(i * 7919 % 100000)).collect();
+    extend_batch_arrayset(&batch);
+    extend_single_arrayset(&batch);
+
+    let sorted: Vec<Element> = (0..1000000).map(|i| element(i / 2)).collect();
+    collect_sorted_arrayset(&sorted);
+
+    let set: VecSet<Element> = (0..1000000).map(|i| element(i * 3)).collect();
+    let queries: Vec<Element> = (0..1000000).map(|i| element(1000000 + i)).collect();
+    contains_clustered_arrayset(&set, &queries);
+
+    let many: Vec<Vec<Element>> = (0..1000)
+        .map(|i| (0..100).map(|j| element(i * 7 + j * 1000)).collect())
+        .collect();
+    union_all_arrayset(&many);
+
+    let small = small_sets(1000000);
+    small_union_arrayset(&small);
+    small_union_smallvec(&small);
 }
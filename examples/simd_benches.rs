@@ -0,0 +1,48 @@
+extern crate vec_collections;
+
+use rand::seq::SliceRandom;
+use vec_collections::VecSet;
+
+/// two sets of n elements each, half of which are in both
+fn make_sets(n: u32) -> (Vec<u32>, Vec<u32>) {
+    let mut rng = rand::thread_rng();
+    let mut elements: Vec<u32> = (0..n * 3 / 2).map(|x| x * 3).collect();
+    elements.shuffle(&mut rng);
+    let (common, rest) = elements.split_at((n / 2) as usize);
+    let (only_a, only_b) = rest.split_at((n / 2) as usize);
+    let a = common.iter().chain(only_a.iter()).cloned().collect();
+    let b = common.iter().chain(only_b.iter()).cloned().collect();
+    (a, b)
+}
+
+fn intersection_scalar<T: Ord + Clone>(a: &VecSet<T>, b: &VecSet<T>, name: &str) -> VecSet<T> {
+    let t0 = std::time::Instant::now();
+    let r = a.intersection(b);
+    let dt = std::time::Instant::now() - t0;
+    println!("intersection scalar {} {:?}", name, dt);
+    r
+}
+
+fn main() {
+    let n = 1_000_000;
+    let (a, b) = make_sets(n);
+    let (a, b): (VecSet<u32>, VecSet<u32>) = (a.into(), b.into());
+    for _ in 0..5 {
+        let expected = intersection_scalar(&a, &b, "u32");
+        let t0 = std::time::Instant::now();
+        let r = a.intersection_simd(&b);
+        let dt = std::time::Instant::now() - t0;
+        println!("intersection simd u32 {:?}", dt);
+        assert_eq!(r, expected);
+    }
+    let a: VecSet<u64> = a.iter().map(|x| *x as u64).collect();
+    let b: VecSet<u64> = b.iter().map(|x| *x as u64).collect();
+    for _ in 0..5 {
+        let expected = intersection_scalar(&a, &b, "u64");
+        let t0 = std::time::Instant::now();
+        let r = a.intersection_simd(&b);
+        let dt = std::time::Instant::now() - t0;
+        println!("intersection simd u64 {:?}", dt);
+        assert_eq!(r, expected);
+    }
+}
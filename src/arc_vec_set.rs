@@ -0,0 +1,238 @@
+use crate::iterators::SortedIter;
+use crate::VecSet;
+use std::fmt::Debug;
+use std::iter::FromIterator;
+use std::sync::Arc;
+
+/// A `VecSet` behind an `Arc`, so that cloning is cheap and the elements are only copied on mutation
+///
+/// Clones are snapshots that share their storage. Mutating a snapshot copies the elements using
+/// `Arc::make_mut` if they are shared, and leaves the other snapshots unchanged. Operations that find
+/// that they would not change anything, such as inserting an element that is already contained, never
+/// copy. Likewise, the set operations return a shared snapshot when the result is equal to one of the
+/// operands.
+#[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct ArcVecSet<T>(Arc<VecSet<T>>);
+
+impl<T: Debug> Debug for ArcVecSet<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.0.iter()).finish()
+    }
+}
+
+impl<T> ArcVecSet<T> {
+    pub fn as_slice(&self) -> &[T] {
+        self.0.as_slice()
+    }
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    /// An iterator that returns the items of this set in sorted order
+    pub fn iter(&self) -> SortedIter<std::slice::Iter<'_, T>> {
+        self.0.iter()
+    }
+    /// true if self and that are snapshots of the same storage
+    pub fn shares_storage(&self, that: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &that.0)
+    }
+}
+
+impl<T> From<VecSet<T>> for ArcVecSet<T> {
+    fn from(value: VecSet<T>) -> Self {
+        Self(Arc::new(value))
+    }
+}
+
+impl<T: Clone> From<ArcVecSet<T>> for VecSet<T> {
+    /// Unwraps the set, copying the elements only if they are shared with another snapshot
+    fn from(value: ArcVecSet<T>) -> Self {
+        Arc::try_unwrap(value.0).unwrap_or_else(|shared| (*shared).clone())
+    }
+}
+
+impl<T: Ord> FromIterator<T> for ArcVecSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        VecSet::from_iter(iter).into()
+    }
+}
+
+impl<T: Ord> ArcVecSet<T> {
+    pub fn contains(&self, value: &T) -> bool {
+        self.0.contains(value)
+    }
+    pub fn is_disjoint(&self, that: &Self) -> bool {
+        self.0.is_disjoint(&that.0)
+    }
+    pub fn is_subset(&self, that: &Self) -> bool {
+        self.shares_storage(that) || self.0.is_subset(&that.0)
+    }
+    pub fn is_superset(&self, that: &Self) -> bool {
+        that.is_subset(self)
+    }
+}
+
+impl<T: Ord + Clone> ArcVecSet<T> {
+    /// Inserts a single element, returning true if it was not already contained
+    ///
+    /// Unlike [VecSet::insert], an equal element that is already contained is not replaced, so that
+    /// this does not copy shared elements without changing the set.
+    pub fn insert(&mut self, value: T) -> bool {
+        !self.contains(&value) && Arc::make_mut(&mut self.0).insert(value)
+    }
+
+    /// Removes a single element, returning true if it was contained
+    pub fn remove(&mut self, value: &T) -> bool {
+        self.contains(value) && Arc::make_mut(&mut self.0).remove(value)
+    }
+
+    /// The elements that are in self or that, sharing the storage of an operand if it is the result
+    pub fn union(&self, that: &Self) -> Self {
+        if that.is_subset(self) {
+            self.clone()
+        } else if self.is_subset(that) {
+            that.clone()
+        } else {
+            self.0.union(&that.0).into()
+        }
+    }
+
+    /// The elements that are in both self and that, sharing the storage of an operand if it is the result
+    pub fn intersection(&self, that: &Self) -> Self {
+        if self.is_subset(that) {
+            self.clone()
+        } else if that.is_subset(self) {
+            that.clone()
+        } else {
+            self.0.intersection(&that.0).into()
+        }
+    }
+
+    /// The elements that are in self but not in that, sharing the storage of self if nothing is removed
+    pub fn difference(&self, that: &Self) -> Self {
+        if self.is_disjoint(that) {
+            self.clone()
+        } else {
+            self.0.difference(&that.0).into()
+        }
+    }
+}
+
+/// In-place operations that only copy shared elements if self actually changes
+///
+/// They return true if self was changed, like the in-place operations of `VecSet`.
+impl<T: Ord + Clone + Default> ArcVecSet<T> {
+    pub fn union_with(&mut self, that: &Self) -> bool {
+        !that.is_subset(self) && Arc::make_mut(&mut self.0).union_with(&that.0)
+    }
+
+    pub fn intersection_with(&mut self, that: &Self) -> bool {
+        !self.is_subset(that) && Arc::make_mut(&mut self.0).intersection_with(&that.0)
+    }
+
+    pub fn difference_with(&mut self, that: &Self) -> bool {
+        !self.is_disjoint(that) && Arc::make_mut(&mut self.0).difference_with(&that.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+    use std::collections::BTreeSet;
+
+    type Test = ArcVecSet<i64>;
+
+    fn count(a: &Test) -> usize {
+        Arc::strong_count(&a.0)
+    }
+
+    #[test]
+    fn snapshots_share_until_mutated() {
+        let mut a: Test = vec![1, 2, 3].into_iter().collect();
+        let snapshot = a.clone();
+        assert_eq!(count(&a), 2);
+        assert!(a.shares_storage(&snapshot));
+        // no change, so no copy
+        assert!(!a.insert(2));
+        assert!(!a.remove(&4));
+        assert!(!a.union_with(&vec![1, 3].into_iter().collect()));
+        assert!(a.shares_storage(&snapshot));
+        assert_eq!(count(&a), 2);
+        // the first change copies
+        assert!(a.insert(4));
+        assert!(!a.shares_storage(&snapshot));
+        assert_eq!(count(&a), 1);
+        assert_eq!(count(&snapshot), 1);
+        assert_eq!(a.as_slice(), &[1, 2, 3, 4]);
+        assert_eq!(snapshot.as_slice(), &[1, 2, 3]);
+        // now that it is unique, further changes happen in place
+        let ptr = a.as_slice().as_ptr();
+        assert!(a.remove(&4));
+        assert_eq!(a.as_slice().as_ptr(), ptr);
+        assert_eq!(a, snapshot);
+    }
+
+    #[test]
+    fn merges_return_shared_snapshots() {
+        let a: Test = vec![1, 2, 3, 4].into_iter().collect();
+        let b: Test = vec![2, 3].into_iter().collect();
+        let c: Test = vec![5, 6].into_iter().collect();
+        assert!(a.union(&b).shares_storage(&a));
+        assert!(b.union(&a).shares_storage(&a));
+        assert!(a.intersection(&b).shares_storage(&b));
+        assert!(b.intersection(&a).shares_storage(&b));
+        assert!(a.difference(&c).shares_storage(&a));
+        assert_eq!(count(&a), 1);
+        let snapshot = a.union(&b);
+        assert_eq!(count(&a), 2);
+        drop(snapshot);
+        // results that differ from both operands are new
+        let d = a.union(&c);
+        assert!(!d.shares_storage(&a) && !d.shares_storage(&c));
+        assert_eq!(d.as_slice(), &[1, 2, 3, 4, 5, 6]);
+        assert_eq!(a.difference(&b).as_slice(), &[1, 4]);
+        // converting back only copies if shared
+        let ptr = d.as_slice().as_ptr();
+        let v: VecSet<i64> = d.into();
+        assert_eq!(v.as_slice().as_ptr(), ptr);
+        let e = a.clone();
+        let v: VecSet<i64> = e.into();
+        assert_ne!(v.as_slice().as_ptr(), a.as_slice().as_ptr());
+    }
+
+    #[quickcheck]
+    fn same_as_vec_set(a: BTreeSet<i64>, b: BTreeSet<i64>) -> bool {
+        let a1: VecSet<i64> = a.iter().cloned().collect();
+        let b1: VecSet<i64> = b.iter().cloned().collect();
+        let a2: Test = a1.clone().into();
+        let b2: Test = b1.clone().into();
+        let mut u = a2.clone();
+        let mut i = a2.clone();
+        let mut d = a2.clone();
+        let changed = (
+            u.union_with(&b2),
+            i.intersection_with(&b2),
+            d.difference_with(&b2),
+        );
+        let expected = {
+            let (mut u, mut i, mut d) = (a1.clone(), a1.clone(), a1.clone());
+            (
+                u.union_with(&b1),
+                i.intersection_with(&b1),
+                d.difference_with(&b1),
+            )
+        };
+        a2.union(&b2).as_slice() == a1.union(&b1).as_slice()
+            && a2.intersection(&b2).as_slice() == a1.intersection(&b1).as_slice()
+            && a2.difference(&b2).as_slice() == a1.difference(&b1).as_slice()
+            && u.as_slice() == a1.union(&b1).as_slice()
+            && i.as_slice() == a1.intersection(&b1).as_slice()
+            && d.as_slice() == a1.difference(&b1).as_slice()
+            && changed == expected
+            && a2.is_subset(&b2) == a1.is_subset(&b1)
+            && a2.is_disjoint(&b2) == a1.is_disjoint(&b1)
+    }
+}
@@ -1,14 +1,165 @@
-use std::cmp::Ordering;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::Cell;
+use core::cmp::Ordering;
+use core::task::Poll;
 
 /// The read part of the merge state that is needed for the binary merge algorithm
 /// it just needs random access for the remainder of a and b
 ///
+/// The remainders do not have to be contiguous slices, so this can be implemented for e.g. a `VecDeque`.
+///
 /// Very often A and B are the same type, but this is not strictly necessary
 pub(crate) trait MergeStateRead<A, B> {
-    /// The remaining data in a
-    fn a_slice(&self) -> &[A];
-    /// The remaining data in b
-    fn b_slice(&self) -> &[B];
+    /// The number of remaining elements in a
+    fn a_len(&self) -> usize;
+    /// The number of remaining elements in b
+    fn b_len(&self) -> usize;
+    /// The remaining element of a at index i
+    fn a_at(&self, i: usize) -> &A;
+    /// The remaining element of b at index i
+    fn b_at(&self, i: usize) -> &B;
+}
+
+/// Like `binary_search_by`, but for an indexed sequence of length n instead of a slice
+pub(crate) fn binary_search_by_index<F: FnMut(usize) -> Ordering>(
+    n: usize,
+    mut f: F,
+) -> Result<usize, usize> {
+    let mut lo = 0;
+    let mut hi = n;
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        match f(mid) {
+            Ordering::Less => lo = mid + 1,
+            Ordering::Greater => hi = mid,
+            Ordering::Equal => return Ok(mid),
+        }
+    }
+    Err(lo)
+}
+
+/// Like `binary_search_by_index`, but first probes exponentially from the start of the sequence to bracket
+/// the position, and only then does a binary search within the bracketed window.
+///
+/// This needs O(log(i)) comparisons to find position i, so it is faster than a plain binary search
+/// when the position is expected to be close to the start of the sequence.
+pub(crate) fn gallop_search_by<F: FnMut(usize) -> Ordering>(
+    n: usize,
+    mut f: F,
+) -> Result<usize, usize> {
+    let mut lo = 0;
+    let mut hi = 1;
+    // widen the window until the element at hi is not less than what we are looking for
+    while hi < n && f(hi) == Ordering::Less {
+        lo = hi + 1;
+        hi = hi * 2 + 1;
+    }
+    let hi = core::cmp::min(hi + 1, n);
+    match binary_search_by_index(hi - lo, |i| f(lo + i)) {
+        Ok(i) => Ok(lo + i),
+        Err(i) => Err(lo + i),
+    }
+}
+
+/// The default size ratio below which the adaptive merge uses a linear scan instead of bisection
+pub(crate) const LINEAR_MERGE_RATIO: usize = 8;
+
+/// The number of interior positions compared by `ShortcutMergeOperation::looks_identical`
+const IDENTICAL_SAMPLES: usize = 8;
+
+/// The minimum size for which `ShortcutMergeOperation::merge` checks for nearly identical inputs
+const IDENTICAL_MIN_LEN: usize = 64;
+
+/// A pending step of the iterative merge, see `ShortcutMergeOperation::merge_iterative`
+pub(crate) enum MergeStep {
+    /// merge `an` elements from a and `bn` elements from b
+    Merge(usize, usize),
+    /// a single element from a
+    FromA,
+    /// a single element from both a and b
+    Collision,
+}
+
+/// The pending work of an iterative merge that can be suspended and resumed
+///
+/// This does the same calls in the same order as `merge_iterative0`, but stops once the merge state has
+/// produced a given number of elements, splitting up runs if needed. The positions in a and b and the result
+/// are kept by the merge state, so the same state has to be passed to every call.
+pub(crate) struct ResumableMergeStack(Vec<MergeStep>);
+
+impl ResumableMergeStack {
+    /// The work for merging `an` elements from a and `bn` elements from b
+    pub fn new(an: usize, bn: usize) -> Self {
+        Self(vec![MergeStep::Merge(an, bn)])
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Produces up to n elements, returning `Poll::Ready` once the merge is complete or aborted
+    ///
+    /// `len` is the number of elements the merge state has produced so far. Every element taken from the
+    /// inputs must produce at most one element, so a run is split to the remaining budget, and the budget is
+    /// only used up by the elements that are actually produced.
+    pub fn step<A, B, M, O, L>(&mut self, o: &O, m: &mut M, n: usize, len: L) -> Poll<()>
+    where
+        M: MergeStateRead<A, B>,
+        O: ShortcutMergeOperation<A, B, M>,
+        L: Fn(&M) -> usize,
+    {
+        let end = len(m).saturating_add(n);
+        while let Some(step) = self.0.pop() {
+            let budget = end.saturating_sub(len(m));
+            let res = match step {
+                MergeStep::Merge(0, 0) => Some(()),
+                MergeStep::Merge(an, bn) if an > 0 && bn > 0 => {
+                    // splitting does not take any elements, so it does not need any budget
+                    let am: usize = an / 2;
+                    let a = m.a_at(am);
+                    match binary_search_by_index(bn, |i| o.cmp(a, m.b_at(i)).reverse()) {
+                        Ok(bm) => {
+                            self.0.push(MergeStep::Merge(an - am - 1, bn - bm - 1));
+                            self.0.push(MergeStep::Collision);
+                            self.0.push(MergeStep::Merge(am, bm));
+                        }
+                        Err(bi) => {
+                            self.0.push(MergeStep::Merge(an - am - 1, bn - bi));
+                            self.0.push(MergeStep::FromA);
+                            self.0.push(MergeStep::Merge(am, bi));
+                        }
+                    }
+                    Some(())
+                }
+                _ if budget == 0 => {
+                    self.0.push(step);
+                    return Poll::Pending;
+                }
+                MergeStep::FromA => o.from_a(m, 1),
+                MergeStep::Collision => o.collision(m),
+                MergeStep::Merge(an, bn) => {
+                    // one of an and bn is 0, so this is a run from a single side
+                    let k = budget.min(an + bn);
+                    if an > k {
+                        self.0.push(MergeStep::Merge(an - k, 0));
+                    } else if bn > k {
+                        self.0.push(MergeStep::Merge(0, bn - k));
+                    }
+                    if an > 0 {
+                        o.from_a(m, k)
+                    } else {
+                        o.from_b(m, k)
+                    }
+                }
+            };
+            if res.is_none() {
+                self.0.clear();
+                return Poll::Ready(());
+            }
+        }
+        Poll::Ready(())
+    }
 }
 
 /// A binary merge operation
@@ -16,66 +167,31 @@ pub(crate) trait MergeStateRead<A, B> {
 /// It is often useful to keep the merge operation and the merge state separate. E.g. computing the
 /// intersection and checking if the intersection exists can be done with the same operation, but
 /// a different merge state. Likewise in-place operations and operations that produce a new entity
-/// can use the same merge operation. THerefore, the merge state is an additional parameter.SortedPairIter
+/// can use the same merge operation. THerefore, the merge state is an additional parameter.
 ///
 /// The operation itself will often be a zero size struct
+#[allow(clippy::wrong_self_convention)]
 pub(crate) trait MergeOperation<A, B, M: MergeStateRead<A, B>> {
+    /// The size ratio below which `merge_adaptive0` switches from bisection to a linear scan
+    const LINEAR_MERGE_RATIO: usize = LINEAR_MERGE_RATIO;
     fn from_a(&self, m: &mut M, n: usize);
     fn from_b(&self, m: &mut M, n: usize);
     fn collision(&self, m: &mut M);
     fn cmp(&self, a: &A, b: &B) -> Ordering;
-    /// merge `an` elements from a and `bn` elements from b into the result
-    fn merge0(&self, m: &mut M, an: usize, bn: usize) {
-        if an == 0 {
-            if bn > 0 {
-                self.from_b(m, bn);
-            }
-        } else if bn == 0 {
-            if an > 0 {
-                self.from_a(m, an);
-            }
-        } else {
-            // neither a nor b are 0
-            let am: usize = an / 2;
-            // pick the center element of a and find the corresponding one in b using binary search
-            let a = &m.a_slice()[am];
-            match m.b_slice()[..bn].binary_search_by(|b| self.cmp(a, b).reverse()) {
-                Ok(bm) => {
-                    // same elements. bm is the index corresponding to am
-                    // merge everything below am with everything below the found element bm
-                    self.merge0(m, am, bm);
-                    // add the elements a(am) and b(bm)
-                    self.collision(m);
-                    // merge everything above a(am) with everything above the found element
-                    self.merge0(m, an - am - 1, bn - bm - 1);
-                }
-                Err(bi) => {
-                    // not found. bi is the insertion point
-                    // merge everything below a(am) with everything below the found insertion point bi
-                    self.merge0(m, am, bi);
-                    // add a(am)
-                    self.from_a(m, 1);
-                    // everything above a(am) with everything above the found insertion point
-                    self.merge0(m, an - am - 1, bn - bi);
-                }
-            }
-        }
-    }
-    fn merge(&self, m: &mut M) {
-        let a1 = m.a_slice().len();
-        let b1 = m.b_slice().len();
-        self.merge0(m, a1, b1);
-    }
 }
 
 /// Basically a convenient to use bool to allow aborting a piece of code early using ?
 /// return `None` to abort and `Some(())` to continue
 pub(crate) type EarlyOut = Option<()>;
 
-/// This is exactly the same as MergeOperation, except that it allows aborting the operation early.
-/// In theory we could have just this operation with no runtime cost, since rust/LLVM will optimize away
-/// the EarlyOut when not used. But it is convenient to have two versions.
+/// This is the same as MergeOperation, except that it allows aborting the operation early.
+///
+/// All merge algorithms are implemented only here. A MergeOperation is run by wrapping it in `NoShortcut`,
+/// and rust/LLVM will optimize away the EarlyOut when it is not used.
+#[allow(clippy::wrong_self_convention)]
 pub(crate) trait ShortcutMergeOperation<A, B, M: MergeStateRead<A, B>> {
+    /// The size ratio below which `merge_adaptive0` switches from bisection to a linear scan
+    const LINEAR_MERGE_RATIO: usize = LINEAR_MERGE_RATIO;
     fn from_a(&self, m: &mut M, n: usize) -> EarlyOut;
     fn from_b(&self, m: &mut M, n: usize) -> EarlyOut;
     fn collision(&self, m: &mut M) -> EarlyOut;
@@ -94,8 +210,8 @@ pub(crate) trait ShortcutMergeOperation<A, B, M: MergeStateRead<A, B>> {
             // neither a nor b are 0
             let am: usize = an / 2;
             // pick the center element of a and find the corresponding one in b using binary search
-            let a = &m.a_slice()[am];
-            match m.b_slice()[..bn].binary_search_by(|b| self.cmp(a, b).reverse()) {
+            let a = m.a_at(am);
+            match binary_search_by_index(bn, |i| self.cmp(a, m.b_at(i)).reverse()) {
                 Ok(bm) => {
                     // same elements. bm is the index corresponding to am
                     // merge everything below am with everything below the found element bm
@@ -118,9 +234,1055 @@ pub(crate) trait ShortcutMergeOperation<A, B, M: MergeStateRead<A, B>> {
         }
         Some(())
     }
+    /// merge `an` elements from a and `bn` elements from b into the result, bisecting the smaller side
+    ///
+    /// `merge0` always bisects a and searches in b, which is good when a is small, but needs several times
+    /// more comparisons when b is small. This picks the smaller of the two remainders to bisect at every step
+    /// and searches in the larger one, so it is as good as `merge0` for a small a, regardless of the order of
+    /// the arguments. Bisecting the larger side instead would be symmetric as well, but as bad as the bad case
+    /// of `merge0`.
+    fn merge_symmetric0(&self, m: &mut M, an: usize, bn: usize) -> EarlyOut {
+        if an == 0 {
+            if bn > 0 {
+                self.from_b(m, bn)?;
+            }
+        } else if bn == 0 {
+            self.from_a(m, an)?;
+        } else if an <= bn {
+            let am: usize = an / 2;
+            let a = m.a_at(am);
+            match binary_search_by_index(bn, |i| self.cmp(a, m.b_at(i)).reverse()) {
+                Ok(bm) => {
+                    self.merge_symmetric0(m, am, bm)?;
+                    self.collision(m)?;
+                    self.merge_symmetric0(m, an - am - 1, bn - bm - 1)?;
+                }
+                Err(bi) => {
+                    self.merge_symmetric0(m, am, bi)?;
+                    self.from_a(m, 1)?;
+                    self.merge_symmetric0(m, an - am - 1, bn - bi)?;
+                }
+            }
+        } else {
+            let bm: usize = bn / 2;
+            // pick the center element of b and find the corresponding one in a using binary search
+            let b = m.b_at(bm);
+            match binary_search_by_index(an, |i| self.cmp(m.a_at(i), b)) {
+                Ok(am) => {
+                    self.merge_symmetric0(m, am, bm)?;
+                    self.collision(m)?;
+                    self.merge_symmetric0(m, an - am - 1, bn - bm - 1)?;
+                }
+                Err(ai) => {
+                    self.merge_symmetric0(m, ai, bm)?;
+                    self.from_b(m, 1)?;
+                    self.merge_symmetric0(m, an - ai, bn - bm - 1)?;
+                }
+            }
+        }
+        Some(())
+    }
+    /// merge `an` elements from a and `bn` elements from b into the result, using galloping
+    ///
+    /// Instead of bisecting, this alternately probes exponentially from the front of b for the first element of a
+    /// and from the front of a for the first element of b. This is faster than `merge0` when the elements interleave
+    /// in long runs close to the current position, e.g. when merging a tiny slice into the start of a huge one.
+    fn merge_galloping0(&self, m: &mut M, an: usize, bn: usize) -> EarlyOut {
+        let mut an = an;
+        let mut bn = bn;
+        while an > 0 && bn > 0 {
+            // take all elements of b that are below the first element of a
+            let a = m.a_at(0);
+            match gallop_search_by(bn, |i| self.cmp(a, m.b_at(i)).reverse()) {
+                Ok(bi) => {
+                    if bi > 0 {
+                        self.from_b(m, bi)?;
+                    }
+                    self.collision(m)?;
+                    an -= 1;
+                    bn -= bi + 1;
+                }
+                Err(bi) => {
+                    if bi > 0 {
+                        self.from_b(m, bi)?;
+                    }
+                    bn -= bi;
+                }
+            }
+            if an == 0 || bn == 0 {
+                break;
+            }
+            // take all elements of a that are below the first element of b
+            let b = m.b_at(0);
+            match gallop_search_by(an, |i| self.cmp(m.a_at(i), b)) {
+                Ok(ai) => {
+                    if ai > 0 {
+                        self.from_a(m, ai)?;
+                    }
+                    self.collision(m)?;
+                    an -= ai + 1;
+                    bn -= 1;
+                }
+                Err(ai) => {
+                    if ai > 0 {
+                        self.from_a(m, ai)?;
+                    }
+                    an -= ai;
+                }
+            }
+        }
+        if an > 0 {
+            self.from_a(m, an)?;
+        }
+        if bn > 0 {
+            self.from_b(m, bn)?;
+        }
+        Some(())
+    }
+    /// merge `an` elements from a and `bn` elements from b into the result, without recursion
+    ///
+    /// This does exactly the same calls as `merge0` in exactly the same order, but keeps the pending work
+    /// on an explicit stack instead of the call stack, so the depth of the bisection is not limited by the
+    /// size of the thread stack.
+    fn merge_iterative0(&self, m: &mut M, an: usize, bn: usize) -> EarlyOut {
+        let mut stack = vec![MergeStep::Merge(an, bn)];
+        while let Some(step) = stack.pop() {
+            match step {
+                MergeStep::FromA => self.from_a(m, 1)?,
+                MergeStep::Collision => self.collision(m)?,
+                MergeStep::Merge(an, bn) => {
+                    if an == 0 {
+                        if bn > 0 {
+                            self.from_b(m, bn)?;
+                        }
+                    } else if bn == 0 {
+                        self.from_a(m, an)?;
+                    } else {
+                        let am: usize = an / 2;
+                        let a = m.a_at(am);
+                        // push in reverse order, so the lower half gets processed first
+                        match binary_search_by_index(bn, |i| self.cmp(a, m.b_at(i)).reverse()) {
+                            Ok(bm) => {
+                                stack.push(MergeStep::Merge(an - am - 1, bn - bm - 1));
+                                stack.push(MergeStep::Collision);
+                                stack.push(MergeStep::Merge(am, bm));
+                            }
+                            Err(bi) => {
+                                stack.push(MergeStep::Merge(an - am - 1, bn - bi));
+                                stack.push(MergeStep::FromA);
+                                stack.push(MergeStep::Merge(am, bi));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Some(())
+    }
+    /// merge `an` elements from a and `bn` elements from b into the result, using a linear scan
+    ///
+    /// This compares the first elements of both remainders and takes the smaller one, taking runs from the same side
+    /// in a single call. It needs O(an + bn) comparisons, which is more than `merge0` when the sizes are very
+    /// different, but it has less overhead and better cache behavior when they are similar.
+    fn merge_linear0(&self, m: &mut M, an: usize, bn: usize) -> EarlyOut {
+        let mut an = an;
+        let mut bn = bn;
+        while an > 0 && bn > 0 {
+            match self.cmp(m.a_at(0), m.b_at(0)) {
+                Ordering::Less => {
+                    // count the elements of a that are below the first element of b
+                    let b = m.b_at(0);
+                    let n = (1..an)
+                        .take_while(|&i| self.cmp(m.a_at(i), b) == Ordering::Less)
+                        .count()
+                        + 1;
+                    self.from_a(m, n)?;
+                    an -= n;
+                }
+                Ordering::Greater => {
+                    // count the elements of b that are below the first element of a
+                    let a = m.a_at(0);
+                    let n = (1..bn)
+                        .take_while(|&i| self.cmp(a, m.b_at(i)) == Ordering::Greater)
+                        .count()
+                        + 1;
+                    self.from_b(m, n)?;
+                    bn -= n;
+                }
+                Ordering::Equal => {
+                    self.collision(m)?;
+                    an -= 1;
+                    bn -= 1;
+                }
+            }
+        }
+        if an > 0 {
+            self.from_a(m, an)?;
+        }
+        if bn > 0 {
+            self.from_b(m, bn)?;
+        }
+        Some(())
+    }
+    /// true if the next `an` elements of a and `bn` elements of b are probably identical
+    ///
+    /// This compares the first and last elements and a few evenly spaced interior positions, so it is cheap
+    /// but can give false positives. It is only used to pick a strategy, never for the result.
+    fn looks_identical(&self, m: &M, an: usize, bn: usize) -> bool {
+        an == bn
+            && an > 0
+            && (0..=IDENTICAL_SAMPLES)
+                .map(|i| i * (an - 1) / IDENTICAL_SAMPLES)
+                .all(|i| self.cmp(m.a_at(i), m.b_at(i)) == Ordering::Equal)
+    }
+    /// merge `an` elements from a and `bn` elements from b into the result, with a fast path for nearly
+    /// identical inputs
+    ///
+    /// If the inputs look identical according to `looks_identical`, this uses `merge_linear0`, which
+    /// verifies the assumption while merging, so the result is correct even if the sample was misleading.
+    /// For nearly identical inputs, the linear scan takes one comparison per element and long runs of
+    /// collisions, which is cheaper than bisection. Otherwise this falls back to `merge0`.
+    fn merge_similar0(&self, m: &mut M, an: usize, bn: usize) -> EarlyOut {
+        if self.looks_identical(m, an, bn) {
+            self.merge_linear0(m, an, bn)
+        } else {
+            self.merge0(m, an, bn)
+        }
+    }
+    /// merge `an` elements from a and `bn` elements from b into the result, using bisection or a linear scan
+    ///
+    /// This bisects like `merge0`, but switches to `merge_linear0` as soon as the larger of the two remainders is at
+    /// most `LINEAR_MERGE_RATIO` times the size of the smaller one.
+    fn merge_adaptive0(&self, m: &mut M, an: usize, bn: usize) -> EarlyOut {
+        if an == 0 {
+            if bn > 0 {
+                self.from_b(m, bn)?;
+            }
+        } else if bn == 0 {
+            self.from_a(m, an)?;
+        } else if an.max(bn) <= an.min(bn).saturating_mul(Self::LINEAR_MERGE_RATIO) {
+            self.merge_linear0(m, an, bn)?;
+        } else {
+            let am: usize = an / 2;
+            let a = m.a_at(am);
+            match binary_search_by_index(bn, |i| self.cmp(a, m.b_at(i)).reverse()) {
+                Ok(bm) => {
+                    self.merge_adaptive0(m, am, bm)?;
+                    self.collision(m)?;
+                    self.merge_adaptive0(m, an - am - 1, bn - bm - 1)?;
+                }
+                Err(bi) => {
+                    self.merge_adaptive0(m, am, bi)?;
+                    self.from_a(m, 1)?;
+                    self.merge_adaptive0(m, an - am - 1, bn - bi)?;
+                }
+            }
+        }
+        Some(())
+    }
+    /// merge all elements, using `merge_similar0` for large inputs of equal size and `merge_symmetric0` otherwise
+    ///
+    /// `merge_symmetric0` does the same as `merge0` if a is the smaller input, and saves comparisons if it is not.
+    fn merge(&self, m: &mut M) {
+        let a1 = m.a_len();
+        let b1 = m.b_len();
+        if a1 == b1 && a1 >= IDENTICAL_MIN_LEN {
+            self.merge_similar0(m, a1, b1);
+        } else {
+            self.merge_symmetric0(m, a1, b1);
+        }
+    }
+    #[cfg(test)]
+    fn merge_symmetric(&self, m: &mut M) {
+        let a1 = m.a_len();
+        let b1 = m.b_len();
+        self.merge_symmetric0(m, a1, b1);
+    }
+    #[cfg(test)]
+    fn merge_similar(&self, m: &mut M) {
+        let a1 = m.a_len();
+        let b1 = m.b_len();
+        self.merge_similar0(m, a1, b1);
+    }
+    fn merge_galloping(&self, m: &mut M) {
+        let a1 = m.a_len();
+        let b1 = m.b_len();
+        self.merge_galloping0(m, a1, b1);
+    }
+    fn merge_iterative(&self, m: &mut M) {
+        let a1 = m.a_len();
+        let b1 = m.b_len();
+        self.merge_iterative0(m, a1, b1);
+    }
+    fn merge_adaptive(&self, m: &mut M) {
+        let a1 = m.a_len();
+        let b1 = m.b_len();
+        self.merge_adaptive0(m, a1, b1);
+    }
+}
+
+/// Adapts a MergeOperation to a ShortcutMergeOperation that never aborts
+///
+/// This can not be a blanket impl, since that would conflict with all the direct impls of ShortcutMergeOperation.
+pub(crate) struct NoShortcut<O>(pub O);
+
+impl<A, B, M, O> ShortcutMergeOperation<A, B, M> for NoShortcut<O>
+where
+    M: MergeStateRead<A, B>,
+    O: MergeOperation<A, B, M>,
+{
+    const LINEAR_MERGE_RATIO: usize = O::LINEAR_MERGE_RATIO;
+    fn from_a(&self, m: &mut M, n: usize) -> EarlyOut {
+        self.0.from_a(m, n);
+        Some(())
+    }
+    fn from_b(&self, m: &mut M, n: usize) -> EarlyOut {
+        self.0.from_b(m, n);
+        Some(())
+    }
+    fn collision(&self, m: &mut M) -> EarlyOut {
+        self.0.collision(m);
+        Some(())
+    }
+    fn cmp(&self, a: &A, b: &B) -> Ordering {
+        self.0.cmp(a, b)
+    }
+}
+
+/// The read part of the merge state for a three-way merge, analogous to `MergeStateRead`
+pub(crate) trait MergeStateRead3<A, B, C> {
+    /// The remaining data in a
+    fn a_slice(&self) -> &[A];
+    /// The remaining data in b
+    fn b_slice(&self) -> &[B];
+    /// The remaining data in c
+    fn c_slice(&self) -> &[C];
+}
+
+/// A three-way merge operation, analogous to `MergeOperation`
+///
+/// The collision callbacks consume one element from each of the named inputs, e.g. `collision_ab` is
+/// called when an element of a and an element of b are equal and there is no equal element in c.
+#[allow(clippy::wrong_self_convention)]
+pub(crate) trait MergeOperation3<A, B, C, M: MergeStateRead3<A, B, C>> {
+    fn from_a(&self, m: &mut M, n: usize);
+    fn from_b(&self, m: &mut M, n: usize);
+    fn from_c(&self, m: &mut M, n: usize);
+    fn collision_ab(&self, m: &mut M);
+    fn collision_ac(&self, m: &mut M);
+    fn collision_bc(&self, m: &mut M);
+    fn collision_abc(&self, m: &mut M);
+    fn cmp_ab(&self, a: &A, b: &B) -> Ordering;
+    fn cmp_ac(&self, a: &A, c: &C) -> Ordering;
+    fn cmp_bc(&self, b: &B, c: &C) -> Ordering;
+    /// consume the pivot element, together with the equal elements of the other inputs if present
+    fn pivot(&self, m: &mut M, a: bool, b: bool, c: bool) {
+        match (a, b, c) {
+            (true, false, false) => self.from_a(m, 1),
+            (false, true, false) => self.from_b(m, 1),
+            (false, false, true) => self.from_c(m, 1),
+            (true, true, false) => self.collision_ab(m),
+            (true, false, true) => self.collision_ac(m),
+            (false, true, true) => self.collision_bc(m),
+            (true, true, true) => self.collision_abc(m),
+            (false, false, false) => unreachable!(),
+        }
+    }
+    /// merge `an` elements from a, `bn` elements from b and `cn` elements from c into the result
+    ///
+    /// This picks the center element of the largest of the three remainders and finds the corresponding
+    /// elements in the other two using binary search, then recurses into the parts below and above.
+    fn merge0(&self, m: &mut M, an: usize, bn: usize, cn: usize) {
+        // if one of the remainders is empty, this degenerates to a two-way merge
+        if an == 0 && bn == 0 {
+            if cn > 0 {
+                self.from_c(m, cn);
+            }
+        } else if an == 0 && cn == 0 {
+            self.from_b(m, bn);
+        } else if bn == 0 && cn == 0 {
+            self.from_a(m, an);
+        } else if an >= bn && an >= cn {
+            let am = an / 2;
+            let a = &m.a_slice()[am];
+            let br = m.b_slice()[..bn].binary_search_by(|b| self.cmp_ab(a, b).reverse());
+            let cr = m.c_slice()[..cn].binary_search_by(|c| self.cmp_ac(a, c).reverse());
+            let (bm, bf) = split(br);
+            let (cm, cf) = split(cr);
+            self.merge0(m, am, bm, cm);
+            self.pivot(m, true, bf, cf);
+            self.merge0(m, an - am - 1, bn - bm - bf as usize, cn - cm - cf as usize);
+        } else if bn >= cn {
+            let bm = bn / 2;
+            let b = &m.b_slice()[bm];
+            let ar = m.a_slice()[..an].binary_search_by(|a| self.cmp_ab(a, b));
+            let cr = m.c_slice()[..cn].binary_search_by(|c| self.cmp_bc(b, c).reverse());
+            let (am, af) = split(ar);
+            let (cm, cf) = split(cr);
+            self.merge0(m, am, bm, cm);
+            self.pivot(m, af, true, cf);
+            self.merge0(m, an - am - af as usize, bn - bm - 1, cn - cm - cf as usize);
+        } else {
+            let cm = cn / 2;
+            let c = &m.c_slice()[cm];
+            let ar = m.a_slice()[..an].binary_search_by(|a| self.cmp_ac(a, c));
+            let br = m.b_slice()[..bn].binary_search_by(|b| self.cmp_bc(b, c));
+            let (am, af) = split(ar);
+            let (bm, bf) = split(br);
+            self.merge0(m, am, bm, cm);
+            self.pivot(m, af, bf, true);
+            self.merge0(m, an - am - af as usize, bn - bm - bf as usize, cn - cm - 1);
+        }
+    }
     fn merge(&self, m: &mut M) {
         let a1 = m.a_slice().len();
         let b1 = m.b_slice().len();
-        self.merge0(m, a1, b1);
+        let c1 = m.c_slice().len();
+        self.merge0(m, a1, b1, c1);
+    }
+}
+
+/// the number of elements below a binary search result, and whether an equal element was found
+fn split(r: Result<usize, usize>) -> (usize, bool) {
+    match r {
+        Ok(i) => (i, true),
+        Err(i) => (i, false),
+    }
+}
+
+/// Wraps a merge operation, but uses the given function for comparison instead of the operation's `cmp`
+///
+/// This allows e.g. to merge by a derived key without having to implement a new operation.
+///
+/// Precondition: both inputs must be sorted and deduplicated with respect to the comparison function.
+/// If they are not, the merge will silently produce garbage.
+pub(crate) struct CmpMergeOperation<O, F> {
+    op: O,
+    cmp: F,
+}
+
+impl<O, F> CmpMergeOperation<O, F> {
+    pub fn new(op: O, cmp: F) -> Self {
+        Self { op, cmp }
+    }
+}
+
+impl<A, B, M, O, F> ShortcutMergeOperation<A, B, M> for CmpMergeOperation<O, F>
+where
+    M: MergeStateRead<A, B>,
+    O: ShortcutMergeOperation<A, B, M>,
+    F: Fn(&A, &B) -> Ordering,
+{
+    fn from_a(&self, m: &mut M, n: usize) -> EarlyOut {
+        self.op.from_a(m, n)
+    }
+    fn from_b(&self, m: &mut M, n: usize) -> EarlyOut {
+        self.op.from_b(m, n)
+    }
+    fn collision(&self, m: &mut M) -> EarlyOut {
+        self.op.collision(m)
+    }
+    fn cmp(&self, a: &A, b: &B) -> Ordering {
+        (self.cmp)(a, b)
+    }
+}
+
+/// Wraps a merge operation, but merges inputs that are sorted in descending instead of ascending order
+///
+/// This just reverses the comparison of the wrapped operation. It is used by `DescendingVecSet`.
+pub(crate) struct DescendingMergeOperation<O>(pub O);
+
+impl<A, B, M, O> ShortcutMergeOperation<A, B, M> for DescendingMergeOperation<O>
+where
+    M: MergeStateRead<A, B>,
+    O: ShortcutMergeOperation<A, B, M>,
+{
+    fn from_a(&self, m: &mut M, n: usize) -> EarlyOut {
+        self.0.from_a(m, n)
+    }
+    fn from_b(&self, m: &mut M, n: usize) -> EarlyOut {
+        self.0.from_b(m, n)
+    }
+    fn collision(&self, m: &mut M) -> EarlyOut {
+        self.0.collision(m)
+    }
+    fn cmp(&self, a: &A, b: &B) -> Ordering {
+        self.0.cmp(a, b).reverse()
+    }
+}
+
+/// Wraps a merge operation and counts how many comparisons it performs
+///
+/// This is useful to check how many comparisons the binary merge saves compared to a linear merge for a
+/// given input. The count is kept in a `Cell`, since all merge callbacks take `&self`.
+pub(crate) struct CountingMergeOperation<O> {
+    op: O,
+    comparisons: Cell<usize>,
+}
+
+impl<O> CountingMergeOperation<O> {
+    pub fn new(op: O) -> Self {
+        Self {
+            op,
+            comparisons: Cell::new(0),
+        }
+    }
+
+    /// The number of comparisons performed so far
+    pub fn comparisons(&self) -> usize {
+        self.comparisons.get()
+    }
+}
+
+impl<A, B, M, O> ShortcutMergeOperation<A, B, M> for CountingMergeOperation<O>
+where
+    M: MergeStateRead<A, B>,
+    O: ShortcutMergeOperation<A, B, M>,
+{
+    const LINEAR_MERGE_RATIO: usize = O::LINEAR_MERGE_RATIO;
+    fn from_a(&self, m: &mut M, n: usize) -> EarlyOut {
+        self.op.from_a(m, n)
+    }
+    fn from_b(&self, m: &mut M, n: usize) -> EarlyOut {
+        self.op.from_b(m, n)
+    }
+    fn collision(&self, m: &mut M) -> EarlyOut {
+        self.op.collision(m)
+    }
+    fn cmp(&self, a: &A, b: &B) -> Ordering {
+        self.comparisons.set(self.comparisons.get() + 1);
+        self.op.cmp(a, b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merge_state::{
+        ChunkedMergeState, CopyVecMergeState, MergeStateMut, VecMergeState, VecMergeState3,
+    };
+    use crate::vec_set::{union_runs, MergeStrategy, SetIntersectionOp, SetUnionOp};
+    use crate::VecSet;
+    use quickcheck_macros::quickcheck;
+    use std::cell::{Cell, RefCell};
+    use std::collections::{BTreeSet, VecDeque};
+
+    /// a plain union operation
+    struct UnionOp;
+
+    impl<T: Ord, M: MergeStateMut<T, T>> ShortcutMergeOperation<T, T, M> for UnionOp {
+        fn cmp(&self, a: &T, b: &T) -> Ordering {
+            a.cmp(b)
+        }
+        fn from_a(&self, m: &mut M, n: usize) -> EarlyOut {
+            m.advance_a(n, true)
+        }
+        fn from_b(&self, m: &mut M, n: usize) -> EarlyOut {
+            m.advance_b(n, true)
+        }
+        fn collision(&self, m: &mut M) -> EarlyOut {
+            m.advance_a(1, true)?;
+            m.advance_b(1, false)
+        }
+    }
+
+    /// an operation that records the sequence of calls
+    ///
+    /// this implements both MergeOperation and ShortcutMergeOperation, to check that they behave the same
+    #[derive(Default)]
+    struct RecordingOp(RefCell<Vec<(char, usize)>>);
+
+    impl<T: Ord, M: MergeStateMut<T, T>> ShortcutMergeOperation<T, T, M> for RecordingOp {
+        fn cmp(&self, a: &T, b: &T) -> Ordering {
+            a.cmp(b)
+        }
+        fn from_a(&self, m: &mut M, n: usize) -> EarlyOut {
+            self.0.borrow_mut().push(('a', n));
+            m.advance_a(n, true)
+        }
+        fn from_b(&self, m: &mut M, n: usize) -> EarlyOut {
+            self.0.borrow_mut().push(('b', n));
+            m.advance_b(n, true)
+        }
+        fn collision(&self, m: &mut M) -> EarlyOut {
+            self.0.borrow_mut().push(('c', 1));
+            m.advance_a(1, true)?;
+            m.advance_b(1, false)
+        }
+    }
+
+    impl<T: Ord, M: MergeStateMut<T, T>> MergeOperation<T, T, M> for RecordingOp {
+        fn cmp(&self, a: &T, b: &T) -> Ordering {
+            a.cmp(b)
+        }
+        fn from_a(&self, m: &mut M, n: usize) {
+            self.0.borrow_mut().push(('a', n));
+            m.advance_a(n, true);
+        }
+        fn from_b(&self, m: &mut M, n: usize) {
+            self.0.borrow_mut().push(('b', n));
+            m.advance_b(n, true);
+        }
+        fn collision(&self, m: &mut M) {
+            self.0.borrow_mut().push(('c', 1));
+            m.advance_a(1, true);
+            m.advance_b(1, false);
+        }
+    }
+
+    /// a merge state over two ring buffers, which are not necessarily contiguous
+    struct VecDequeMergeState<'a, T> {
+        a: &'a VecDeque<T>,
+        b: &'a VecDeque<T>,
+        ai: usize,
+        bi: usize,
+        r: Vec<T>,
+    }
+
+    impl<'a, T> MergeStateRead<T, T> for VecDequeMergeState<'a, T> {
+        fn a_len(&self) -> usize {
+            self.a.len() - self.ai
+        }
+        fn b_len(&self) -> usize {
+            self.b.len() - self.bi
+        }
+        fn a_at(&self, i: usize) -> &T {
+            &self.a[self.ai + i]
+        }
+        fn b_at(&self, i: usize) -> &T {
+            &self.b[self.bi + i]
+        }
+    }
+
+    impl<'a, T: Clone> MergeStateMut<T, T> for VecDequeMergeState<'a, T> {
+        fn advance_a(&mut self, n: usize, take: bool) -> EarlyOut {
+            if take {
+                self.r.extend(self.a.range(self.ai..self.ai + n).cloned());
+            }
+            self.ai += n;
+            Some(())
+        }
+        fn advance_b(&mut self, n: usize, take: bool) -> EarlyOut {
+            if take {
+                self.r.extend(self.b.range(self.bi..self.bi + n).cloned());
+            }
+            self.bi += n;
+            Some(())
+        }
+    }
+
+    /// a ring buffer containing the elements of x, wrapped around in the middle
+    fn wrapped_deque(x: &BTreeSet<i64>) -> VecDeque<i64> {
+        let mut r = VecDeque::with_capacity(x.len());
+        let v: Vec<i64> = x.iter().cloned().collect();
+        let (lo, hi) = v.split_at(v.len() / 2);
+        r.extend(hi.iter().cloned());
+        for x in lo.iter().rev() {
+            r.push_front(*x);
+        }
+        r
+    }
+
+    fn vec_deque_union_consistent(a: BTreeSet<i64>, b: BTreeSet<i64>) -> bool {
+        let ad = wrapped_deque(&a);
+        let bd = wrapped_deque(&b);
+        let mut state = VecDequeMergeState {
+            a: &ad,
+            b: &bd,
+            ai: 0,
+            bi: 0,
+            r: Vec::new(),
+        };
+        UnionOp.merge(&mut state);
+        let expected: Vec<i64> = a.union(&b).cloned().collect();
+        state.r == expected
+    }
+
+    #[quickcheck]
+    fn vec_deque_union(a: BTreeSet<i64>, b: BTreeSet<i64>) -> bool {
+        vec_deque_union_consistent(a, b)
+    }
+
+    #[test]
+    fn vec_deque_non_contiguous() {
+        let x: BTreeSet<i64> = (0..100).collect();
+        let d = wrapped_deque(&x);
+        assert!(!d.as_slices().1.is_empty());
+        assert!(vec_deque_union_consistent(x, (50..150).collect()));
+    }
+
+    fn galloping_union(a: &[i64], b: &[i64]) -> (Vec<i64>, usize) {
+        let op = CountingMergeOperation::new(UnionOp);
+        let mut state = VecMergeState::new(a, b, Vec::new());
+        op.merge_galloping(&mut state);
+        (state.into_vec(), op.comparisons())
+    }
+
+    fn adaptive_union(a: &[i64], b: &[i64]) -> (Vec<i64>, usize) {
+        let op = CountingMergeOperation::new(UnionOp);
+        let mut state = VecMergeState::new(a, b, Vec::new());
+        op.merge_adaptive(&mut state);
+        (state.into_vec(), op.comparisons())
+    }
+
+    fn symmetric_union(a: &[i64], b: &[i64]) -> (Vec<i64>, usize) {
+        let op = CountingMergeOperation::new(UnionOp);
+        let mut state = VecMergeState::new(a, b, Vec::new());
+        op.merge_symmetric(&mut state);
+        (state.into_vec(), op.comparisons())
+    }
+
+    fn similar_union(a: &[i64], b: &[i64]) -> (Vec<i64>, usize) {
+        let op = CountingMergeOperation::new(UnionOp);
+        let mut state = VecMergeState::new(a, b, Vec::new());
+        op.merge_similar(&mut state);
+        (state.into_vec(), op.comparisons())
+    }
+
+    fn linear_union(a: &[i64], b: &[i64]) -> (Vec<i64>, usize) {
+        let op = CountingMergeOperation::new(UnionOp);
+        let mut state = VecMergeState::new(a, b, Vec::new());
+        op.merge_linear0(&mut state, a.len(), b.len());
+        (state.into_vec(), op.comparisons())
+    }
+
+    fn binary_union(a: &[i64], b: &[i64]) -> (Vec<i64>, usize) {
+        let op = CountingMergeOperation::new(UnionOp);
+        let mut state = VecMergeState::new(a, b, Vec::new());
+        op.merge0(&mut state, a.len(), b.len());
+        (state.into_vec(), op.comparisons())
+    }
+
+    #[quickcheck]
+    fn gallop_search_by_consistent(x: BTreeSet<i64>, e: i64) -> bool {
+        let x: Vec<i64> = x.into_iter().collect();
+        gallop_search_by(x.len(), |i| x[i].cmp(&e)) == x.binary_search(&e)
+            && binary_search_by_index(x.len(), |i| x[i].cmp(&e)) == x.binary_search(&e)
+    }
+
+    #[quickcheck]
+    fn galloping_binary_consistent(a: BTreeSet<i64>, b: BTreeSet<i64>) -> bool {
+        let a: Vec<i64> = a.into_iter().collect();
+        let b: Vec<i64> = b.into_iter().collect();
+        galloping_union(&a, &b).0 == binary_union(&a, &b).0
+    }
+
+    #[quickcheck]
+    fn adaptive_linear_binary_consistent(a: BTreeSet<i64>, b: BTreeSet<i64>) -> bool {
+        let a: Vec<i64> = a.into_iter().collect();
+        let b: Vec<i64> = b.into_iter().collect();
+        let (r, _) = binary_union(&a, &b);
+        adaptive_union(&a, &b).0 == r && linear_union(&a, &b).0 == r
+    }
+
+    #[quickcheck]
+    fn symmetric_binary_consistent(a: BTreeSet<i64>, b: BTreeSet<i64>) -> bool {
+        let a: Vec<i64> = a.into_iter().collect();
+        let b: Vec<i64> = b.into_iter().collect();
+        let (r, _) = binary_union(&a, &b);
+        symmetric_union(&a, &b).0 == r && symmetric_union(&b, &a).0 == r
+    }
+
+    #[test]
+    fn symmetric_comparisons() {
+        let large: Vec<i64> = (0..100_000).collect();
+        let small: Vec<i64> = (0..100).map(|x| x * 1000 + 500).collect();
+        let (_, binary_small_large) = binary_union(&small, &large);
+        let (_, binary_large_small) = binary_union(&large, &small);
+        let (_, symmetric_small_large) = symmetric_union(&small, &large);
+        let (_, symmetric_large_small) = symmetric_union(&large, &small);
+        // merge0 is only good if the first argument is the small one
+        assert!(binary_small_large < binary_large_small);
+        assert_eq!(symmetric_small_large, binary_small_large);
+        assert_eq!(symmetric_large_small, binary_small_large);
+    }
+
+    #[quickcheck]
+    fn similar_binary_consistent(a: BTreeSet<i64>, changes: Vec<(u8, bool)>) -> bool {
+        // b is a with a few elements added and removed, so the fast path is taken often
+        let mut b = a.clone();
+        for (i, add) in changes.iter().take(3) {
+            let x = *i as i64;
+            if *add {
+                b.insert(x);
+            } else {
+                b.remove(&x);
+            }
+        }
+        let a: Vec<i64> = a.into_iter().collect();
+        let b: Vec<i64> = b.into_iter().collect();
+        let (r, _) = binary_union(&a, &b);
+        let mut state = VecMergeState::new(&a, &b, Vec::new());
+        UnionOp.merge0(&mut state, a.len(), b.len());
+        similar_union(&a, &b).0 == r && state.into_vec() == r
+    }
+
+    #[test]
+    fn similar_fast_path() {
+        let a: Vec<i64> = (0..1000).map(|x| x * 2).collect();
+        let mut b = a.clone();
+        b[500] += 1;
+        let (r, comparisons) = similar_union(&a, &b);
+        let mut expected = a.clone();
+        expected.insert(501, 1001);
+        assert_eq!(r, expected);
+        // the samples plus about one comparison per element
+        assert!(comparisons <= a.len() + IDENTICAL_SAMPLES + 4);
+        assert!(comparisons < binary_union(&a, &b).1);
+        // the first sample mismatches, so this falls back to bisection after a single comparison
+        b[0] = -1;
+        let (r, comparisons) = similar_union(&a, &b);
+        let (expected, binary_comparisons) = binary_union(&a, &b);
+        assert_eq!(r, expected);
+        assert_eq!(comparisons, binary_comparisons + 1);
+    }
+
+    #[quickcheck]
+    fn iterative_recursive_identical_calls(a: BTreeSet<i64>, b: BTreeSet<i64>) -> bool {
+        let a: Vec<i64> = a.into_iter().collect();
+        let b: Vec<i64> = b.into_iter().collect();
+        let recursive = RecordingOp::default();
+        let mut state = VecMergeState::new(&a, &b, Vec::new());
+        recursive.merge0(&mut state, a.len(), b.len());
+        let iterative = RecordingOp::default();
+        let mut state = VecMergeState::new(&a, &b, Vec::new());
+        iterative.merge_iterative(&mut state);
+        recursive.0.into_inner() == iterative.0.into_inner()
+    }
+
+    #[quickcheck]
+    fn no_shortcut_identical_calls(a: BTreeSet<i64>, b: BTreeSet<i64>) -> bool {
+        let a: Vec<i64> = a.into_iter().collect();
+        let b: Vec<i64> = b.into_iter().collect();
+        let shortcut = RecordingOp::default();
+        let mut state = VecMergeState::new(&a, &b, Vec::new());
+        shortcut.merge(&mut state);
+        let r1 = state.into_vec();
+        let no_shortcut = NoShortcut(RecordingOp::default());
+        let mut state = VecMergeState::new(&a, &b, Vec::new());
+        no_shortcut.merge(&mut state);
+        let r2 = state.into_vec();
+        r1 == r2 && shortcut.0.into_inner() == no_shortcut.0 .0.into_inner()
+    }
+
+    #[test]
+    fn iterative_large() {
+        // run on a thread with a small stack, so only the explicit work stack can grow
+        let r = std::thread::Builder::new()
+            .stack_size(64 * 1024)
+            .spawn(|| {
+                let a: VecSet<i64> = (0..10_000_000).map(|x| x * 2).collect();
+                let b: VecSet<i64> = (0..10_000_000).map(|x| x * 2 + 1).collect();
+                a.union_with_strategy(&b, MergeStrategy::Iterative)
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(r.len(), 20_000_000);
+        assert!(r.as_slice().windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn comparisons_skewed() {
+        // merging m elements into n elements should take O(m log(n/m)) comparisons
+        let n = 1 << 20;
+        let b: Vec<i64> = (0..n).map(|x| x * 2).collect();
+        for m in [1, 4, 16, 64, 256, 1024] {
+            let step = (n / m) * 2;
+            let a: Vec<i64> = (0..m).map(|x| x * step + 1).collect();
+            let (r, c) = binary_union(&a, &b);
+            assert_eq!(r.len() as i64, n + m);
+            let log = 64 - ((n / m) as u64).leading_zeros() as usize;
+            let bound = 2 * (m as usize) * (log + 1);
+            assert!(c <= bound, "m={} {} comparisons, bound {}", m, c, bound);
+            // a linear merge needs about n + m comparisons
+            assert!(c * 4 < (n + m) as usize);
+        }
+    }
+
+    #[test]
+    fn galloping_tiny_into_huge() {
+        let a: Vec<i64> = (0..10).map(|x| x * 100_000 + 1).collect();
+        let b: Vec<i64> = (0..1_000_000).collect();
+        let (r1, c1) = galloping_union(&a, &b);
+        let (r2, c2) = binary_union(&a, &b);
+        assert_eq!(r1, r2);
+        // at most one gallop per element of a, each taking O(log(n/m)) comparisons
+        assert!(c1 < 10 * 2 * 20, "{} comparisons", c1);
+        // the bisection needs about log2(n) comparisons per element of a
+        assert!(c2 < 10 * 20, "{} comparisons", c2);
+    }
+
+    /// expands runs into the individual values
+    fn expand_runs(runs: &[(u32, u32)]) -> BTreeSet<u32> {
+        runs.iter()
+            .flat_map(|&(start, len)| start..start + len)
+            .collect()
+    }
+
+    /// turn arbitrary values into runs sorted by distinct start values. The runs may overlap.
+    fn make_runs(raw: Vec<(u16, u8)>) -> Vec<(u32, u32)> {
+        let mut runs: Vec<(u32, u32)> = raw
+            .into_iter()
+            .map(|(start, len)| (start as u32 % 1000, len as u32 % 20))
+            .collect();
+        runs.sort();
+        runs.dedup_by_key(|x| x.0);
+        runs
+    }
+
+    fn rle_union_consistent(a: Vec<(u32, u32)>, b: Vec<(u32, u32)>) -> bool {
+        let r = union_runs(&a, &b);
+        let expected: BTreeSet<u32> = expand_runs(&a).union(&expand_runs(&b)).cloned().collect();
+        // the result must be normalized: sorted, non-empty, non-overlapping and non-touching runs
+        let normalized =
+            r.iter().all(|x| x.1 > 0) && r.windows(2).all(|w| w[0].0 + w[0].1 < w[1].0);
+        normalized && expand_runs(&r) == expected
+    }
+
+    #[quickcheck]
+    fn rle_union(a: Vec<(u16, u8)>, b: Vec<(u16, u8)>) -> bool {
+        rle_union_consistent(make_runs(a), make_runs(b))
+    }
+
+    #[test]
+    fn rle_union_cases() {
+        // overlapping, touching, identical start and contained runs
+        let a = vec![(0, 5), (10, 2), (20, 10), (40, 1)];
+        let b = vec![(3, 4), (12, 3), (20, 5), (22, 2), (50, 0)];
+        assert_eq!(union_runs(&a, &b), vec![(0, 7), (10, 5), (20, 10), (40, 1)]);
+        assert!(rle_union_consistent(a, b));
+        assert_eq!(union_runs(&[(1u32, 2u32)], &[]), vec![(1, 2)]);
+        assert!(union_runs::<u32>(&[], &[]).is_empty());
+    }
+
+    fn chunked_merge<O>(a: &[u8], b: &[u8], chunk_size: usize, o: O) -> (Vec<u8>, usize)
+    where
+        O: Copy + for<'x> ShortcutMergeOperation<u8, u8, VecMergeState<'x, u8, u8, u8>>,
+    {
+        let a = a.iter().cloned().map(Ok::<u8, ()>);
+        let b = b.iter().cloned().map(Ok::<u8, ()>);
+        let mut state = ChunkedMergeState::new(a, b, chunk_size);
+        let mut window = Vec::new();
+        let mut result = Vec::new();
+        let mut windows = 0;
+        while state.merge_window(o, &mut window).unwrap() {
+            result.extend_from_slice(&window);
+            windows += 1;
+        }
+        (result, windows)
+    }
+
+    #[quickcheck]
+    fn chunked_merge_consistent(a: BTreeSet<u8>, b: BTreeSet<u8>, chunk_size: u8) -> bool {
+        let chunk_size = chunk_size as usize % 8 + 1;
+        let a: Vec<u8> = a.into_iter().collect();
+        let b: Vec<u8> = b.into_iter().collect();
+        let union = VecMergeState::merge_shortcut(&a, &b, SetUnionOp);
+        let intersection = VecMergeState::merge_shortcut(&a, &b, SetIntersectionOp);
+        chunked_merge(&a, &b, chunk_size, SetUnionOp).0 == union
+            && chunked_merge(&a, &b, chunk_size, SetIntersectionOp).0 == intersection
+    }
+
+    #[test]
+    fn chunked_merge_cases() {
+        let a = [1, 3, 5, 7, 9, 11];
+        let b = [2, 3, 4, 10];
+        let union = vec![1, 2, 3, 4, 5, 7, 9, 10, 11];
+        // windows end at 3, 7 and the end of both sources
+        assert_eq!(chunked_merge(&a, &b, 2, SetUnionOp), (union.clone(), 3));
+        assert_eq!(chunked_merge(&a, &b, 100, SetUnionOp), (union, 1));
+        assert_eq!(chunked_merge(&a, &b, 1, SetIntersectionOp).0, vec![3]);
+        assert_eq!(chunked_merge(&[], &[], 1, SetUnionOp), (vec![], 0));
+        // errors from a source are returned
+        let a = vec![Ok(1), Err("broken"), Ok(3)].into_iter();
+        let b = vec![Ok(2)].into_iter();
+        let mut state = ChunkedMergeState::new(a, b, 4);
+        assert_eq!(
+            state.merge_window(SetUnionOp, &mut Vec::new()),
+            Err("broken")
+        );
+    }
+
+    #[quickcheck]
+    fn copy_merge_consistent(a: BTreeSet<u64>, b: BTreeSet<u64>) -> bool {
+        let a: Vec<u64> = a.into_iter().collect();
+        let b: Vec<u64> = b.into_iter().collect();
+        CopyVecMergeState::merge_shortcut(&a, &b, SetUnionOp)
+            == VecMergeState::merge_shortcut(&a, &b, SetUnionOp)
+            && CopyVecMergeState::merge_shortcut(&a, &b, SetIntersectionOp)
+                == VecMergeState::merge_shortcut(&a, &b, SetIntersectionOp)
+    }
+
+    /// a three-way union that counts the elements that are present in all three inputs
+    #[derive(Default)]
+    struct Union3Op(Cell<usize>);
+
+    impl<'a, T: Ord + Clone> MergeOperation3<T, T, T, VecMergeState3<'a, T>> for Union3Op {
+        fn from_a(&self, m: &mut VecMergeState3<'a, T>, n: usize) {
+            m.r.extend_from_slice(m.a.take_front(n));
+        }
+        fn from_b(&self, m: &mut VecMergeState3<'a, T>, n: usize) {
+            m.r.extend_from_slice(m.b.take_front(n));
+        }
+        fn from_c(&self, m: &mut VecMergeState3<'a, T>, n: usize) {
+            m.r.extend_from_slice(m.c.take_front(n));
+        }
+        fn collision_ab(&self, m: &mut VecMergeState3<'a, T>) {
+            self.from_a(m, 1);
+            m.b.drop_front(1);
+        }
+        fn collision_ac(&self, m: &mut VecMergeState3<'a, T>) {
+            self.from_a(m, 1);
+            m.c.drop_front(1);
+        }
+        fn collision_bc(&self, m: &mut VecMergeState3<'a, T>) {
+            self.from_b(m, 1);
+            m.c.drop_front(1);
+        }
+        fn collision_abc(&self, m: &mut VecMergeState3<'a, T>) {
+            self.0.set(self.0.get() + 1);
+            self.from_a(m, 1);
+            m.b.drop_front(1);
+            m.c.drop_front(1);
+        }
+        fn cmp_ab(&self, a: &T, b: &T) -> Ordering {
+            a.cmp(b)
+        }
+        fn cmp_ac(&self, a: &T, c: &T) -> Ordering {
+            a.cmp(c)
+        }
+        fn cmp_bc(&self, b: &T, c: &T) -> Ordering {
+            b.cmp(c)
+        }
+    }
+
+    fn union3(a: &[i64], b: &[i64], c: &[i64]) -> (Vec<i64>, usize) {
+        let op = Union3Op::default();
+        let mut m = VecMergeState3::new(a, b, c, Vec::new());
+        op.merge(&mut m);
+        (m.r, op.0.get())
+    }
+
+    #[quickcheck]
+    fn union3_consistent(a: BTreeSet<i64>, b: BTreeSet<i64>, c: BTreeSet<i64>) -> bool {
+        let av: Vec<i64> = a.iter().cloned().collect();
+        let bv: Vec<i64> = b.iter().cloned().collect();
+        let cv: Vec<i64> = c.iter().cloned().collect();
+        let expected: BTreeSet<i64> = a.iter().chain(&b).chain(&c).cloned().collect();
+        let expected_abc = a.iter().filter(|x| b.contains(x) && c.contains(x)).count();
+        let (r, abc) = union3(&av, &bv, &cv);
+        r == expected.into_iter().collect::<Vec<_>>() && abc == expected_abc
+    }
+
+    #[test]
+    fn union3_cases() {
+        assert_eq!(union3(&[], &[], &[]), (vec![], 0));
+        assert_eq!(union3(&[1, 2, 3], &[], &[]), (vec![1, 2, 3], 0));
+        assert_eq!(union3(&[], &[], &[1, 2, 3]), (vec![1, 2, 3], 0));
+        assert_eq!(
+            union3(&[1, 4, 7], &[2, 4, 8], &[3, 4, 7, 8, 9, 10]),
+            (vec![1, 2, 3, 4, 7, 8, 9, 10], 1)
+        );
     }
 }
@@ -1,4 +1,5 @@
-use std::cmp::{min, Ordering};
+use alloc::vec::Vec;
+use core::cmp::Ordering;
 
 /// deduplicate a slice, moving the duplicates to the end.
 /// returns the number of unique elements.
@@ -25,6 +26,7 @@ pub fn dedup_by<T, F: Fn(&T, &T) -> bool>(d: &mut [T], same_bucket: F, keep: Kee
     j + 1
 }
 
+#[allow(dead_code)]
 fn dedup<T: Eq>(d: &mut [T], keep: Keep) -> usize {
     dedup_by(d, T::eq, keep)
 }
@@ -71,6 +73,7 @@ pub fn sort_and_dedup<T: Ord, I: Iterator<Item = T>>(iter: I) -> Vec<T> {
     agg.into_vec()
 }
 
+#[cfg(feature = "std")]
 pub fn sort_and_dedup_by_key<T, K, I, F>(iter: I, key: F, keep: Keep) -> Vec<T>
 where
     K: Ord,
@@ -78,10 +81,10 @@ where
     F: Fn(&T) -> &K,
 {
     let mut agg: SortAndDedup<T, _> = SortAndDedup {
-        data: Vec::with_capacity(min(iter.size_hint().0, 16)),
+        data: Vec::with_capacity(iter.size_hint().0.min(16)),
         count: 0,
         sorted: 0,
-        cmp: |a: &T, b: &T| key(a).cmp(&key(b)),
+        cmp: |a: &T, b: &T| key(a).cmp(key(b)),
         keep,
     };
     for x in iter {
@@ -0,0 +1,172 @@
+use crate::binary_merge::DescendingMergeOperation;
+use crate::merge_state::{BoolOpMergeState, VecMergeState};
+use crate::vec_set::{SetDiffOp, SetIntersectionOp, SetUnionOp, SetXorOp};
+use crate::VecSet;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::iter::FromIterator;
+
+/// A set backed by a `Vec` without duplicates, sorted in descending instead of ascending order
+///
+/// The set operations merge the elements in descending order directly, so there is no need to wrap them
+/// in `core::cmp::Reverse` or to reverse the result.
+#[derive(Clone, Hash, PartialEq, Eq, Default)]
+pub struct DescendingVecSet<T>(Vec<T>);
+
+impl<T: Debug> Debug for DescendingVecSet<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_set().entries(self.0.iter()).finish()
+    }
+}
+
+impl<T> DescendingVecSet<T> {
+    pub fn empty() -> Self {
+        Self(Vec::new())
+    }
+    pub fn as_slice(&self) -> &[T] {
+        &self.0
+    }
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    /// An iterator that returns the items of this set in descending order
+    pub fn iter(&self) -> core::slice::Iter<'_, T> {
+        self.0.iter()
+    }
+    pub fn into_vec(self) -> Vec<T> {
+        self.0
+    }
+}
+
+impl<T: Ord> DescendingVecSet<T> {
+    /// Creates a set from a vec, sorting it in descending order and removing duplicates
+    pub fn from_vec(vec: Vec<T>) -> Self {
+        let mut elements = vec;
+        elements.sort_by(|a, b| b.cmp(a));
+        elements.dedup();
+        Self(elements)
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.0.binary_search_by(|x| value.cmp(x)).is_ok()
+    }
+
+    pub fn is_disjoint(&self, that: &Self) -> bool {
+        !BoolOpMergeState::merge(
+            &self.0,
+            &that.0,
+            DescendingMergeOperation(SetIntersectionOp),
+        )
+    }
+
+    pub fn is_subset(&self, that: &Self) -> bool {
+        !BoolOpMergeState::merge(&self.0, &that.0, DescendingMergeOperation(SetDiffOp))
+    }
+
+    pub fn is_superset(&self, that: &Self) -> bool {
+        that.is_subset(self)
+    }
+}
+
+impl<T: Ord + Clone> DescendingVecSet<T> {
+    /// The elements that are in self or that, as a new set
+    pub fn union(&self, that: &Self) -> Self {
+        let op = DescendingMergeOperation(SetUnionOp);
+        Self(VecMergeState::merge_shortcut(&self.0, &that.0, op))
+    }
+
+    /// The elements that are in both self and that, as a new set
+    pub fn intersection(&self, that: &Self) -> Self {
+        let op = DescendingMergeOperation(SetIntersectionOp);
+        Self(VecMergeState::merge_shortcut(&self.0, &that.0, op))
+    }
+
+    /// The elements that are in self but not in that, as a new set
+    pub fn difference(&self, that: &Self) -> Self {
+        let op = DescendingMergeOperation(SetDiffOp);
+        Self(VecMergeState::merge_shortcut(&self.0, &that.0, op))
+    }
+
+    /// The elements that are in exactly one of self and that, as a new set
+    pub fn symmetric_difference(&self, that: &Self) -> Self {
+        let op = DescendingMergeOperation(SetXorOp);
+        Self(VecMergeState::merge_shortcut(&self.0, &that.0, op))
+    }
+}
+
+impl<T: Ord> FromIterator<T> for DescendingVecSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::from_vec(iter.into_iter().collect())
+    }
+}
+
+impl<T> From<VecSet<T>> for DescendingVecSet<T> {
+    fn from(value: VecSet<T>) -> Self {
+        let mut elements = value.into_vec();
+        elements.reverse();
+        Self(elements)
+    }
+}
+
+impl<T: Ord> From<DescendingVecSet<T>> for VecSet<T> {
+    fn from(value: DescendingVecSet<T>) -> Self {
+        let mut elements = value.0;
+        elements.reverse();
+        VecSet::from_sorted_unchecked(elements)
+    }
+}
+
+impl<T> IntoIterator for DescendingVecSet<T> {
+    type Item = T;
+    type IntoIter = alloc::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+    use std::collections::BTreeSet;
+
+    type Test = DescendingVecSet<i64>;
+
+    fn descending(x: BTreeSet<i64>) -> Vec<i64> {
+        x.into_iter().rev().collect()
+    }
+
+    #[quickcheck]
+    fn same_as_btree_set(a: BTreeSet<i64>, b: BTreeSet<i64>) -> bool {
+        let a1: Test = a.iter().cloned().collect();
+        let b1: Test = b.iter().cloned().collect();
+        a1.union(&b1).into_vec() == descending(&a | &b)
+            && a1.intersection(&b1).into_vec() == descending(&a & &b)
+            && a1.difference(&b1).into_vec() == descending(&a - &b)
+            && a1.symmetric_difference(&b1).into_vec() == descending(&a ^ &b)
+            && a1.is_disjoint(&b1) == a.is_disjoint(&b)
+            && a1.is_subset(&b1) == a.is_subset(&b)
+            && a1.is_superset(&b1) == a.is_superset(&b)
+    }
+
+    #[test]
+    fn descending_cases() {
+        let a: Test = vec![1, 5, 3, 7, 5].into_iter().collect();
+        let b: Test = vec![2, 6, 5].into_iter().collect();
+        assert_eq!(a.as_slice(), &[7, 5, 3, 1]);
+        assert!(a.contains(&3));
+        assert!(!a.contains(&4));
+        assert_eq!(a.union(&b).as_slice(), &[7, 6, 5, 3, 2, 1]);
+        assert_eq!(a.intersection(&b).as_slice(), &[5]);
+        assert_eq!(a.difference(&b).as_slice(), &[7, 3, 1]);
+        assert_eq!(a.symmetric_difference(&b).as_slice(), &[7, 6, 3, 2, 1]);
+        let ascending: VecSet<i64> = a.clone().into();
+        assert_eq!(ascending.as_slice(), &[1, 3, 5, 7]);
+        assert_eq!(Test::from(ascending), a);
+        assert_eq!(format!("{:?}", b), "{6, 5, 2}");
+    }
+}
@@ -1,8 +1,10 @@
 //! A data structure for in place modification of vecs.
 // #![deny(warnings)]
 #![deny(missing_docs)]
-use std::cell::RefCell;
-use std::fmt::Debug;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::fmt::Debug;
 pub mod small_vec_builder;
 
 /// A contiguous chunk of memory that is logically divided into a source and a target part.
@@ -21,7 +23,7 @@ pub struct InPlaceVecBuilder<T> {
 }
 
 impl<T> Debug for InPlaceVecBuilder<T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let InPlaceVecBuilder { s0, t1, v } = self;
         let s1 = v.len();
         let cap = v.capacity();
@@ -66,7 +68,7 @@ impl<T> InPlaceVecBuilder<T> {
             let cap = v.capacity();
             // just move source to the end without any concern about dropping
             unsafe {
-                std::ptr::copy(v.as_ptr().add(s0), v.as_mut_ptr().add(cap - sn), sn);
+                core::ptr::copy(v.as_ptr().add(s0), v.as_mut_ptr().add(cap - sn), sn);
                 v.set_len(cap);
             }
             // move s0
@@ -94,17 +96,17 @@ impl<T> InPlaceVecBuilder<T> {
     }
 
     fn push_unsafe(&mut self, value: T) {
-        unsafe { std::ptr::write(self.v.as_mut_ptr().add(self.t1), value) }
+        unsafe { core::ptr::write(self.v.as_mut_ptr().add(self.t1), value) }
         self.t1 += 1;
     }
 
     pub fn consume(&mut self, n: usize, take: bool) {
-        let n = std::cmp::min(n, self.source_slice().len());
+        let n = core::cmp::min(n, self.source_slice().len());
         let v = self.v.as_mut_ptr();
         if take {
             if self.t1 != self.s0 {
                 unsafe {
-                    std::ptr::copy(v.add(self.s0), v.add(self.t1), n);
+                    core::ptr::copy(v.add(self.s0), v.add(self.t1), n);
                 }
             }
             self.t1 += n;
@@ -113,7 +115,7 @@ impl<T> InPlaceVecBuilder<T> {
             for _ in 0..n {
                 unsafe {
                     self.s0 += 1;
-                    std::ptr::drop_in_place(v.add(self.s0 - 1));
+                    core::ptr::drop_in_place(v.add(self.s0 - 1));
                 }
             }
         }
@@ -122,12 +124,12 @@ impl<T> InPlaceVecBuilder<T> {
     /// Skip up to `n` elements from source without adding them to the target.
     /// They will be immediately dropped!
     pub fn skip(&mut self, n: usize) {
-        let n = std::cmp::min(n, self.source_slice().len());
+        let n = core::cmp::min(n, self.source_slice().len());
         let v = self.v.as_mut_ptr();
         for _ in 0..n {
             unsafe {
                 self.s0 += 1;
-                std::ptr::drop_in_place(v.add(self.s0 - 1));
+                core::ptr::drop_in_place(v.add(self.s0 - 1));
             }
         }
     }
@@ -135,11 +137,11 @@ impl<T> InPlaceVecBuilder<T> {
     /// Take up to `n` elements from source to target.
     /// If n is larger than the size of the remaining source, this will only copy all remaining elements in source.
     pub fn take(&mut self, n: usize) {
-        let n = std::cmp::min(n, self.source_slice().len());
+        let n = core::cmp::min(n, self.source_slice().len());
         if self.t1 != self.s0 {
             unsafe {
                 let v = self.v.as_mut_ptr();
-                std::ptr::copy(v.add(self.s0), v.add(self.t1), n);
+                core::ptr::copy(v.add(self.s0), v.add(self.t1), n);
             }
         }
         self.t1 += n;
@@ -150,7 +152,7 @@ impl<T> InPlaceVecBuilder<T> {
     pub fn pop_front(&mut self) -> Option<T> {
         if self.s0 < self.v.len() {
             self.s0 += 1;
-            Some(unsafe { std::ptr::read(self.v.as_ptr().add(self.s0 - 1)) })
+            Some(unsafe { core::ptr::read(self.v.as_ptr().add(self.s0 - 1)) })
         } else {
             None
         }
@@ -171,8 +173,9 @@ impl<T> InPlaceVecBuilder<T> {
     pub fn into_vec(self) -> Vec<T> {
         let mut r = self;
         r.drop_source();
-        let mut t: Vec<T> = Vec::new();
-        std::mem::swap(&mut t, &mut r.v);
+        let t: Vec<T> = core::mem::take(&mut r.v);
+        // forget the rest to prevent drop from running on the already dropped source
+        core::mem::forget(r);
         t
     }
 }
@@ -215,9 +218,9 @@ impl<'a, T> Builder<'a, T> {
     }
 }
 
-struct BAI<T>(RefCell<InPlaceVecBuilder<T>>);
+struct Bai<T>(RefCell<InPlaceVecBuilder<T>>);
 
-impl<T> BAI<T> {
+impl<T> Bai<T> {
     fn new(v: Vec<T>) -> Self {
         Self(RefCell::new(InPlaceVecBuilder::from(v)))
     }
@@ -248,6 +251,15 @@ impl<'a, T> Iterator for Iter<'a, T> {
     }
 }
 
+pub fn demo() {
+    let a = vec![1, 2, 3];
+    let mut a = Bai::new(a);
+    let (mut b, i) = a.pair();
+    b.extend(i.map(|x| x * 2));
+    let r: Vec<_> = a.into_vec();
+    assert_eq!(r, vec![2, 4, 6]);
+}
+
 #[cfg(test)]
 mod tests {
     extern crate testdrop;
@@ -344,19 +356,10 @@ mod tests {
     #[test]
     fn builder() {
         let a = vec![1, 2, 3];
-        let mut a = BAI::new(a);
+        let mut a = Bai::new(a);
         let (mut b, i) = a.pair();
         b.extend(i.map(|x| x * 2));
         let r: Vec<_> = a.into_vec();
         assert_eq!(r, vec![2, 4, 6]);
     }
 }
-
-pub fn demo() {
-    let a = vec![1, 2, 3];
-    let mut a = BAI::new(a);
-    let (mut b, i) = a.pair();
-    b.extend(i.map(|x| x * 2));
-    let r: Vec<_> = a.into_vec();
-    assert_eq!(r, vec![2, 4, 6]);
-}
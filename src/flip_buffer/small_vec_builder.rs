@@ -1,8 +1,8 @@
 //! A data structure for in place modification of vecs.
 // #![deny(warnings)]
 #![deny(missing_docs)]
+use core::fmt::Debug;
 use smallvec::{Array, SmallVec};
-use std::fmt::Debug;
 
 pub struct InPlaceSmallVecBuilder<A: Array> {
     /// the underlying vector, possibly containing some uninitialized values in the middle!
@@ -14,7 +14,7 @@ pub struct InPlaceSmallVecBuilder<A: Array> {
 }
 
 impl<T: Debug, A: Array<Item = T>> Debug for InPlaceSmallVecBuilder<A> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let InPlaceSmallVecBuilder { s0, t1, v } = self;
         let s1 = v.len();
         let cap = v.capacity();
@@ -68,7 +68,7 @@ impl<A: Array> InPlaceSmallVecBuilder<A> {
             let cap = v.capacity();
             // just move source to the end without any concern about dropping
             unsafe {
-                std::ptr::copy(v.as_ptr().add(s0), v.as_mut_ptr().add(cap - sn), sn);
+                core::ptr::copy(v.as_ptr().add(s0), v.as_mut_ptr().add(cap - sn), sn);
                 v.set_len(cap);
             }
             // move s0
@@ -96,17 +96,17 @@ impl<A: Array> InPlaceSmallVecBuilder<A> {
     }
 
     fn push_unsafe(&mut self, value: A::Item) {
-        unsafe { std::ptr::write(self.v.as_mut_ptr().add(self.t1), value) }
+        unsafe { core::ptr::write(self.v.as_mut_ptr().add(self.t1), value) }
         self.t1 += 1;
     }
 
     pub fn consume(&mut self, n: usize, take: bool) {
-        let n = std::cmp::min(n, self.source_slice().len());
+        let n = core::cmp::min(n, self.source_slice().len());
         let v = self.v.as_mut_ptr();
         if take {
             if self.t1 != self.s0 {
                 unsafe {
-                    std::ptr::copy(v.add(self.s0), v.add(self.t1), n);
+                    core::ptr::copy(v.add(self.s0), v.add(self.t1), n);
                 }
             }
             self.t1 += n;
@@ -115,7 +115,7 @@ impl<A: Array> InPlaceSmallVecBuilder<A> {
             for _ in 0..n {
                 unsafe {
                     self.s0 += 1;
-                    std::ptr::drop_in_place(v.add(self.s0 - 1));
+                    core::ptr::drop_in_place(v.add(self.s0 - 1));
                 }
             }
         }
@@ -124,12 +124,12 @@ impl<A: Array> InPlaceSmallVecBuilder<A> {
     /// Skip up to `n` elements from source without adding them to the target.
     /// They will be immediately dropped!
     pub fn skip(&mut self, n: usize) {
-        let n = std::cmp::min(n, self.source_slice().len());
+        let n = core::cmp::min(n, self.source_slice().len());
         let v = self.v.as_mut_ptr();
         for _ in 0..n {
             unsafe {
                 self.s0 += 1;
-                std::ptr::drop_in_place(v.add(self.s0 - 1));
+                core::ptr::drop_in_place(v.add(self.s0 - 1));
             }
         }
     }
@@ -137,11 +137,11 @@ impl<A: Array> InPlaceSmallVecBuilder<A> {
     /// Take up to `n` elements from source to target.
     /// If n is larger than the size of the remaining source, this will only copy all remaining elements in source.
     pub fn take(&mut self, n: usize) {
-        let n = std::cmp::min(n, self.source_slice().len());
+        let n = core::cmp::min(n, self.source_slice().len());
         if self.t1 != self.s0 {
             unsafe {
                 let v = self.v.as_mut_ptr();
-                std::ptr::copy(v.add(self.s0), v.add(self.t1), n);
+                core::ptr::copy(v.add(self.s0), v.add(self.t1), n);
             }
         }
         self.t1 += n;
@@ -152,7 +152,7 @@ impl<A: Array> InPlaceSmallVecBuilder<A> {
     pub fn pop_front(&mut self) -> Option<A::Item> {
         if self.s0 < self.v.len() {
             self.s0 += 1;
-            Some(unsafe { std::ptr::read(self.v.as_ptr().add(self.s0 - 1)) })
+            Some(unsafe { core::ptr::read(self.v.as_ptr().add(self.s0 - 1)) })
         } else {
             None
         }
@@ -174,9 +174,9 @@ impl<A: Array> InPlaceSmallVecBuilder<A> {
         // drop the source part
         self.drop_source();
         // tear out the v
-        let v = std::mem::replace(&mut self.v, unsafe { std::mem::uninitialized() });
-        // forget the rest to prevent drop to run on uninitialized data
-        std::mem::forget(self);
+        let v = core::mem::take(&mut self.v);
+        // forget the rest to prevent drop from running on the already dropped source
+        core::mem::forget(self);
         v
     }
 }
@@ -190,6 +190,64 @@ impl<A: Array> Drop for InPlaceSmallVecBuilder<A> {
     }
 }
 
+/// workaround until https://github.com/servo/rust-smallvec/issues/181 is implemented
+pub struct SmallVecIntoIter<A: Array> {
+    data: SmallVec<A>,
+    current: usize,
+    end: usize,
+}
+
+impl<A: Array> Drop for SmallVecIntoIter<A> {
+    fn drop(&mut self) {
+        for _ in self {}
+    }
+}
+
+impl<A: Array> Iterator for SmallVecIntoIter<A> {
+    type Item = A::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<A::Item> {
+        if self.current == self.end {
+            None
+        } else {
+            unsafe {
+                let current = self.current as isize;
+                self.current += 1;
+                Some(core::ptr::read(self.data.as_ptr().offset(current)))
+            }
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let size = self.end - self.current;
+        (size, Some(size))
+    }
+}
+
+impl<A: Array> SmallVecIntoIter<A> {
+    pub fn new(data: SmallVec<A>) -> Self {
+        Self {
+            current: 0,
+            end: data.len(),
+            data,
+        }
+    }
+
+    /// Returns the remaining items of this iterator as a slice.
+    pub fn as_slice(&self) -> &[A::Item] {
+        let len = self.end - self.current;
+        unsafe { core::slice::from_raw_parts(self.data.as_ptr().add(self.current), len) }
+    }
+
+    /// Returns the remaining items of this iterator as a mutable slice.
+    pub fn as_mut_slice(&mut self) -> &mut [A::Item] {
+        let len = self.end - self.current;
+        unsafe { core::slice::from_raw_parts_mut(self.data.as_mut_ptr().add(self.current), len) }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     extern crate testdrop;
@@ -285,61 +343,3 @@ mod tests {
         })
     }
 }
-
-/// workaround until https://github.com/servo/rust-smallvec/issues/181 is implemented
-pub struct SmallVecIntoIter<A: Array> {
-    data: SmallVec<A>,
-    current: usize,
-    end: usize,
-}
-
-impl<A: Array> Drop for SmallVecIntoIter<A> {
-    fn drop(&mut self) {
-        for _ in self {}
-    }
-}
-
-impl<A: Array> Iterator for SmallVecIntoIter<A> {
-    type Item = A::Item;
-
-    #[inline]
-    fn next(&mut self) -> Option<A::Item> {
-        if self.current == self.end {
-            None
-        } else {
-            unsafe {
-                let current = self.current as isize;
-                self.current += 1;
-                Some(core::ptr::read(self.data.as_ptr().offset(current)))
-            }
-        }
-    }
-
-    #[inline]
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        let size = self.end - self.current;
-        (size, Some(size))
-    }
-}
-
-impl<A: Array> SmallVecIntoIter<A> {
-    pub fn new(data: SmallVec<A>) -> Self {
-        Self {
-            current: 0,
-            end: data.len(),
-            data,
-        }
-    }
-
-    /// Returns the remaining items of this iterator as a slice.
-    pub fn as_slice(&self) -> &[A::Item] {
-        let len = self.end - self.current;
-        unsafe { core::slice::from_raw_parts(self.data.as_ptr().add(self.current), len) }
-    }
-
-    /// Returns the remaining items of this iterator as a mutable slice.
-    pub fn as_mut_slice(&mut self) -> &mut [A::Item] {
-        let len = self.end - self.current;
-        unsafe { core::slice::from_raw_parts_mut(self.data.as_mut_ptr().add(self.current), len) }
-    }
-}
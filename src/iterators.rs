@@ -1,13 +1,693 @@
+use crate::merge_state::ChunkedMergeState;
+use crate::vec_set::SetUnionOp;
+use crate::VecSet;
+use alloc::collections::binary_heap::{BinaryHeap, PeekMut};
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::convert::Infallible;
+use core::iter::{FusedIterator, Map, Peekable};
+
+/// An iterator whose items are sorted in ascending order
+///
+/// The set combinators of `SortedIterator`, such as `difference` and `symmetric_difference`, assume that
+/// the items are strictly ascending. Duplicates within one side are not suppressed, so use [SortedIter::dedup]
+/// first for inputs that may contain them.
 pub struct SortedIter<I> {
     i: I,
 }
 
+#[cfg(feature = "std")]
 impl<I> sorted_iter::sorted_iterator::SortedByItem for SortedIter<I> {}
 
 impl<I: Iterator> SortedIter<I> {
     pub(crate) fn new(i: I) -> Self {
         Self { i }
     }
+
+    /// Stable merge of two iterators that are both sorted according to `cmp`.
+    ///
+    /// Unlike union, elements that compare equal are not deduplicated. All of them are
+    /// yielded, with the elements of `self` before the elements of `other`. The result
+    /// is sorted according to `cmp`, so this can e.g. be used to interleave logs by timestamp.
+    pub fn merge_by<J, F>(self, other: J, cmp: F) -> SortedIter<MergeBy<I, J::IntoIter, F>>
+    where
+        J: IntoIterator<Item = I::Item>,
+        F: FnMut(&I::Item, &I::Item) -> Ordering,
+    {
+        SortedIter::new(MergeBy {
+            a: self.i.peekable(),
+            b: other.into_iter().peekable(),
+            cmp,
+        })
+    }
+}
+
+impl<I: Iterator> SortedIter<I>
+where
+    I::Item: Ord,
+{
+    /// Lazy merge-join of two sorted iterators.
+    ///
+    /// Yields [EitherOrBoth::Left] for elements only in `self`, [EitherOrBoth::Right] for
+    /// elements only in `other`, and [EitherOrBoth::Both] for elements that compare equal.
+    pub fn join<J>(self, other: J) -> Join<I, J::IntoIter>
+    where
+        J: IntoIterator<Item = I::Item>,
+    {
+        Join {
+            a: self.i.peekable(),
+            b: other.into_iter().peekable(),
+        }
+    }
+}
+
+/// An element of a merge-join: present only on the left, only on the right, or on both sides
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EitherOrBoth<A, B> {
+    Left(A),
+    Right(B),
+    Both(A, B),
+}
+
+impl<T: Ord, E, I: Iterator<Item = Result<T, E>>> SortedIter<I> {
+    /// The union of two sorted iterators of results, stopping at the first error
+    ///
+    /// The `Ok` values of both iterators must be sorted. The result contains the union of the `Ok` values in
+    /// sorted order, until an error is read from either side. The error is returned, and after that the
+    /// iterator returns `None`.
+    pub fn try_union<J: Iterator<Item = Result<T, E>>>(
+        self,
+        other: SortedIter<J>,
+    ) -> TryUnion<I, J, T> {
+        TryUnion(TryMerge::new(self.i, other.i))
+    }
+
+    /// The intersection of two sorted iterators of results, stopping at the first error
+    ///
+    /// Like [SortedIter::try_union], but for the intersection. Once one side is exhausted, the rest of the
+    /// other side is not read, so errors in it are not returned.
+    pub fn try_intersection<J: Iterator<Item = Result<T, E>>>(
+        self,
+        other: SortedIter<J>,
+    ) -> TryIntersection<I, J, T> {
+        TryIntersection(TryMerge::new(self.i, other.i))
+    }
+}
+
+/// The state shared by [TryUnion] and [TryIntersection]
+struct TryMerge<I, J, T> {
+    a: core::iter::Fuse<I>,
+    b: core::iter::Fuse<J>,
+    a_next: Option<T>,
+    b_next: Option<T>,
+    failed: bool,
+}
+
+impl<T, E, I: Iterator<Item = Result<T, E>>, J: Iterator<Item = Result<T, E>>> TryMerge<I, J, T> {
+    fn new(a: I, b: J) -> Self {
+        Self {
+            a: a.fuse(),
+            b: b.fuse(),
+            a_next: None,
+            b_next: None,
+            failed: false,
+        }
+    }
+
+    fn fill<S: Iterator<Item = Result<T, E>>>(
+        source: &mut S,
+        next: &mut Option<T>,
+    ) -> Result<(), E> {
+        if next.is_none() {
+            if let Some(x) = source.next() {
+                *next = Some(x?);
+            }
+        }
+        Ok(())
+    }
+
+    /// reads the next element of both sides, unless it has already been read
+    ///
+    /// Returns false if iteration is over. Errors are stored in err, and end the iteration.
+    fn fill_both(&mut self, err: &mut Option<E>) -> bool {
+        if self.failed {
+            return false;
+        }
+        let res = Self::fill(&mut self.a, &mut self.a_next)
+            .and_then(|_| Self::fill(&mut self.b, &mut self.b_next));
+        if let Err(e) = res {
+            self.failed = true;
+            *err = Some(e);
+            return false;
+        }
+        true
+    }
+}
+
+/// Iterator returned by [SortedIter::try_union]
+pub struct TryUnion<I, J, T>(TryMerge<I, J, T>);
+
+impl<T: Ord, E, I: Iterator<Item = Result<T, E>>, J: Iterator<Item = Result<T, E>>> Iterator
+    for TryUnion<I, J, T>
+{
+    type Item = Result<T, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut err = None;
+        let m = &mut self.0;
+        if !m.fill_both(&mut err) {
+            return err.map(Err);
+        }
+        match (m.a_next.take(), m.b_next.take()) {
+            (Some(a), Some(b)) => match a.cmp(&b) {
+                Ordering::Less => {
+                    m.b_next = Some(b);
+                    Some(Ok(a))
+                }
+                Ordering::Greater => {
+                    m.a_next = Some(a);
+                    Some(Ok(b))
+                }
+                Ordering::Equal => Some(Ok(a)),
+            },
+            (Some(a), None) => Some(Ok(a)),
+            (None, Some(b)) => Some(Ok(b)),
+            (None, None) => None,
+        }
+    }
+}
+
+impl<T: Ord, E, I: Iterator<Item = Result<T, E>>, J: Iterator<Item = Result<T, E>>> FusedIterator
+    for TryUnion<I, J, T>
+{
+}
+
+/// Iterator returned by [SortedIter::try_intersection]
+pub struct TryIntersection<I, J, T>(TryMerge<I, J, T>);
+
+impl<T: Ord, E, I: Iterator<Item = Result<T, E>>, J: Iterator<Item = Result<T, E>>> Iterator
+    for TryIntersection<I, J, T>
+{
+    type Item = Result<T, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut err = None;
+        let m = &mut self.0;
+        while m.fill_both(&mut err) {
+            match (m.a_next.take(), m.b_next.take()) {
+                (Some(a), Some(b)) => match a.cmp(&b) {
+                    Ordering::Less => m.b_next = Some(b),
+                    Ordering::Greater => m.a_next = Some(a),
+                    Ordering::Equal => return Some(Ok(a)),
+                },
+                _ => {
+                    // one side is exhausted, so we are done
+                    m.failed = true;
+                    return None;
+                }
+            }
+        }
+        err.map(Err)
+    }
+}
+
+impl<T: Ord, E, I: Iterator<Item = Result<T, E>>, J: Iterator<Item = Result<T, E>>> FusedIterator
+    for TryIntersection<I, J, T>
+{
+}
+
+impl<I: Iterator> SortedIter<I>
+where
+    I::Item: Ord,
+{
+    /// Collects into a `VecSet`, without sorting since the elements are already sorted
+    ///
+    /// This only has to remove duplicates, which is `O(n)`, whereas collecting via `FromIterator` has to
+    /// sort the elements first.
+    pub fn collect_sorted(self) -> VecSet<I::Item> {
+        let mut elements: Vec<I::Item> = self.i.collect();
+        elements.dedup();
+        VecSet::from_sorted_unchecked(elements)
+    }
+
+    /// Collects into a `BTreeSet`
+    ///
+    /// This uses `BTreeSet::from_iter`, which buffers and sorts the elements and then builds the tree in
+    /// bulk. Sorting input that is already sorted is `O(n)`, so there is no need for a separate bulk
+    /// build path here.
+    pub fn collect_btree(self) -> BTreeSet<I::Item> {
+        self.i.collect()
+    }
+}
+
+/// Iterator returned by [SortedIter::join]
+pub struct Join<I: Iterator, J: Iterator> {
+    a: Peekable<I>,
+    b: Peekable<J>,
+}
+
+impl<I, J> Iterator for Join<I, J>
+where
+    I: Iterator,
+    I::Item: Ord,
+    J: Iterator<Item = I::Item>,
+{
+    type Item = EitherOrBoth<I::Item, I::Item>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let order = match (self.a.peek(), self.b.peek()) {
+            (Some(a), Some(b)) => a.cmp(b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => return None,
+        };
+        Some(match order {
+            Ordering::Less => EitherOrBoth::Left(self.a.next()?),
+            Ordering::Greater => EitherOrBoth::Right(self.b.next()?),
+            Ordering::Equal => EitherOrBoth::Both(self.a.next()?, self.b.next()?),
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (amin, amax) = self.a.size_hint();
+        let (bmin, bmax) = self.b.size_hint();
+        let max = match (amax, bmax) {
+            (Some(a), Some(b)) => a.checked_add(b),
+            _ => None,
+        };
+        (amin.max(bmin), max)
+    }
+}
+
+impl<I, J> FusedIterator for Join<I, J>
+where
+    I: FusedIterator,
+    I::Item: Ord,
+    J: FusedIterator<Item = I::Item>,
+{
+}
+
+impl<I: Iterator> SortedIter<I>
+where
+    I::Item: PartialEq,
+{
+    /// Collapses runs of equal adjacent elements into one, keeping the first element of each run
+    pub fn dedup(self) -> SortedIter<Dedup<I>> {
+        SortedIter::new(Dedup {
+            i: self.i.peekable(),
+        })
+    }
+
+    /// Collapses runs of equal adjacent elements into pairs of the first element of the run and its length
+    ///
+    /// This is lazy, and only looks one element ahead to find the end of a run.
+    pub fn run_length_encode(self) -> SortedIter<RunLengths<I>> {
+        SortedIter::new(RunLengths {
+            i: self.i.peekable(),
+        })
+    }
+
+    /// The number of distinct elements, counted in a single pass without allocating
+    pub fn count_unique(self) -> usize {
+        let mut count = 0;
+        let mut prev = None;
+        for x in self.i {
+            if prev.as_ref() != Some(&x) {
+                count += 1;
+                prev = Some(x);
+            }
+        }
+        count
+    }
+}
+
+/// Iterator returned by [SortedIter::dedup]
+pub struct Dedup<I: Iterator> {
+    i: Peekable<I>,
+}
+
+impl<I: Iterator> Iterator for Dedup<I>
+where
+    I::Item: PartialEq,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let res = self.i.next()?;
+        while self.i.next_if_eq(&res).is_some() {}
+        Some(res)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (min, max) = self.i.size_hint();
+        (min.min(1), max)
+    }
+}
+
+impl<I: FusedIterator> FusedIterator for Dedup<I> where I::Item: PartialEq {}
+
+/// Iterator returned by [SortedIter::run_length_encode]
+pub struct RunLengths<I: Iterator> {
+    i: Peekable<I>,
+}
+
+impl<I: Iterator> Iterator for RunLengths<I>
+where
+    I::Item: PartialEq,
+{
+    type Item = (I::Item, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let res = self.i.next()?;
+        let mut count = 1;
+        while self.i.next_if_eq(&res).is_some() {
+            count += 1;
+        }
+        Some((res, count))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (min, max) = self.i.size_hint();
+        (min.min(1), max)
+    }
+}
+
+impl<I: FusedIterator> FusedIterator for RunLengths<I> where I::Item: PartialEq {}
+
+/// Wraps an iterator that is known to be sorted in ascending order, so the sorted combinators can be used
+///
+/// The order is not checked. If the iterator is not actually sorted, the combinators will give unspecified
+/// results. Use [try_sorted] to check the order in debug builds.
+pub fn assume_sorted_by_item<I: IntoIterator>(iter: I) -> SortedIter<I::IntoIter> {
+    SortedIter::new(iter.into_iter())
+}
+
+/// Like [assume_sorted_by_item], but checks that the elements are in ascending order while iterating
+///
+/// In debug builds, iterating panics as soon as an element is less than its predecessor. In release builds
+/// the order is not checked.
+pub fn try_sorted<I>(iter: I) -> SortedIter<CheckSorted<I::IntoIter>>
+where
+    I: IntoIterator,
+    I::Item: PartialOrd,
+{
+    SortedIter::new(CheckSorted {
+        i: iter.into_iter().peekable(),
+    })
+}
+
+/// Iterator returned by [try_sorted]
+pub struct CheckSorted<I: Iterator> {
+    i: Peekable<I>,
+}
+
+impl<I: Iterator> Iterator for CheckSorted<I>
+where
+    I::Item: PartialOrd,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let res = self.i.next()?;
+        if cfg!(debug_assertions) {
+            if let Some(next) = self.i.peek() {
+                assert!(res <= *next, "iterator is not sorted");
+            }
+        }
+        Some(res)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.i.size_hint()
+    }
+}
+
+/// Merges k sorted iterators into one sorted iterator, yielding elements that are in multiple
+/// iterators only once
+///
+/// This uses a binary heap of the heads of the iterators, so each element takes `O(log k)`.
+pub fn kmerge<I>(iters: Vec<SortedIter<I>>) -> SortedIter<KMerge<I>>
+where
+    I: Iterator,
+    I::Item: Ord,
+{
+    SortedIter::new(KMerge::new(iters, true))
+}
+
+/// Merges k sorted iterators into one sorted iterator, keeping all elements
+///
+/// Elements that compare equal are yielded in the order of the iterators they come from.
+pub fn kmerge_multi<I>(iters: Vec<SortedIter<I>>) -> SortedIter<KMerge<I>>
+where
+    I: Iterator,
+    I::Item: Ord,
+{
+    SortedIter::new(KMerge::new(iters, false))
+}
+
+/// The head of one of the iterators of a k-way merge
+struct Head<I: Iterator> {
+    item: I::Item,
+    index: usize,
+    rest: I,
+}
+
+impl<I: Iterator> Head<I>
+where
+    I::Item: Ord,
+{
+    fn key(&self) -> (&I::Item, usize) {
+        (&self.item, self.index)
+    }
+}
+
+impl<I: Iterator> PartialEq for Head<I>
+where
+    I::Item: Ord,
+{
+    fn eq(&self, that: &Self) -> bool {
+        self.key() == that.key()
+    }
+}
+
+impl<I: Iterator> Eq for Head<I> where I::Item: Ord {}
+
+impl<I: Iterator> PartialOrd for Head<I>
+where
+    I::Item: Ord,
+{
+    fn partial_cmp(&self, that: &Self) -> Option<Ordering> {
+        Some(self.cmp(that))
+    }
+}
+
+/// reversed, so that the max heap returns the smallest head first
+impl<I: Iterator> Ord for Head<I>
+where
+    I::Item: Ord,
+{
+    fn cmp(&self, that: &Self) -> Ordering {
+        that.key().cmp(&self.key())
+    }
+}
+
+/// Iterator returned by [kmerge] and [kmerge_multi]
+pub struct KMerge<I: Iterator> {
+    heap: BinaryHeap<Head<I>>,
+    dedup: bool,
+}
+
+impl<I: Iterator> KMerge<I>
+where
+    I::Item: Ord,
+{
+    fn new(iters: Vec<SortedIter<I>>, dedup: bool) -> Self {
+        let heap = iters
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, mut iter)| {
+                iter.i.next().map(|item| Head {
+                    item,
+                    index,
+                    rest: iter.i,
+                })
+            })
+            .collect();
+        Self { heap, dedup }
+    }
+
+    /// the smallest remaining element, advancing the iterator it came from
+    fn pop(&mut self) -> Option<I::Item> {
+        let mut head = self.heap.peek_mut()?;
+        match head.rest.next() {
+            Some(item) => Some(core::mem::replace(&mut head.item, item)),
+            None => Some(PeekMut::pop(head).item),
+        }
+    }
+}
+
+impl<I: Iterator> Iterator for KMerge<I>
+where
+    I::Item: Ord,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let res = self.pop()?;
+        if self.dedup {
+            while self.heap.peek().map(|head| head.item == res) == Some(true) {
+                self.pop();
+            }
+        }
+        Some(res)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let mut min = 0usize;
+        let mut max = Some(0usize);
+        for head in self.heap.iter() {
+            let (rmin, rmax) = head.rest.size_hint();
+            min = min.saturating_add(rmin).saturating_add(1);
+            max = max.and_then(|m| m.checked_add(rmax?)?.checked_add(1));
+        }
+        if self.dedup {
+            // all remaining elements could be equal
+            min = min.min(1);
+        }
+        (min, max)
+    }
+}
+
+/// Iterator returned by [SortedIter::merge_by]
+pub struct MergeBy<I: Iterator, J: Iterator, F> {
+    a: Peekable<I>,
+    b: Peekable<J>,
+    cmp: F,
+}
+
+impl<I, J, F> Iterator for MergeBy<I, J, F>
+where
+    I: Iterator,
+    J: Iterator<Item = I::Item>,
+    F: FnMut(&I::Item, &I::Item) -> Ordering,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let take_a = match (self.a.peek(), self.b.peek()) {
+            (Some(a), Some(b)) => (self.cmp)(a, b) != Ordering::Greater,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+        if take_a {
+            self.a.next()
+        } else {
+            self.b.next()
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (amin, amax) = self.a.size_hint();
+        let (bmin, bmax) = self.b.size_hint();
+        let max = match (amax, bmax) {
+            (Some(a), Some(b)) => a.checked_add(b),
+            _ => None,
+        };
+        (amin.saturating_add(bmin), max)
+    }
+}
+
+/// The number of elements buffered per side by [SortedIter::binary_union]
+pub const BINARY_MERGE_CHUNK_SIZE: usize = 256;
+
+impl<I: Iterator> SortedIter<I>
+where
+    I::Item: Ord + Clone,
+{
+    /// Union of two strictly sorted iterators, using the binary merge on buffered chunks
+    ///
+    /// Unlike the union of [SortedIterator](crate::SortedIterator), which compares the heads of both
+    /// iterators for every element, this buffers up to [BINARY_MERGE_CHUNK_SIZE] elements of each side
+    /// and merges them by bisection. That needs far fewer comparisons when the inputs consist of long runs
+    /// that do not interleave, e.g. `0..1000` and `1000..2000`. For finely interleaved inputs it is no
+    /// better than comparing heads, and the buffering adds some overhead, so the plain union is preferable
+    /// there.
+    pub fn binary_union<J: Iterator<Item = I::Item>>(
+        self,
+        that: SortedIter<J>,
+    ) -> SortedIter<BinaryMergeIter<I, J>> {
+        SortedIter::new(BinaryMergeIter::new(
+            self.i,
+            that.i,
+            BINARY_MERGE_CHUNK_SIZE,
+        ))
+    }
+}
+
+type Infallibly<I> = Map<I, fn(<I as Iterator>::Item) -> Result<<I as Iterator>::Item, Infallible>>;
+type InfallibleChunks<I, J> =
+    ChunkedMergeState<<I as Iterator>::Item, Infallibly<I>, Infallibly<J>>;
+
+/// Iterator returned by [SortedIter::binary_union]
+pub struct BinaryMergeIter<I: Iterator, J: Iterator> {
+    state: InfallibleChunks<I, J>,
+    window: alloc::vec::IntoIter<I::Item>,
+    done: bool,
+}
+
+impl<I, J> BinaryMergeIter<I, J>
+where
+    I: Iterator,
+    J: Iterator<Item = I::Item>,
+    I::Item: Ord + Clone,
+{
+    /// A union of a and b that buffers up to chunk_size elements of each
+    ///
+    /// Both iterators must be strictly sorted. Panics if chunk_size is 0.
+    pub fn new(a: I, b: J, chunk_size: usize) -> Self {
+        let a: Infallibly<I> = a.map(Ok);
+        let b: Infallibly<J> = b.map(Ok);
+        Self {
+            state: ChunkedMergeState::new(a, b, chunk_size),
+            window: Vec::new().into_iter(),
+            done: false,
+        }
+    }
+}
+
+impl<I, J> Iterator for BinaryMergeIter<I, J>
+where
+    I: Iterator,
+    J: Iterator<Item = I::Item>,
+    I::Item: Ord + Clone,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(x) = self.window.next() {
+                return Some(x);
+            }
+            if self.done {
+                return None;
+            }
+            let mut window = Vec::new();
+            match self.state.merge_window(SetUnionOp, &mut window) {
+                Ok(more) => self.done = !more,
+                Err(never) => match never {},
+            }
+            self.window = window.into_iter();
+        }
+    }
+}
+
+impl<I, J> FusedIterator for BinaryMergeIter<I, J>
+where
+    I: Iterator,
+    J: Iterator<Item = I::Item>,
+    I::Item: Ord + Clone,
+{
 }
 
 impl<I: Iterator> Iterator for SortedIter<I> {
@@ -22,7 +702,180 @@ impl<I: Iterator> Iterator for SortedIter<I> {
     }
 }
 
-pub(crate) struct SliceIterator<'a, T>(pub &'a [T]);
+/// An iterator of `(K, V)` pairs that is sorted by `K`, without duplicate keys
+///
+/// This is the counterpart of [SortedIter] for key-value pairs. The combinators only compare the keys.
+pub struct SortedPairIter<I> {
+    i: I,
+}
+
+#[cfg(feature = "std")]
+impl<I> sorted_iter::sorted_pair_iterator::SortedByKey for SortedPairIter<I> {}
+
+/// Wraps an iterator of `(K, V)` pairs that is known to be sorted by key, so the sorted combinators can be used
+///
+/// The order is not checked. If the iterator is not actually sorted by key, or contains duplicate keys, the
+/// combinators will give unspecified results.
+pub fn assume_sorted_by_key<I: IntoIterator>(iter: I) -> SortedPairIter<I::IntoIter> {
+    SortedPairIter::new(iter.into_iter())
+}
+
+impl<I> SortedPairIter<I> {
+    pub(crate) fn new(i: I) -> Self {
+        Self { i }
+    }
+}
+
+impl<K: Ord, V, I: Iterator<Item = (K, V)>> SortedPairIter<I> {
+    /// Lazy merge-join of two iterators sorted by key.
+    ///
+    /// Yields the key together with [EitherOrBoth::Left] for keys only in `self`, [EitherOrBoth::Right] for
+    /// keys only in `other`, and [EitherOrBoth::Both] for keys in both, in ascending order of the keys.
+    pub fn join<W, J>(self, other: SortedPairIter<J>) -> SortedPairIter<PairJoin<I, J>>
+    where
+        J: Iterator<Item = (K, W)>,
+    {
+        SortedPairIter::new(PairJoin {
+            a: self.i.peekable(),
+            b: other.i.peekable(),
+        })
+    }
+
+    /// Lazy union of two iterators sorted by key, combining the values of keys in both using `f`.
+    ///
+    /// `f` is called with the value of `self` and the value of `other`.
+    pub fn merge_with<J, F>(
+        self,
+        other: SortedPairIter<J>,
+        f: F,
+    ) -> SortedPairIter<PairMergeWith<I, J, F>>
+    where
+        J: Iterator<Item = (K, V)>,
+        F: FnMut(V, V) -> V,
+    {
+        SortedPairIter::new(PairMergeWith {
+            join: PairJoin {
+                a: self.i.peekable(),
+                b: other.i.peekable(),
+            },
+            f,
+        })
+    }
+}
+
+impl<I: Iterator> Iterator for SortedPairIter<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.i.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.i.size_hint()
+    }
+}
+
+impl<I: FusedIterator> FusedIterator for SortedPairIter<I> {}
+
+/// Iterator returned by [SortedPairIter::join]
+pub struct PairJoin<I: Iterator, J: Iterator> {
+    a: Peekable<I>,
+    b: Peekable<J>,
+}
+
+impl<K, V, W, I, J> Iterator for PairJoin<I, J>
+where
+    K: Ord,
+    I: Iterator<Item = (K, V)>,
+    J: Iterator<Item = (K, W)>,
+{
+    type Item = (K, EitherOrBoth<V, W>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let order = match (self.a.peek(), self.b.peek()) {
+            (Some((a, _)), Some((b, _))) => a.cmp(b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => return None,
+        };
+        Some(match order {
+            Ordering::Less => {
+                let (k, v) = self.a.next()?;
+                (k, EitherOrBoth::Left(v))
+            }
+            Ordering::Greater => {
+                let (k, w) = self.b.next()?;
+                (k, EitherOrBoth::Right(w))
+            }
+            Ordering::Equal => {
+                let (k, v) = self.a.next()?;
+                let (_, w) = self.b.next()?;
+                (k, EitherOrBoth::Both(v, w))
+            }
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (amin, amax) = self.a.size_hint();
+        let (bmin, bmax) = self.b.size_hint();
+        let max = match (amax, bmax) {
+            (Some(a), Some(b)) => a.checked_add(b),
+            _ => None,
+        };
+        (amin.max(bmin), max)
+    }
+}
+
+impl<K, V, W, I, J> FusedIterator for PairJoin<I, J>
+where
+    K: Ord,
+    I: FusedIterator<Item = (K, V)>,
+    J: FusedIterator<Item = (K, W)>,
+{
+}
+
+/// Iterator returned by [SortedPairIter::merge_with]
+pub struct PairMergeWith<I: Iterator, J: Iterator, F> {
+    join: PairJoin<I, J>,
+    f: F,
+}
+
+impl<K, V, I, J, F> Iterator for PairMergeWith<I, J, F>
+where
+    K: Ord,
+    I: Iterator<Item = (K, V)>,
+    J: Iterator<Item = (K, V)>,
+    F: FnMut(V, V) -> V,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (k, v) = self.join.next()?;
+        Some(match v {
+            EitherOrBoth::Left(v) | EitherOrBoth::Right(v) => (k, v),
+            EitherOrBoth::Both(a, b) => (k, (self.f)(a, b)),
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.join.size_hint()
+    }
+}
+
+impl<K, V, I, J, F> FusedIterator for PairMergeWith<I, J, F>
+where
+    K: Ord,
+    I: FusedIterator<Item = (K, V)>,
+    J: FusedIterator<Item = (K, V)>,
+    F: FnMut(V, V) -> V,
+{
+}
+
+/// An iterator over the elements of a slice, that can cheaply skip or take runs of elements
+///
+/// If the slice is sorted, [SliceIterator::into_sorted] turns this into a [SortedIter], so the sorted
+/// combinators can be used directly on the slice, without building a `VecSet` first.
+pub struct SliceIterator<'a, T>(pub(crate) &'a [T]);
 
 impl<'a, T> Iterator for SliceIterator<'a, T> {
     type Item = &'a T;
@@ -36,20 +889,982 @@ impl<'a, T> Iterator for SliceIterator<'a, T> {
             Some(res)
         }
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.len(), Some(self.0.len()))
+    }
+}
+
+impl<'a, T> ExactSizeIterator for SliceIterator<'a, T> {}
+
+impl<'a, T> FusedIterator for SliceIterator<'a, T> {}
+
+impl<'a, T> DoubleEndedIterator for SliceIterator<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.0.is_empty() {
+            None
+        } else {
+            let n = self.0.len() - 1;
+            let res: Self::Item = &self.0[n];
+            self.0 = &self.0[..n];
+            Some(res)
+        }
+    }
+}
+
+/// A forward cursor over the elements that are currently available to a merge
+///
+/// The read only merge states are generic over this, so they can run on a [SliceIterator] over a whole
+/// slice, or on a cursor that refills a buffer from a source between merges, for out-of-core merging.
+pub(crate) trait Cursor<T> {
+    /// The elements that are currently available
+    fn head(&self) -> &[T];
+    /// Consume the first n available elements
+    fn advance(&mut self, n: usize);
+    /// The number of elements that are currently available
+    fn remaining(&self) -> usize {
+        self.head().len()
+    }
+}
+
+impl<'a, T> Cursor<T> for SliceIterator<'a, T> {
+    fn head(&self) -> &[T] {
+        self.0
+    }
+    fn advance(&mut self, n: usize) {
+        self.drop_front(n)
+    }
 }
 
 impl<'a, T> SliceIterator<'a, T> {
+    pub fn new(slice: &'a [T]) -> Self {
+        Self(slice)
+    }
+
+    /// Wraps this in a [SortedIter], assuming that the slice is sorted
+    ///
+    /// The order is not checked. If the slice is not sorted, the combinators will give unspecified results.
+    pub fn into_sorted(self) -> SortedIter<Self> {
+        SortedIter::new(self)
+    }
+
     pub fn as_slice(&self) -> &[T] {
         self.0
     }
 
-    pub(crate) fn drop_front(&mut self, n: usize) {
+    /// The element that `next` would return, without advancing
+    pub fn peek_front(&self) -> Option<&'a T> {
+        self.0.first()
+    }
+
+    /// The element that `next_back` would return, without advancing
+    pub fn peek_back(&self) -> Option<&'a T> {
+        self.0.last()
+    }
+
+    /// Skips the first n elements
+    ///
+    /// Panics if there are less than n elements. See [SliceIterator::try_drop_front] for a variant that does not.
+    pub fn drop_front(&mut self, n: usize) {
         self.0 = &self.0[n..];
     }
 
-    pub(crate) fn take_front(&mut self, n: usize) -> &'a [T] {
+    /// Returns the first n elements as a slice, and advances past them
+    ///
+    /// Panics if there are less than n elements. See [SliceIterator::try_take_front] for a variant that does not.
+    pub fn take_front(&mut self, n: usize) -> &'a [T] {
         let res = &self.0[..n];
         self.0 = &self.0[n..];
         res
     }
+
+    /// Like [SliceIterator::take_front], but returns None instead of panicking if there are less than n elements
+    pub fn try_take_front(&mut self, n: usize) -> Option<&'a [T]> {
+        if n <= self.0.len() {
+            Some(self.take_front(n))
+        } else {
+            None
+        }
+    }
+
+    /// Like [SliceIterator::drop_front], but returns false instead of panicking if there are less than n elements
+    pub fn try_drop_front(&mut self, n: usize) -> bool {
+        let ok = n <= self.0.len();
+        if ok {
+            self.drop_front(n);
+        }
+        ok
+    }
+
+    /// Splits into the first mid elements and the rest, like `slice::split_at`
+    ///
+    /// Panics if mid is larger than the number of remaining elements.
+    pub fn split_at(self, mid: usize) -> (SliceIterator<'a, T>, SliceIterator<'a, T>) {
+        let (a, b) = self.0.split_at(mid);
+        (SliceIterator(a), SliceIterator(b))
+    }
+
+    /// The first element and an iterator over the rest, or None if empty
+    pub fn split_first(self) -> Option<(&'a T, SliceIterator<'a, T>)> {
+        let (first, rest) = self.0.split_first()?;
+        Some((first, SliceIterator(rest)))
+    }
+
+    /// The last element and an iterator over the rest, or None if empty
+    pub fn split_last(self) -> Option<(&'a T, SliceIterator<'a, T>)> {
+        let (last, rest) = self.0.split_last()?;
+        Some((last, SliceIterator(rest)))
+    }
+
+    /// Skips the last n elements
+    ///
+    /// Panics if there are less than n elements.
+    pub fn drop_back(&mut self, n: usize) {
+        self.0 = &self.0[..self.0.len() - n];
+    }
+
+    /// Returns the last n elements as a slice, and removes them from the end
+    ///
+    /// Panics if there are less than n elements.
+    pub fn take_back(&mut self, n: usize) -> &'a [T] {
+        let m = self.0.len() - n;
+        let res = &self.0[m..];
+        self.0 = &self.0[..m];
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        assume_sorted_by_item, assume_sorted_by_key, kmerge, kmerge_multi, try_sorted,
+        BinaryMergeIter, EitherOrBoth, SliceIterator, SortedIter,
+    };
+    use crate::{SortedIterator, VecSet};
+    use quickcheck_macros::quickcheck;
+    use sorted_iter::sorted_iterator::SortedByItem;
+    use sorted_iter::sorted_pair_iterator::SortedByKey;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    type Reference = BTreeSet<i64>;
+
+    fn is_sorted_by_item<I: Iterator + SortedByItem>(_i: &I) {}
+
+    fn is_sorted_by_key<I: Iterator + SortedByKey>(_i: &I) {}
+
+    #[quickcheck]
+    fn union(a: Reference, b: Reference) -> bool {
+        let a1: VecSet<i64> = a.iter().cloned().collect();
+        let b1: VecSet<i64> = b.iter().cloned().collect();
+        let r = a1.iter().union(b1.iter());
+        is_sorted_by_item(&r);
+        let expected: Vec<i64> = a.union(&b).cloned().collect();
+        let actual: Vec<i64> = r.cloned().collect();
+        expected == actual
+    }
+
+    fn intersection_vec(a: &[i64], b: &[i64]) -> Vec<i64> {
+        let a: VecSet<i64> = a.iter().cloned().collect();
+        let b: VecSet<i64> = b.iter().cloned().collect();
+        let r = a.iter().intersection(b.iter());
+        is_sorted_by_item(&r);
+        r.cloned().collect()
+    }
+
+    #[quickcheck]
+    fn intersection(a: Reference, b: Reference) -> bool {
+        let a1: Vec<i64> = a.iter().cloned().collect();
+        let b1: Vec<i64> = b.iter().cloned().collect();
+        let expected: Vec<i64> = a.intersection(&b).cloned().collect();
+        expected == intersection_vec(&a1, &b1)
+    }
+
+    #[test]
+    fn intersection_cases() {
+        // empty overlap
+        assert!(intersection_vec(&[1, 3, 5], &[2, 4, 6]).is_empty());
+        assert!(intersection_vec(&[], &[2, 4, 6]).is_empty());
+        // full overlap
+        assert_eq!(intersection_vec(&[1, 2, 3], &[1, 2, 3]), vec![1, 2, 3]);
+        // interleaved
+        assert_eq!(
+            intersection_vec(&[1, 2, 4, 7, 9], &[2, 3, 7, 8, 9]),
+            vec![2, 7, 9]
+        );
+    }
+
+    fn set(x: &[i64]) -> VecSet<i64> {
+        x.iter().cloned().collect()
+    }
+
+    #[quickcheck]
+    fn difference(a: Reference, b: Reference) -> bool {
+        let a1: VecSet<i64> = a.iter().cloned().collect();
+        let b1: VecSet<i64> = b.iter().cloned().collect();
+        let r = a1.iter().difference(b1.iter());
+        is_sorted_by_item(&r);
+        let expected: Vec<i64> = a.difference(&b).cloned().collect();
+        let actual: Vec<i64> = r.cloned().collect();
+        expected == actual
+    }
+
+    #[quickcheck]
+    fn symmetric_difference(a: Reference, b: Reference) -> bool {
+        let a1: VecSet<i64> = a.iter().cloned().collect();
+        let b1: VecSet<i64> = b.iter().cloned().collect();
+        let r = a1.iter().symmetric_difference(b1.iter());
+        is_sorted_by_item(&r);
+        let expected: Vec<i64> = a.symmetric_difference(&b).cloned().collect();
+        let actual: Vec<i64> = r.cloned().collect();
+        expected == actual
+    }
+
+    #[test]
+    fn duplicates_within_one_side() {
+        let a = vec![1, 1, 2, 3, 3, 3, 5];
+        let b = set(&[3, 4]);
+        let iter = |x: &Vec<i64>| SortedIter::new(x.clone().into_iter());
+        // without dedup, the duplicates flow through, and each element of b only cancels one of them
+        let diff: Vec<i64> = iter(&a).difference(b.iter().cloned()).collect();
+        assert_eq!(diff, vec![1, 1, 2, 3, 3, 5]);
+        // dedup suppresses them, so the results are the same as for sets
+        let diff: Vec<i64> = iter(&a).dedup().difference(b.iter().cloned()).collect();
+        assert_eq!(diff, vec![1, 2, 5]);
+        let xor: Vec<i64> = iter(&a)
+            .dedup()
+            .symmetric_difference(b.iter().cloned())
+            .collect();
+        assert_eq!(xor, vec![1, 2, 4, 5]);
+        let diff: Vec<i64> = b.iter().cloned().difference(iter(&a).dedup()).collect();
+        assert_eq!(diff, vec![4]);
+    }
+
+    #[test]
+    fn one_side_exhausted() {
+        let a = set(&[1, 2, 3]);
+        let b = set(&[2, 5, 6, 7]);
+        let xor: Vec<i64> = a.iter().symmetric_difference(b.iter()).cloned().collect();
+        assert_eq!(xor, vec![1, 3, 5, 6, 7]);
+        let xor: Vec<i64> = b.iter().symmetric_difference(a.iter()).cloned().collect();
+        assert_eq!(xor, vec![1, 3, 5, 6, 7]);
+        let diff: Vec<i64> = b.iter().difference(a.iter()).cloned().collect();
+        assert_eq!(diff, vec![5, 6, 7]);
+        let diff: Vec<i64> = a.iter().difference(b.iter()).cloned().collect();
+        assert_eq!(diff, vec![1, 3]);
+    }
+
+    fn merge_by_timestamp(a: &[(u32, u8)], b: &[(u32, u8)]) -> Vec<(u32, u8)> {
+        let a: VecSet<(u32, u8)> = a.iter().cloned().collect();
+        let b: VecSet<(u32, u8)> = b.iter().cloned().collect();
+        let r = a.iter().merge_by(b.iter(), |x, y| x.0.cmp(&y.0));
+        is_sorted_by_item(&r);
+        r.cloned().collect()
+    }
+
+    #[test]
+    fn merge_by_cases() {
+        // equal timestamps keep the left stream first
+        let a = [(1, 0), (3, 0), (3, 1), (5, 0)];
+        let b = [(2, 1), (3, 1), (5, 1), (6, 1)];
+        assert_eq!(
+            merge_by_timestamp(&a, &b),
+            vec![
+                (1, 0),
+                (2, 1),
+                (3, 0),
+                (3, 1),
+                (3, 1),
+                (5, 0),
+                (5, 1),
+                (6, 1)
+            ]
+        );
+        assert_eq!(merge_by_timestamp(&a, &[]), a.to_vec());
+        assert_eq!(merge_by_timestamp(&[], &b), b.to_vec());
+        assert!(merge_by_timestamp(&[], &[]).is_empty());
+    }
+
+    #[quickcheck]
+    fn merge_by_stable(a: Vec<u32>, b: Vec<u32>) -> bool {
+        // tag each element with its source, then sort each stream by timestamp only
+        let mut a: Vec<(u32, u8)> = a.into_iter().map(|t| (t % 16, 0)).collect();
+        let mut b: Vec<(u32, u8)> = b.into_iter().map(|t| (t % 16, 1)).collect();
+        a.sort_by_key(|x| x.0);
+        b.sort_by_key(|x| x.0);
+        let mut expected = a.clone();
+        expected.extend(b.iter().cloned());
+        // a stable sort of the concatenation puts left elements first for equal keys
+        expected.sort_by_key(|x| x.0);
+        let r = SortedIter::new(a.into_iter()).merge_by(b, |x, y| x.0.cmp(&y.0));
+        is_sorted_by_item(&r);
+        let (min, max) = r.size_hint();
+        let actual: Vec<(u32, u8)> = r.collect();
+        min == actual.len() && max == Some(actual.len()) && expected == actual
+    }
+
+    fn dedup_vec(a: Vec<i64>) -> Vec<i64> {
+        let r = SortedIter::new(a.into_iter()).dedup();
+        is_sorted_by_item(&r);
+        r.collect()
+    }
+
+    #[test]
+    fn dedup_cases() {
+        // runs at the start, middle and end
+        assert_eq!(dedup_vec(vec![1, 1, 1, 2, 3]), vec![1, 2, 3]);
+        assert_eq!(dedup_vec(vec![1, 2, 2, 2, 3]), vec![1, 2, 3]);
+        assert_eq!(dedup_vec(vec![1, 2, 3, 3, 3]), vec![1, 2, 3]);
+        assert_eq!(dedup_vec(vec![1, 1, 2, 3, 3, 4, 4]), vec![1, 2, 3, 4]);
+        // all equal
+        assert_eq!(dedup_vec(vec![5; 10]), vec![5]);
+        assert_eq!(dedup_vec(vec![1, 2, 3]), vec![1, 2, 3]);
+        assert!(dedup_vec(vec![]).is_empty());
+    }
+
+    #[quickcheck]
+    fn dedup_consistent(a: Vec<i64>) -> bool {
+        let mut a = a;
+        a.sort();
+        let mut expected = a.clone();
+        expected.dedup();
+        dedup_vec(a) == expected
+    }
+
+    fn results(
+        values: &[i64],
+        error_at: Option<usize>,
+    ) -> SortedIter<std::vec::IntoIter<Result<i64, usize>>> {
+        let mut res: Vec<Result<i64, usize>> = values.iter().cloned().map(Ok).collect();
+        if let Some(i) = error_at {
+            res.insert(i, Err(i));
+        }
+        SortedIter::new(res.into_iter())
+    }
+
+    #[test]
+    fn try_union_cases() {
+        let a = [1, 3, 5, 7];
+        let b = [2, 3, 6];
+        let r: Vec<_> = results(&a, None).try_union(results(&b, None)).collect();
+        assert_eq!(r, vec![Ok(1), Ok(2), Ok(3), Ok(5), Ok(6), Ok(7)]);
+        // error in the middle of a, before 5
+        let mut r = results(&a, Some(2)).try_union(results(&b, None));
+        let head: Vec<_> = r.by_ref().take(4).collect();
+        assert_eq!(head, vec![Ok(1), Ok(2), Ok(3), Err(2)]);
+        // nothing after the error
+        assert_eq!(r.next(), None);
+        assert_eq!(r.next(), None);
+        // error at the end of b
+        let r: Vec<_> = results(&a, None).try_union(results(&b, Some(3))).collect();
+        assert_eq!(r, vec![Ok(1), Ok(2), Ok(3), Ok(5), Ok(6), Err(3)]);
+        let r: Vec<_> = results(&[], Some(0)).try_union(results(&b, None)).collect();
+        assert_eq!(r, vec![Err(0)]);
+    }
+
+    #[test]
+    fn try_intersection_cases() {
+        let a = [1, 3, 5, 7];
+        let b = [3, 5, 6, 7];
+        let r: Vec<_> = results(&a, None)
+            .try_intersection(results(&b, None))
+            .collect();
+        assert_eq!(r, vec![Ok(3), Ok(5), Ok(7)]);
+        let r: Vec<_> = results(&a, Some(3))
+            .try_intersection(results(&b, None))
+            .collect();
+        assert_eq!(r, vec![Ok(3), Ok(5), Err(3)]);
+        // b is exhausted before the error is read
+        let r: Vec<_> = results(&a, Some(4))
+            .try_intersection(results(&[3], None))
+            .collect();
+        assert_eq!(r, vec![Ok(3)]);
+    }
+
+    #[quickcheck]
+    fn try_merge_consistent(a: BTreeSet<i64>, b: BTreeSet<i64>) -> bool {
+        let av: Vec<i64> = a.iter().cloned().collect();
+        let bv: Vec<i64> = b.iter().cloned().collect();
+        let union: Vec<Result<i64, usize>> =
+            results(&av, None).try_union(results(&bv, None)).collect();
+        let intersection: Vec<Result<i64, usize>> = results(&av, None)
+            .try_intersection(results(&bv, None))
+            .collect();
+        let expected_union: Vec<Result<i64, usize>> = a.union(&b).cloned().map(Ok).collect();
+        let expected_intersection: Vec<Result<i64, usize>> =
+            a.intersection(&b).cloned().map(Ok).collect();
+        union == expected_union && intersection == expected_intersection
+    }
+
+    #[test]
+    fn pair_join_cases() {
+        let a = assume_sorted_by_key(vec![(1, "a"), (3, "c"), (5, "e")]);
+        let b = assume_sorted_by_key(vec![(2, 20), (3, 30), (6, 60)]);
+        let r = a.join(b);
+        is_sorted_by_key(&r);
+        let r: Vec<_> = r.collect();
+        assert_eq!(
+            r,
+            vec![
+                (1, EitherOrBoth::Left("a")),
+                (2, EitherOrBoth::Right(20)),
+                (3, EitherOrBoth::Both("c", 30)),
+                (5, EitherOrBoth::Left("e")),
+                (6, EitherOrBoth::Right(60)),
+            ]
+        );
+        let a = assume_sorted_by_key(vec![(1, 1), (3, 3)]);
+        let b = assume_sorted_by_key(vec![(3, 30), (4, 40)]);
+        let r: Vec<_> = a.merge_with(b, |a, b| a + b).collect();
+        assert_eq!(r, vec![(1, 1), (3, 33), (4, 40)]);
+    }
+
+    #[test]
+    fn pair_join_is_lazy() {
+        // the join works on infinite streams, since it only pulls what it needs
+        let a = assume_sorted_by_key((0u64..).map(|x| (x * 2, x)));
+        let b = assume_sorted_by_key((0u64..).map(|x| (x * 3, x)));
+        let both: Vec<u64> = a
+            .join(b)
+            .filter_map(|(k, v)| match v {
+                EitherOrBoth::Both(_, _) => Some(k),
+                _ => None,
+            })
+            .take(4)
+            .collect();
+        assert_eq!(both, vec![0, 6, 12, 18]);
+    }
+
+    #[quickcheck]
+    fn pair_join_consistent(a: BTreeMap<u8, u32>, b: BTreeMap<u8, u32>) -> bool {
+        let joined: Vec<(u8, EitherOrBoth<u32, u32>)> = assume_sorted_by_key(a.clone())
+            .join(assume_sorted_by_key(b.clone()))
+            .collect();
+        let keys: BTreeSet<u8> = a.keys().chain(b.keys()).cloned().collect();
+        let expected: Vec<(u8, EitherOrBoth<u32, u32>)> = keys
+            .iter()
+            .map(|k| {
+                let v = match (a.get(k), b.get(k)) {
+                    (Some(a), Some(b)) => EitherOrBoth::Both(*a, *b),
+                    (Some(a), None) => EitherOrBoth::Left(*a),
+                    (None, Some(b)) => EitherOrBoth::Right(*b),
+                    (None, None) => unreachable!(),
+                };
+                (*k, v)
+            })
+            .collect();
+        let merged: BTreeMap<u8, u32> = assume_sorted_by_key(a.clone())
+            .merge_with(assume_sorted_by_key(b.clone()), u32::wrapping_add)
+            .collect();
+        let mut expected_merged = a.clone();
+        for (k, v) in b {
+            let e = expected_merged.entry(k).or_default();
+            *e = e.wrapping_add(v);
+        }
+        joined == expected && merged == expected_merged
+    }
+
+    fn run_lengths(a: Vec<i64>) -> Vec<(i64, usize)> {
+        let r = SortedIter::new(a.into_iter()).run_length_encode();
+        is_sorted_by_item(&r);
+        r.collect()
+    }
+
+    #[test]
+    fn run_length_encode_cases() {
+        // singletons, runs of different lengths, and a trailing run
+        assert_eq!(
+            run_lengths(vec![1, 2, 2, 3, 3, 3, 4, 5, 5, 5, 5]),
+            vec![(1, 1), (2, 2), (3, 3), (4, 1), (5, 4)]
+        );
+        assert_eq!(run_lengths(vec![1, 1, 2]), vec![(1, 2), (2, 1)]);
+        assert_eq!(run_lengths(vec![1, 2, 3]), vec![(1, 1), (2, 1), (3, 1)]);
+        assert_eq!(run_lengths(vec![7; 10]), vec![(7, 10)]);
+        assert!(run_lengths(vec![]).is_empty());
+    }
+
+    #[quickcheck]
+    fn run_length_encode_consistent(a: Vec<i64>) -> bool {
+        let mut a = a;
+        a.sort();
+        let r = run_lengths(a.clone());
+        let decoded: Vec<i64> = r
+            .iter()
+            .flat_map(|(x, n)| core::iter::repeat_n(*x, *n))
+            .collect();
+        let mut unique = a.clone();
+        unique.dedup();
+        decoded == a && r.iter().map(|(x, _)| *x).eq(unique.into_iter())
+    }
+
+    #[test]
+    fn count_unique_cases() {
+        let count = |a: Vec<i64>| SortedIter::new(a.into_iter()).count_unique();
+        assert_eq!(count(vec![1, 1, 1, 2, 3, 3]), 3);
+        assert_eq!(count(vec![1, 2, 2, 2, 3]), 3);
+        assert_eq!(count(vec![5; 10]), 1);
+        assert_eq!(count(vec![1, 2, 3]), 3);
+        assert_eq!(count(vec![]), 0);
+    }
+
+    #[quickcheck]
+    fn count_unique_consistent(a: Vec<i64>) -> bool {
+        let mut a = a;
+        a.sort();
+        let expected = a.iter().collect::<BTreeSet<_>>().len();
+        SortedIter::new(a.into_iter()).count_unique() == expected
+    }
+
+    /// 10 sorted streams with lots of overlap between them
+    fn streams() -> Vec<Vec<i64>> {
+        (0..10)
+            .map(|i| (0..50).map(|j| j * (i + 1) + i).collect())
+            .collect()
+    }
+
+    #[test]
+    fn kmerge_10_streams() {
+        let streams = streams();
+        let sets: Vec<VecSet<i64>> = streams.iter().map(|x| x.clone().into()).collect();
+        let r = kmerge(sets.iter().map(|x| x.iter()).collect());
+        is_sorted_by_item(&r);
+        let actual: Vec<i64> = r.cloned().collect();
+        let mut expected: Vec<i64> = streams.iter().flatten().cloned().collect();
+        expected.sort();
+        expected.dedup();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn kmerge_multi_10_streams() {
+        let streams = streams();
+        let sets: Vec<VecSet<i64>> = streams.iter().map(|x| x.clone().into()).collect();
+        let r = kmerge_multi(sets.iter().map(|x| x.iter()).collect());
+        is_sorted_by_item(&r);
+        let actual: Vec<i64> = r.cloned().collect();
+        let mut expected: Vec<i64> = streams.iter().flatten().cloned().collect();
+        expected.sort();
+        assert_eq!(actual, expected);
+        assert!(kmerge_multi::<std::vec::IntoIter<i64>>(vec![])
+            .next()
+            .is_none());
+    }
+
+    #[quickcheck]
+    fn kmerge_consistent(sets: Vec<Reference>) -> bool {
+        let sets1: Vec<VecSet<i64>> = sets.iter().map(|x| x.iter().cloned().collect()).collect();
+        let r = kmerge(sets1.iter().map(|x| x.iter()).collect());
+        let (min, max) = r.size_hint();
+        let actual: Vec<i64> = r.cloned().collect();
+        let expected: Vec<i64> = sets
+            .iter()
+            .flatten()
+            .cloned()
+            .collect::<Reference>()
+            .into_iter()
+            .collect();
+        min <= actual.len() && max >= Some(actual.len()) && actual == expected
+    }
+
+    #[quickcheck]
+    fn kmerge_multi_all(streams: Vec<Vec<u8>>) -> bool {
+        // few distinct values, so there are many duplicates within and across streams
+        let streams: Vec<Vec<u8>> = streams
+            .into_iter()
+            .map(|x| {
+                let mut x: Vec<u8> = x.into_iter().map(|v| v % 8).collect();
+                x.sort();
+                x
+            })
+            .collect();
+        let iters = streams
+            .iter()
+            .map(|x| SortedIter::new(x.iter().cloned()))
+            .collect();
+        let r = kmerge_multi(iters);
+        let (min, max) = r.size_hint();
+        let actual: Vec<u8> = r.collect();
+        let mut expected: Vec<u8> = streams.iter().flatten().cloned().collect();
+        expected.sort();
+        min == actual.len() && max == Some(actual.len()) && actual == expected
+    }
+
+    #[test]
+    fn collect_sorted_cases() {
+        let a: VecSet<i64> = vec![1, 3, 5].into();
+        let b: VecSet<i64> = vec![3, 4].into();
+        assert_eq!(a.clone().into_iter().collect_sorted(), a);
+        let union =
+            kmerge_multi(vec![a.clone().into_iter(), b.clone().into_iter()]).collect_sorted();
+        assert_eq!(union, a.union(&b));
+        // duplicates in a sorted iterator are removed
+        let r = assume_sorted_by_item(vec![1, 1, 2, 2, 2, 3]).collect_sorted();
+        assert_eq!(r.as_slice(), &[1, 2, 3]);
+        assert!(assume_sorted_by_item(Vec::<i64>::new())
+            .collect_sorted()
+            .is_empty());
+    }
+
+    #[quickcheck]
+    fn collect_sorted_consistent(a: Vec<i64>) -> bool {
+        let mut sorted = a.clone();
+        sorted.sort();
+        let expected: VecSet<i64> = a.into_iter().collect();
+        assume_sorted_by_item(sorted).collect_sorted() == expected
+    }
+
+    std::thread_local! {
+        static COMPARISONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+
+    /// an integer that counts how often it is compared
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct Counted(i64);
+
+    impl PartialOrd for Counted {
+        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Counted {
+        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+            COMPARISONS.with(|c| c.set(c.get() + 1));
+            self.0.cmp(&other.0)
+        }
+    }
+
+    fn comparisons<T>(f: impl FnOnce() -> T) -> (T, usize) {
+        COMPARISONS.with(|c| c.set(0));
+        let res = f();
+        (res, COMPARISONS.with(|c| c.get()))
+    }
+
+    #[quickcheck]
+    fn binary_union_consistent(a: Reference, b: Reference, chunk_size: u8) -> bool {
+        let expected: Vec<i64> = a.union(&b).cloned().collect();
+        let default: Vec<i64> = assume_sorted_by_item(a.clone())
+            .binary_union(assume_sorted_by_item(b.clone()))
+            .collect();
+        let chunk_size = chunk_size as usize % 8 + 1;
+        let chunked: Vec<i64> =
+            BinaryMergeIter::new(a.into_iter(), b.into_iter(), chunk_size).collect();
+        default == expected && chunked == expected
+    }
+
+    #[test]
+    fn binary_union_comparisons() {
+        let a: Vec<Counted> = (0..1000).map(Counted).collect();
+        let b: Vec<Counted> = (1000..2000).map(Counted).collect();
+        let (binary, binary_comparisons) = comparisons(|| {
+            assume_sorted_by_item(a.clone())
+                .binary_union(assume_sorted_by_item(b.clone()))
+                .collect::<Vec<_>>()
+        });
+        let (naive, naive_comparisons) = comparisons(|| {
+            assume_sorted_by_item(a.clone())
+                .union(assume_sorted_by_item(b.clone()))
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(binary, naive);
+        assert_eq!(binary.len(), 2000);
+        // runs that do not interleave only need a few comparisons per chunk
+        assert!(naive_comparisons >= 1000);
+        assert!(binary_comparisons * 5 < naive_comparisons);
+    }
+
+    #[test]
+    fn binary_union_is_lazy() {
+        // the sources are infinite, so this only terminates if the chunks are merged on demand
+        let evens = assume_sorted_by_item((0..).map(|x| x * 2));
+        let threes = assume_sorted_by_item((0..).map(|x| x * 3));
+        let r: Vec<i64> = evens.binary_union(threes).take(8).collect();
+        assert_eq!(r, vec![0, 2, 3, 4, 6, 8, 9, 10]);
+    }
+
+    #[test]
+    fn collect_btree_cases() {
+        let a: VecSet<i64> = vec![1, 3, 5].into();
+        let b: VecSet<i64> = vec![3, 4].into();
+        let expected: BTreeSet<i64> = vec![1, 3, 4, 5].into_iter().collect();
+        let union = kmerge_multi(vec![a.into_iter(), b.into_iter()]).collect_btree();
+        assert_eq!(union, expected);
+        let r = assume_sorted_by_item(vec![1, 1, 2, 3, 3]).collect_btree();
+        assert_eq!(r.into_iter().collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert!(assume_sorted_by_item(Vec::<i64>::new())
+            .collect_btree()
+            .is_empty());
+    }
+
+    #[quickcheck]
+    fn collect_btree_consistent(a: Vec<i64>) -> bool {
+        let mut sorted = a.clone();
+        sorted.sort();
+        let expected: BTreeSet<i64> = a.into_iter().collect();
+        assume_sorted_by_item(sorted).collect_btree() == expected
+    }
+
+    #[test]
+    fn assume_sorted() {
+        let a = assume_sorted_by_item(vec![1, 3, 5]);
+        is_sorted_by_item(&a);
+        let r: Vec<i64> = a.union(assume_sorted_by_item(vec![2, 3, 4])).collect();
+        assert_eq!(r, vec![1, 2, 3, 4, 5]);
+        // the order is not checked
+        let r: Vec<i64> = assume_sorted_by_item(vec![3, 1]).collect();
+        assert_eq!(r, vec![3, 1]);
+    }
+
+    #[test]
+    fn try_sorted_ok() {
+        let a = try_sorted(vec![1, 1, 3, 5]);
+        is_sorted_by_item(&a);
+        let r: Vec<i64> = a.intersection(try_sorted(vec![1, 5, 6])).collect();
+        assert_eq!(r, vec![1, 5]);
+        assert!(try_sorted(Vec::<i64>::new()).next().is_none());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "iterator is not sorted")]
+    fn try_sorted_unsorted() {
+        let _: Vec<i64> = try_sorted(vec![1, 3, 2]).collect();
+    }
+
+    #[test]
+    fn join_cases() {
+        use EitherOrBoth::*;
+        let a: VecSet<i64> = vec![1, 2, 4, 6].into();
+        let b: VecSet<i64> = vec![2, 3, 6, 7].into();
+        let r: Vec<_> = a.iter().join(b.iter()).collect();
+        assert_eq!(
+            r,
+            vec![
+                Left(&1),
+                Both(&2, &2),
+                Right(&3),
+                Left(&4),
+                Both(&6, &6),
+                Right(&7)
+            ]
+        );
+        let empty = VecSet::<i64>::empty();
+        let r: Vec<_> = a.iter().join(empty.iter()).collect();
+        assert_eq!(r, vec![Left(&1), Left(&2), Left(&4), Left(&6)]);
+        let r: Vec<_> = empty.iter().join(b.iter()).collect();
+        assert_eq!(r, vec![Right(&2), Right(&3), Right(&6), Right(&7)]);
+        assert_eq!(empty.iter().join(empty.iter()).next(), None);
+    }
+
+    #[quickcheck]
+    fn join_consistent(a: Reference, b: Reference) -> bool {
+        let a1: VecSet<i64> = a.iter().cloned().collect();
+        let b1: VecSet<i64> = b.iter().cloned().collect();
+        let r = a1.iter().join(b1.iter());
+        let (min, max) = r.size_hint();
+        let mut left = Vec::new();
+        let mut right = Vec::new();
+        let mut both = Vec::new();
+        let mut all = Vec::new();
+        for x in r {
+            match x {
+                EitherOrBoth::Left(a) => {
+                    left.push(*a);
+                    all.push(*a);
+                }
+                EitherOrBoth::Right(b) => {
+                    right.push(*b);
+                    all.push(*b);
+                }
+                EitherOrBoth::Both(a, b) => {
+                    if a != b {
+                        return false;
+                    }
+                    both.push(*a);
+                    all.push(*a);
+                }
+            }
+        }
+        let n = all.len();
+        min <= n
+            && max >= Some(n)
+            && left == a.difference(&b).cloned().collect::<Vec<_>>()
+            && right == b.difference(&a).cloned().collect::<Vec<_>>()
+            && both == a.intersection(&b).cloned().collect::<Vec<_>>()
+            && all == a.union(&b).cloned().collect::<Vec<_>>()
+    }
+
+    #[test]
+    fn slice_iterator_both_ends() {
+        let data: Vec<i64> = (0..10).collect();
+        let mut i = SliceIterator(&data);
+        assert_eq!(i.next(), Some(&0));
+        assert_eq!(i.next_back(), Some(&9));
+        assert_eq!(i.take_front(2), &[1, 2]);
+        assert_eq!(i.take_back(2), &[7, 8]);
+        assert_eq!(i.as_slice(), &[3, 4, 5, 6]);
+        i.drop_back(1);
+        i.drop_front(1);
+        assert_eq!(i.as_slice(), &[4, 5]);
+        assert_eq!(i.next_back(), Some(&5));
+        assert_eq!(i.next(), Some(&4));
+        assert_eq!(i.next(), None);
+        assert_eq!(i.next_back(), None);
+        let rev: Vec<i64> = SliceIterator(&data).rev().cloned().collect();
+        assert_eq!(rev, (0..10).rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn slice_iterator_peek() {
+        let data: Vec<i64> = (0..4).collect();
+        let mut i = SliceIterator(&data);
+        assert_eq!(i.peek_front(), Some(&0));
+        assert_eq!(i.peek_front(), Some(&0));
+        assert_eq!(i.peek_back(), Some(&3));
+        assert_eq!(i.len(), 4);
+        assert_eq!(i.next(), Some(&0));
+        assert_eq!(i.peek_back(), Some(&3));
+        assert_eq!(i.next_back(), Some(&3));
+        assert_eq!(i.peek_front(), Some(&1));
+        assert_eq!(i.peek_back(), Some(&2));
+        assert_eq!(i.next(), Some(&1));
+        assert_eq!(i.peek_front(), Some(&2));
+        assert_eq!(i.peek_back(), Some(&2));
+        assert_eq!(i.next_back(), Some(&2));
+        assert_eq!(i.peek_front(), None);
+        assert_eq!(i.peek_back(), None);
+        assert_eq!(i.next(), None);
+        let empty: [i64; 0] = [];
+        let i = SliceIterator(&empty);
+        assert_eq!(i.peek_front(), None);
+        assert_eq!(i.peek_back(), None);
+    }
+
+    #[quickcheck]
+    fn slice_iterator_peek_matches_next(data: Vec<i64>, from_back: Vec<bool>) -> bool {
+        let mut i = SliceIterator(&data);
+        for back in from_back {
+            let (peeked, taken) = if back {
+                (i.peek_back(), i.next_back())
+            } else {
+                (i.peek_front(), i.next())
+            };
+            if peeked != taken {
+                return false;
+            }
+        }
+        true
+    }
+
+    #[test]
+    fn slice_iterator_try_front() {
+        let data: Vec<i64> = (0..4).collect();
+        let mut i = SliceIterator(&data);
+        // one past the end fails and leaves the iterator unchanged
+        assert_eq!(i.try_take_front(5), None);
+        assert!(!i.try_drop_front(5));
+        assert_eq!(i.as_slice(), &[0, 1, 2, 3]);
+        assert_eq!(i.try_take_front(1), Some(&[0][..]));
+        assert!(i.try_drop_front(1));
+        assert_eq!(i.as_slice(), &[2, 3]);
+        // exactly the remaining length succeeds
+        assert_eq!(i.try_take_front(2), Some(&[2, 3][..]));
+        assert_eq!(i.try_take_front(0), Some(&[][..]));
+        assert!(i.try_drop_front(0));
+        assert_eq!(i.try_take_front(1), None);
+        assert!(!i.try_drop_front(1));
+        let mut i = SliceIterator(&data);
+        assert!(i.try_drop_front(4));
+        assert!(i.as_slice().is_empty());
+    }
+
+    #[test]
+    fn slice_iterator_into_sorted() {
+        let a = [1, 3, 5, 7, 9];
+        let b = [3, 4, 5, 9, 10];
+        let r: Vec<i64> = SliceIterator::new(&a)
+            .into_sorted()
+            .intersection(SliceIterator::new(&b).into_sorted())
+            .cloned()
+            .collect();
+        assert_eq!(r, vec![3, 5, 9]);
+        let r: Vec<i64> = SliceIterator::new(&a)
+            .into_sorted()
+            .union(SliceIterator::new(&b).into_sorted())
+            .cloned()
+            .collect();
+        assert_eq!(r, vec![1, 3, 4, 5, 7, 9, 10]);
+        // the combinators are lazy, so this does not look at the whole slice
+        let large: Vec<i64> = (0..1_000_000).collect();
+        let r: Vec<i64> = SliceIterator::new(&large)
+            .into_sorted()
+            .intersection(SliceIterator::new(&b).into_sorted())
+            .take(2)
+            .cloned()
+            .collect();
+        assert_eq!(r, vec![3, 4]);
+    }
+
+    #[quickcheck]
+    fn slice_iterator_intersection(a: Reference, b: Reference) -> bool {
+        let a1: Vec<i64> = a.iter().cloned().collect();
+        let b1: Vec<i64> = b.iter().cloned().collect();
+        let r = SliceIterator::new(&a1)
+            .into_sorted()
+            .intersection(SliceIterator::new(&b1).into_sorted());
+        is_sorted_by_item(&r);
+        r.eq(a.intersection(&b))
+    }
+
+    #[quickcheck]
+    fn slice_iterator_split_at(data: Vec<i64>, mid: usize) -> bool {
+        let mid = mid % (data.len() + 1);
+        let (a, b) = SliceIterator(&data).split_at(mid);
+        let joined: Vec<i64> = a.chain(b).cloned().collect();
+        joined == data && SliceIterator(&data).split_at(mid).0.len() == mid
+    }
+
+    #[test]
+    fn slice_iterator_split() {
+        let data: Vec<i64> = (0..4).collect();
+        let (a, b) = SliceIterator(&data).split_at(1);
+        assert_eq!(a.as_slice(), &[0]);
+        assert_eq!(b.as_slice(), &[1, 2, 3]);
+        let (a, b) = SliceIterator(&data).split_at(4);
+        assert_eq!(a.as_slice(), data.as_slice());
+        assert!(b.as_slice().is_empty());
+        let (first, rest) = SliceIterator(&data).split_first().unwrap();
+        assert_eq!(first, &0);
+        assert_eq!(rest.as_slice(), &[1, 2, 3]);
+        let (last, rest) = SliceIterator(&data).split_last().unwrap();
+        assert_eq!(last, &3);
+        assert_eq!(rest.as_slice(), &[0, 1, 2]);
+        let empty: Vec<i64> = Vec::new();
+        assert!(SliceIterator(&empty).split_first().is_none());
+        assert!(SliceIterator(&empty).split_last().is_none());
+    }
+
+    #[test]
+    #[should_panic]
+    fn slice_iterator_split_at_out_of_bounds() {
+        let data: Vec<i64> = (0..4).collect();
+        let _ = SliceIterator(&data).split_at(5);
+    }
+
+    #[test]
+    fn slice_iterator_len() {
+        let data: Vec<i64> = (0..10).collect();
+        let mut i = SliceIterator(&data);
+        assert_eq!(i.len(), 10);
+        assert_eq!(i.size_hint(), (10, Some(10)));
+        i.next();
+        assert_eq!(i.len(), 9);
+        i.drop_front(3);
+        assert_eq!(i.len(), 6);
+        i.next_back();
+        assert_eq!(i.len(), 5);
+        i.drop_front(5);
+        assert_eq!(i.len(), 0);
+        assert_eq!(i.next(), None);
+        assert_eq!(i.next(), None);
+    }
 }
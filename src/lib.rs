@@ -1,18 +1,24 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+extern crate alloc;
+
 #[cfg(test)]
-#[macro_use]
 extern crate quickcheck;
 
 #[cfg(test)]
 #[macro_use(quickcheck)]
 extern crate quickcheck_macros;
 
-#[macro_use]
+#[cfg(feature = "serde")]
+#[cfg_attr(feature = "std", macro_use)]
 extern crate serde;
 
 #[cfg(test)]
 extern crate maplit;
 
+#[cfg(feature = "std")]
 extern crate sorted_iter;
+#[cfg(feature = "std")]
 pub use sorted_iter::{SortedIterator, SortedPairIterator};
 
 #[cfg(test)]
@@ -25,32 +31,75 @@ mod flip_buffer;
 mod binary_merge;
 mod merge_state;
 
+#[cfg(feature = "std")]
 mod total_vec_seq;
+#[cfg(feature = "std")]
 mod vec_seq;
 
+#[cfg(feature = "std")]
+mod arc_vec_set;
+mod descending_vec_set;
+#[cfg(feature = "std")]
 mod total_vec_set;
 mod vec_set;
+mod vec_set_by;
+#[cfg(feature = "allocator_api")]
+mod vec_set_in;
+
+mod vec_bag;
 
+#[cfg(feature = "simd")]
+mod simd;
+
+#[cfg(feature = "std")]
 mod total_vec_map;
+#[cfg(feature = "std")]
 mod vec_map;
 
 mod dedup;
 mod iterators;
 
+// the serde_derive version we use predates the non_local_definitions lint
+#[cfg(feature = "std")]
+#[allow(dead_code, non_local_definitions)]
 mod radix_tree;
 
 #[cfg(test)]
 mod obey;
 
+#[cfg(test)]
+mod merge_properties;
+
+#[cfg(feature = "std")]
 pub mod range_set;
 
+#[cfg(feature = "std")]
 #[allow(dead_code)]
 mod sonic_reducer;
 
+#[cfg(feature = "std")]
+pub use arc_vec_set::*;
+pub use descending_vec_set::*;
+pub use iterators::{
+    assume_sorted_by_item, assume_sorted_by_key, kmerge, kmerge_multi, try_sorted, BinaryMergeIter,
+    CheckSorted, Dedup, EitherOrBoth, Join, KMerge, MergeBy, PairJoin, PairMergeWith, RunLengths,
+    SliceIterator, SortedIter, SortedPairIter, TryIntersection, TryUnion, BINARY_MERGE_CHUNK_SIZE,
+};
+pub use merge_state::{MergeIndex, Provenance};
+#[cfg(feature = "std")]
 pub use range_set::*;
+#[cfg(feature = "std")]
 pub use total_vec_map::*;
+#[cfg(feature = "std")]
 pub use total_vec_seq::*;
+#[cfg(feature = "std")]
 pub use total_vec_set::*;
+pub use vec_bag::*;
+#[cfg(feature = "std")]
 pub use vec_map::*;
+#[cfg(feature = "std")]
 pub use vec_seq::*;
 pub use vec_set::*;
+pub use vec_set_by::*;
+#[cfg(feature = "allocator_api")]
+pub use vec_set_in::*;
@@ -0,0 +1,127 @@
+//! Property tests that run every set operation through every merge strategy and merge state
+//!
+//! The results are compared with `BTreeSet`, and must themselves be strictly sorted. The inputs are
+//! generated as `BTreeSet`s, so they are sorted and deduplicated, and quickcheck shrinks them on failure.
+use crate::binary_merge::ShortcutMergeOperation;
+use crate::merge_state::{
+    BoolOpMergeState, ChunkedMergeState, CopyVecMergeState, CountMergeState, InPlaceMergeState,
+    SmallVecInPlaceMergeState, SmallVecMergeState, UnsafeInPlaceMergeState, UnsafeSliceMergeState,
+    VecMergeState,
+};
+use crate::vec_set::{SetDiffOp, SetIntersectionOp, SetUnionOp, SetXorOp};
+use quickcheck_macros::quickcheck;
+use smallvec::SmallVec;
+use std::collections::BTreeSet;
+
+type Reference = BTreeSet<i32>;
+
+fn is_strictly_sorted(x: &[i32]) -> bool {
+    x.windows(2).all(|w| w[0] < w[1])
+}
+
+/// the results of running the operation o with all strategies and states, labeled for error messages
+macro_rules! all_results {
+    ($a:expr, $b:expr, $o:expr) => {{
+        let a: &[i32] = $a;
+        let b: &[i32] = $b;
+        let mut res: Vec<(&'static str, Vec<i32>)> = Vec::new();
+        let mut m = VecMergeState::new(a, b, Vec::new());
+        $o.merge(&mut m);
+        res.push(("binary", m.into_vec()));
+        let mut m = VecMergeState::new(a, b, Vec::new());
+        $o.merge_galloping(&mut m);
+        res.push(("galloping", m.into_vec()));
+        let mut m = VecMergeState::new(a, b, Vec::new());
+        $o.merge_iterative(&mut m);
+        res.push(("iterative", m.into_vec()));
+        let mut m = VecMergeState::new(a, b, Vec::new());
+        $o.merge_adaptive(&mut m);
+        res.push(("adaptive", m.into_vec()));
+        let mut m = VecMergeState::new(a, b, Vec::new());
+        $o.merge_symmetric(&mut m);
+        res.push(("symmetric", m.into_vec()));
+        let mut m = VecMergeState::new(a, b, Vec::new());
+        $o.merge_linear0(&mut m, a.len(), b.len());
+        res.push(("linear", m.into_vec()));
+        res.push(("copy", CopyVecMergeState::merge_shortcut(a, b, $o)));
+        let r: SmallVec<[i32; 4]> = SmallVecMergeState::merge_shortcut(a, b, $o);
+        res.push(("smallvec", r.into_vec()));
+        let mut r = a.to_vec();
+        InPlaceMergeState::merge_shortcut(&mut r, b, $o);
+        res.push(("in place", r));
+        let mut r = a.to_vec();
+        UnsafeInPlaceMergeState::merge_shortcut(&mut r, b.to_vec(), $o);
+        res.push(("unsafe in place", r));
+        let mut r: SmallVec<[i32; 4]> = a.into();
+        SmallVecInPlaceMergeState::merge_shortcut(&mut r, SmallVec::<[i32; 4]>::from(b), $o);
+        res.push(("smallvec in place", r.into_vec()));
+        let mut r = a.to_vec();
+        r.extend_from_slice(b);
+        UnsafeSliceMergeState::merge(&mut r, a.len(), b.len(), $o);
+        res.push(("unsafe slice", r));
+        for chunk_size in 1..4 {
+            let mut state = ChunkedMergeState::new(
+                a.iter().cloned().map(Ok::<i32, ()>),
+                b.iter().cloned().map(Ok::<i32, ()>),
+                chunk_size,
+            );
+            let mut window = Vec::new();
+            let mut r = Vec::new();
+            while state.merge_window($o, &mut window).unwrap() {
+                r.extend_from_slice(&window);
+            }
+            res.push(("chunked", r));
+        }
+        res
+    }};
+}
+
+/// checks all results of the operation o against the expected result
+macro_rules! check_all {
+    ($a:expr, $b:expr, $o:expr, $expected:expr) => {{
+        let a: Vec<i32> = $a.iter().cloned().collect();
+        let b: Vec<i32> = $b.iter().cloned().collect();
+        let expected: Vec<i32> = $expected.cloned().collect();
+        let mut ok = true;
+        for (name, r) in all_results!(&a, &b, $o) {
+            if r != expected || !is_strictly_sorted(&r) {
+                println!("{} {}: {:?} != {:?}", stringify!($o), name, r, expected);
+                ok = false;
+            }
+        }
+        ok &= CountMergeState::merge(&a, &b, $o) == expected.len();
+        ok &= BoolOpMergeState::merge(&a, &b, $o) == !expected.is_empty();
+        ok
+    }};
+}
+
+fn all_ops_consistent(a: &Reference, b: &Reference) -> bool {
+    check_all!(a, b, SetUnionOp, a.union(b))
+        && check_all!(a, b, SetIntersectionOp, a.intersection(b))
+        && check_all!(a, b, SetDiffOp, a.difference(b))
+        && check_all!(a, b, SetXorOp, a.symmetric_difference(b))
+}
+
+#[quickcheck]
+fn merge_properties(a: Reference, b: Reference) -> bool {
+    all_ops_consistent(&a, &b) && all_ops_consistent(&b, &a)
+}
+
+/// a small set against a large dense one, so the strategies that adapt to the size ratio take different paths
+#[quickcheck]
+fn merge_properties_skewed(a: Reference, offset: i8) -> bool {
+    let b: Reference = (0..1000).map(|x| x * 3 + offset as i32).collect();
+    all_ops_consistent(&a, &b) && all_ops_consistent(&b, &a)
+}
+
+#[test]
+fn merge_properties_cases() {
+    let empty = Reference::new();
+    let a: Reference = (0..100).collect();
+    let b: Reference = (50..150).collect();
+    let c: Reference = (200..300).collect();
+    for (a, b) in &[(&empty, &empty), (&a, &empty), (&a, &a), (&a, &b), (&a, &c)] {
+        assert!(all_ops_consistent(a, b));
+        assert!(all_ops_consistent(b, a));
+    }
+}
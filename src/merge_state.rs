@@ -1,10 +1,18 @@
-use crate::binary_merge::{EarlyOut, MergeOperation, MergeStateRead, ShortcutMergeOperation};
+use alloc::vec::Vec;
+use crate::binary_merge::{
+    EarlyOut, MergeOperation, MergeOperation3, MergeStateRead, MergeStateRead3, NoShortcut,
+    ShortcutMergeOperation,
+};
 use crate::flip_buffer::InPlaceVecBuilder;
-use crate::iterators::SliceIterator;
+use crate::iterators::{Cursor, SliceIterator};
 use smallvec::{Array, SmallVec};
-use std::cmp::Ord;
-use std::default::Default;
-use std::fmt::Debug;
+use core::cmp::Ord;
+use core::default::Default;
+use core::fmt::Debug;
+use core::ops::Range;
+#[cfg(feature = "allocator_api")]
+use alloc::alloc::Allocator;
+use num_traits::PrimInt;
 use crate::flip_buffer::small_vec_builder::{InPlaceSmallVecBuilder, SmallVecIntoIter};
 
 /// A typical write part for the merge state
@@ -32,16 +40,22 @@ impl<A: Array, B: Array> SmallVecInPlaceMergeState<A, B> {
     }
 }
 
-impl<'a, A: Array, B: Array> MergeStateRead<A::Item, B::Item> for SmallVecInPlaceMergeState<A, B> {
-    fn a_slice(&self) -> &[A::Item] {
-        &self.a.source_slice()
+impl<A: Array, B: Array> MergeStateRead<A::Item, B::Item> for SmallVecInPlaceMergeState<A, B> {
+    fn a_len(&self) -> usize {
+        self.a.source_slice().len()
     }
-    fn b_slice(&self) -> &[B::Item] {
-        self.b.as_slice()
+    fn a_at(&self, i: usize) -> &A::Item {
+        &self.a.source_slice()[i]
+    }
+    fn b_len(&self) -> usize {
+        self.b.as_slice().len()
+    }
+    fn b_at(&self, i: usize) -> &B::Item {
+        &self.b.as_slice()[i]
     }
 }
 
-impl<'a, A: Array> MergeStateMut<A::Item, A::Item> for SmallVecInPlaceMergeState<A, A> {
+impl<A: Array> MergeStateMut<A::Item, A::Item> for SmallVecInPlaceMergeState<A, A> {
     fn advance_a(&mut self, n: usize, take: bool) -> EarlyOut {
         self.a.consume(n, take);
         Some(())
@@ -58,27 +72,28 @@ impl<'a, A: Array> MergeStateMut<A::Item, A::Item> for SmallVecInPlaceMergeState
     }
 }
 
-impl<'a, A: Array, B: Array> SmallVecInPlaceMergeState<A, B> {
+impl<A: Array, B: Array> SmallVecInPlaceMergeState<A, B> {
     pub fn merge_shortcut<O: ShortcutMergeOperation<A::Item, B::Item, Self>>(a: &mut SmallVec<A>, b: SmallVec<B>, o: O) {
         let mut t: SmallVec<A> = Default::default();
-        std::mem::swap(a, &mut t);
+        core::mem::swap(a, &mut t);
         let mut state = Self::new(t, b);
         o.merge(&mut state);
         *a = state.result();
     }
 
+    #[allow(dead_code)]
     pub fn merge<O: MergeOperation<A::Item, B::Item, Self>>(a: &mut SmallVec<A>, b: SmallVec<B>, o: O) {
         let mut t: SmallVec<A> = Default::default();
-        std::mem::swap(a, &mut t);
+        core::mem::swap(a, &mut t);
         let mut state = Self::new(t, b);
-        o.merge(&mut state);
+        NoShortcut(o).merge(&mut state);
         *a = state.result();
     }
 }
 
 pub(crate) struct UnsafeInPlaceMergeState<A, B> {
     pub a: InPlaceVecBuilder<A>,
-    pub b: std::vec::IntoIter<B>,
+    pub b: alloc::vec::IntoIter<B>,
 }
 
 impl<A, B> UnsafeInPlaceMergeState<A, B> {
@@ -93,34 +108,41 @@ impl<A, B> UnsafeInPlaceMergeState<A, B> {
     }
 }
 
-impl<'a, A, B> UnsafeInPlaceMergeState<A, B> {
+impl<A, B> UnsafeInPlaceMergeState<A, B> {
     pub fn merge_shortcut<O: ShortcutMergeOperation<A, B, Self>>(a: &mut Vec<A>, b: Vec<B>, o: O) {
         let mut t: Vec<A> = Default::default();
-        std::mem::swap(a, &mut t);
+        core::mem::swap(a, &mut t);
         let mut state = Self::new(t, b);
         o.merge(&mut state);
         *a = state.result();
     }
 
+    #[cfg(feature = "std")]
     pub fn merge<O: MergeOperation<A, B, Self>>(a: &mut Vec<A>, b: Vec<B>, o: O) {
         let mut t: Vec<A> = Default::default();
-        std::mem::swap(a, &mut t);
+        core::mem::swap(a, &mut t);
         let mut state = Self::new(t, b);
-        o.merge(&mut state);
+        NoShortcut(o).merge(&mut state);
         *a = state.result();
     }
 }
 
-impl<'a, A, B> MergeStateRead<A, B> for UnsafeInPlaceMergeState<A, B> {
-    fn a_slice(&self) -> &[A] {
-        &self.a.source_slice()
+impl<A, B> MergeStateRead<A, B> for UnsafeInPlaceMergeState<A, B> {
+    fn a_len(&self) -> usize {
+        self.a.source_slice().len()
     }
-    fn b_slice(&self) -> &[B] {
-        self.b.as_slice()
+    fn a_at(&self, i: usize) -> &A {
+        &self.a.source_slice()[i]
+    }
+    fn b_len(&self) -> usize {
+        self.b.as_slice().len()
+    }
+    fn b_at(&self, i: usize) -> &B {
+        &self.b.as_slice()[i]
     }
 }
 
-impl<'a, T> MergeStateMut<T, T> for UnsafeInPlaceMergeState<T, T> {
+impl<T> MergeStateMut<T, T> for UnsafeInPlaceMergeState<T, T> {
     fn advance_a(&mut self, n: usize, take: bool) -> EarlyOut {
         self.a.consume(n, take);
         Some(())
@@ -137,6 +159,59 @@ impl<'a, T> MergeStateMut<T, T> for UnsafeInPlaceMergeState<T, T> {
     }
 }
 
+/// A merge state where the first argument is filtered in place, and the second argument is only read
+///
+/// Since a and b can have different types, elements of b can not be taken. An operation can still push
+/// elements computed from b to a.
+#[cfg(feature = "std")]
+pub(crate) struct InPlaceFilterMergeState<'a, A, B> {
+    pub a: InPlaceVecBuilder<A>,
+    pub b: SliceIterator<'a, B>,
+}
+
+#[cfg(feature = "std")]
+impl<'a, A, B> InPlaceFilterMergeState<'a, A, B> {
+    pub fn merge<O: MergeOperation<A, B, Self>>(a: &mut Vec<A>, b: &'a [B], o: O) {
+        let mut t: Vec<A> = Default::default();
+        core::mem::swap(a, &mut t);
+        let mut state = Self {
+            a: t.into(),
+            b: SliceIterator(b),
+        };
+        NoShortcut(o).merge(&mut state);
+        *a = state.a.into_vec();
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, A, B> MergeStateRead<A, B> for InPlaceFilterMergeState<'a, A, B> {
+    fn a_len(&self) -> usize {
+        self.a.source_slice().len()
+    }
+    fn a_at(&self, i: usize) -> &A {
+        &self.a.source_slice()[i]
+    }
+    fn b_len(&self) -> usize {
+        self.b.as_slice().len()
+    }
+    fn b_at(&self, i: usize) -> &B {
+        &self.b.as_slice()[i]
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, A, B> MergeStateMut<A, B> for InPlaceFilterMergeState<'a, A, B> {
+    fn advance_a(&mut self, n: usize, take: bool) -> EarlyOut {
+        self.a.consume(n, take);
+        Some(())
+    }
+    fn advance_b(&mut self, n: usize, take: bool) -> EarlyOut {
+        assert!(!take, "elements of b can not be taken");
+        self.b.drop_front(n);
+        Some(())
+    }
+}
+
 /// a merge state where the first argument is modified in place
 pub(crate) struct InPlaceMergeState<'a, T> {
     a: Vec<T>,
@@ -148,12 +223,12 @@ pub(crate) struct InPlaceMergeState<'a, T> {
 }
 
 impl<'a, T: Debug> Debug for InPlaceMergeState<'a, T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(
             f,
             "a: {:?}, b: {:?}, r: {:?}",
-            self.a_slice(),
-            self.b_slice(),
+            &self.a[self.ab..],
+            self.b,
             self.r_slice(),
         )
     }
@@ -168,11 +243,27 @@ impl<'a, T> InPlaceMergeState<'a, T> {
 impl<'a, T: Clone + Default + Ord> InPlaceMergeState<'a, T> {
     pub fn merge_shortcut<O: ShortcutMergeOperation<T, T, Self>>(a: &mut Vec<T>, b: &'a [T], o: O) {
         let mut t: Vec<T> = Default::default();
-        std::mem::swap(a, &mut t);
+        core::mem::swap(a, &mut t);
         let mut state = InPlaceMergeState::new(t, b);
         o.merge(&mut state);
         *a = state.into_vec();
     }
+
+    /// Like `merge_shortcut`, but returns true if a was changed by the merge
+    pub fn merge_shortcut_tracked<
+        O: ShortcutMergeOperation<T, T, ChangeTrackingMergeState<Self>>,
+    >(
+        a: &mut Vec<T>,
+        b: &'a [T],
+        o: O,
+    ) -> bool {
+        let mut t: Vec<T> = Default::default();
+        core::mem::swap(a, &mut t);
+        let mut state = ChangeTrackingMergeState::new(InPlaceMergeState::new(t, b));
+        o.merge(&mut state);
+        *a = state.inner.into_vec();
+        state.changed
+    }
 }
 
 impl<'a, T: Clone + Default> InPlaceMergeState<'a, T> {
@@ -195,18 +286,24 @@ impl<'a, T: Clone + Default> InPlaceMergeState<'a, T> {
             // (for now!)
             let missing = self.b.len();
             let fill = T::default();
-            self.a.splice(ab..ab, std::iter::repeat(fill).take(missing));
+            self.a.splice(ab..ab, core::iter::repeat_n(fill, missing));
             self.ab += missing;
         }
     }
 }
 
 impl<'a, T> MergeStateRead<T, T> for InPlaceMergeState<'a, T> {
-    fn a_slice(&self) -> &[T] {
-        &self.a[self.ab..]
+    fn a_len(&self) -> usize {
+        self.a.len() - self.ab
     }
-    fn b_slice(&self) -> &[T] {
-        self.b
+    fn a_at(&self, i: usize) -> &T {
+        &self.a[self.ab + i]
+    }
+    fn b_len(&self) -> usize {
+        self.b.len()
+    }
+    fn b_at(&self, i: usize) -> &T {
+        &self.b[i]
     }
 }
 
@@ -238,25 +335,27 @@ impl<'a, T: Clone + Default> MergeStateMut<T, T> for InPlaceMergeState<'a, T> {
 }
 
 /// A merge state where we only track if elements have been produced, and abort as soon as the first element is produced
-pub(crate) struct BoolOpMergeState<'a, A, B> {
-    a: SliceIterator<'a, A>,
-    b: SliceIterator<'a, B>,
+pub(crate) struct BoolOpMergeState<CA, CB> {
+    a: CA,
+    b: CB,
     r: bool,
 }
 
-impl<'a, A: Debug, B: Debug> Debug for BoolOpMergeState<'a, A, B> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl<'a, A: Debug, B: Debug> Debug
+    for BoolOpMergeState<SliceIterator<'a, A>, SliceIterator<'a, B>>
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(
             f,
             "a: {:?}, b: {:?} r: {}",
-            self.a_slice(),
-            self.b_slice(),
+            self.a.as_slice(),
+            self.b.as_slice(),
             self.r
         )
     }
 }
 
-impl<'a, A, B> BoolOpMergeState<'a, A, B> {
+impl<'a, A, B> BoolOpMergeState<SliceIterator<'a, A>, SliceIterator<'a, B>> {
     pub fn new(a: &'a [A], b: &'a [B]) -> Self {
         Self {
             a: SliceIterator(a),
@@ -266,7 +365,7 @@ impl<'a, A, B> BoolOpMergeState<'a, A, B> {
     }
 }
 
-impl<'a, A, B> BoolOpMergeState<'a, A, B> {
+impl<'a, A, B> BoolOpMergeState<SliceIterator<'a, A>, SliceIterator<'a, B>> {
     pub fn merge<O: ShortcutMergeOperation<A, B, Self>>(a: &'a [A], b: &'a [B], o: O) -> bool {
         let mut state = Self::new(a, b);
         o.merge(&mut state);
@@ -274,22 +373,28 @@ impl<'a, A, B> BoolOpMergeState<'a, A, B> {
     }
 }
 
-impl<'a, A, B> MergeStateRead<A, B> for BoolOpMergeState<'a, A, B> {
-    fn a_slice(&self) -> &[A] {
-        self.a.as_slice()
+impl<A, B, CA: Cursor<A>, CB: Cursor<B>> MergeStateRead<A, B> for BoolOpMergeState<CA, CB> {
+    fn a_len(&self) -> usize {
+        self.a.remaining()
     }
-    fn b_slice(&self) -> &[B] {
-        self.b.as_slice()
+    fn a_at(&self, i: usize) -> &A {
+        &self.a.head()[i]
+    }
+    fn b_len(&self) -> usize {
+        self.b.remaining()
+    }
+    fn b_at(&self, i: usize) -> &B {
+        &self.b.head()[i]
     }
 }
 
-impl<'a, A, B> MergeStateMut<A, B> for BoolOpMergeState<'a, A, B> {
+impl<A, B, CA: Cursor<A>, CB: Cursor<B>> MergeStateMut<A, B> for BoolOpMergeState<CA, CB> {
     fn advance_a(&mut self, n: usize, take: bool) -> EarlyOut {
         if take {
             self.r = true;
             None
         } else {
-            self.a.drop_front(n);
+            self.a.advance(n);
             Some(())
         }
     }
@@ -298,12 +403,232 @@ impl<'a, A, B> MergeStateMut<A, B> for BoolOpMergeState<'a, A, B> {
             self.r = true;
             None
         } else {
-            self.b.drop_front(n);
+            self.b.advance(n);
+            Some(())
+        }
+    }
+}
+
+/// A merge state where we only count the number of elements that would be produced
+pub(crate) struct CountMergeState<CA, CB> {
+    a: CA,
+    b: CB,
+    r: usize,
+}
+
+impl<'a, A: Debug, B: Debug> Debug for CountMergeState<SliceIterator<'a, A>, SliceIterator<'a, B>> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "a: {:?}, b: {:?} r: {}",
+            self.a.as_slice(),
+            self.b.as_slice(),
+            self.r
+        )
+    }
+}
+
+impl<'a, A, B> CountMergeState<SliceIterator<'a, A>, SliceIterator<'a, B>> {
+    pub fn new(a: &'a [A], b: &'a [B]) -> Self {
+        Self {
+            a: SliceIterator(a),
+            b: SliceIterator(b),
+            r: 0,
+        }
+    }
+
+    pub fn merge<O: ShortcutMergeOperation<A, B, Self>>(a: &'a [A], b: &'a [B], o: O) -> usize {
+        let mut state = Self::new(a, b);
+        o.merge(&mut state);
+        state.r
+    }
+}
+
+impl<A, B, CA: Cursor<A>, CB: Cursor<B>> MergeStateRead<A, B> for CountMergeState<CA, CB> {
+    fn a_len(&self) -> usize {
+        self.a.remaining()
+    }
+    fn a_at(&self, i: usize) -> &A {
+        &self.a.head()[i]
+    }
+    fn b_len(&self) -> usize {
+        self.b.remaining()
+    }
+    fn b_at(&self, i: usize) -> &B {
+        &self.b.head()[i]
+    }
+}
+
+impl<A, B, CA: Cursor<A>, CB: Cursor<B>> MergeStateMut<A, B> for CountMergeState<CA, CB> {
+    fn advance_a(&mut self, n: usize, take: bool) -> EarlyOut {
+        if take {
+            self.r += n;
+        }
+        self.a.advance(n);
+        Some(())
+    }
+    fn advance_b(&mut self, n: usize, take: bool) -> EarlyOut {
+        if take {
+            self.r += n;
+        }
+        self.b.advance(n);
+        Some(())
+    }
+}
+
+/// A merge state that counts the taken elements and aborts once a threshold is reached
+pub(crate) struct ThresholdMergeState<CA, CB> {
+    a: CA,
+    b: CB,
+    r: usize,
+    k: usize,
+}
+
+impl<'a, A: Debug, B: Debug> Debug
+    for ThresholdMergeState<SliceIterator<'a, A>, SliceIterator<'a, B>>
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "a: {:?}, b: {:?} r: {} k: {}",
+            self.a.as_slice(),
+            self.b.as_slice(),
+            self.r,
+            self.k
+        )
+    }
+}
+
+impl<'a, A, B> ThresholdMergeState<SliceIterator<'a, A>, SliceIterator<'a, B>> {
+    pub fn new(a: &'a [A], b: &'a [B], k: usize) -> Self {
+        Self {
+            a: SliceIterator(a),
+            b: SliceIterator(b),
+            r: 0,
+            k,
+        }
+    }
+
+    /// true if merging a and b using o takes at least k elements
+    pub fn merge<O: ShortcutMergeOperation<A, B, Self>>(
+        a: &'a [A],
+        b: &'a [B],
+        k: usize,
+        o: O,
+    ) -> bool {
+        let mut state = Self::new(a, b, k);
+        if !state.reached() {
+            o.merge(&mut state);
+        }
+        state.reached()
+    }
+}
+
+impl<CA, CB> ThresholdMergeState<CA, CB> {
+    /// true if the threshold was reached
+    pub fn reached(&self) -> bool {
+        self.r >= self.k
+    }
+
+    fn take(&mut self, n: usize) -> EarlyOut {
+        self.r += n;
+        if self.reached() {
+            None
+        } else {
+            Some(())
+        }
+    }
+}
+
+impl<A, B, CA: Cursor<A>, CB: Cursor<B>> MergeStateRead<A, B> for ThresholdMergeState<CA, CB> {
+    fn a_len(&self) -> usize {
+        self.a.remaining()
+    }
+    fn a_at(&self, i: usize) -> &A {
+        &self.a.head()[i]
+    }
+    fn b_len(&self) -> usize {
+        self.b.remaining()
+    }
+    fn b_at(&self, i: usize) -> &B {
+        &self.b.head()[i]
+    }
+}
+
+impl<A, B, CA: Cursor<A>, CB: Cursor<B>> MergeStateMut<A, B> for ThresholdMergeState<CA, CB> {
+    fn advance_a(&mut self, n: usize, take: bool) -> EarlyOut {
+        self.a.advance(n);
+        if take {
+            self.take(n)
+        } else {
+            Some(())
+        }
+    }
+    fn advance_b(&mut self, n: usize, take: bool) -> EarlyOut {
+        self.b.advance(n);
+        if take {
+            self.take(n)
+        } else {
             Some(())
         }
     }
 }
 
+/// A merge state that does not produce any elements, but accumulates a value in collisions
+///
+/// Taking elements does nothing, so an operation has to update the accumulator r itself.
+#[cfg(feature = "std")]
+pub(crate) struct FoldMergeState<CA, CB, R> {
+    a: CA,
+    b: CB,
+    pub r: R,
+}
+
+#[cfg(feature = "std")]
+impl<'a, A, B, R> FoldMergeState<SliceIterator<'a, A>, SliceIterator<'a, B>, R> {
+    pub fn new(a: &'a [A], b: &'a [B], r: R) -> Self {
+        Self {
+            a: SliceIterator(a),
+            b: SliceIterator(b),
+            r,
+        }
+    }
+
+    pub fn merge<O: ShortcutMergeOperation<A, B, Self>>(a: &'a [A], b: &'a [B], r: R, o: O) -> R {
+        let mut state = Self::new(a, b, r);
+        o.merge(&mut state);
+        state.r
+    }
+}
+
+#[cfg(feature = "std")]
+impl<A, B, R, CA: Cursor<A>, CB: Cursor<B>> MergeStateRead<A, B> for FoldMergeState<CA, CB, R> {
+    fn a_len(&self) -> usize {
+        self.a.remaining()
+    }
+    fn a_at(&self, i: usize) -> &A {
+        &self.a.head()[i]
+    }
+    fn b_len(&self) -> usize {
+        self.b.remaining()
+    }
+    fn b_at(&self, i: usize) -> &B {
+        &self.b.head()[i]
+    }
+}
+
+#[cfg(feature = "std")]
+impl<A, B, R, CA: Cursor<A>, CB: Cursor<B>> MergeStateMut<A, B> for FoldMergeState<CA, CB, R> {
+    fn advance_a(&mut self, n: usize, _take: bool) -> EarlyOut {
+        self.a.advance(n);
+        Some(())
+    }
+    fn advance_b(&mut self, n: usize, _take: bool) -> EarlyOut {
+        self.b.advance(n);
+        Some(())
+    }
+}
+
 /// A merge state where we build into a new vector
 pub(crate) struct SmallVecMergeState<'a, A, B, Arr: Array> {
     pub a: SliceIterator<'a, A>,
@@ -312,8 +637,8 @@ pub(crate) struct SmallVecMergeState<'a, A, B, Arr: Array> {
 }
 
 impl<'a, A: Debug, B: Debug, Arr: Array> Debug for SmallVecMergeState<'a, A, B, Arr> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "a: {:?}, b: {:?}", self.a_slice(), self.b_slice(),)
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "a: {:?}, b: {:?}", self.a.as_slice(), self.b.as_slice(),)
     }
 }
 
@@ -341,20 +666,27 @@ impl<'a, A, B, Arr: Array> SmallVecMergeState<'a, A, B, Arr> {
         state.into_vec()
     }
 
+    #[allow(dead_code)]
     pub fn merge<O: MergeOperation<A, B, Self>>(a: &'a [A], b: &'a [B], o: O) -> SmallVec<Arr> {
         let t: SmallVec<Arr> = SmallVec::new();
         let mut state = Self::new(a, b, t);
-        o.merge(&mut state);
+        NoShortcut(o).merge(&mut state);
         state.into_vec()
     }
 }
 
 impl<'a, A, B, Arr: Array> MergeStateRead<A, B> for SmallVecMergeState<'a, A, B, Arr> {
-    fn a_slice(&self) -> &[A] {
-        self.a.as_slice()
+    fn a_len(&self) -> usize {
+        self.a.as_slice().len()
     }
-    fn b_slice(&self) -> &[B] {
-        self.b.as_slice()
+    fn a_at(&self, i: usize) -> &A {
+        &self.a.as_slice()[i]
+    }
+    fn b_len(&self) -> usize {
+        self.b.as_slice().len()
+    }
+    fn b_at(&self, i: usize) -> &B {
+        &self.b.as_slice()[i]
     }
 }
 
@@ -391,12 +723,12 @@ pub(crate) struct VecMergeState<'a, A, B, R> {
 }
 
 impl<'a, A: Debug, B: Debug, R: Debug> Debug for VecMergeState<'a, A, B, R> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(
             f,
             "a: {:?}, b: {:?}, r: {:?}",
-            self.a_slice(),
-            self.b_slice(),
+            self.a.as_slice(),
+            self.b.as_slice(),
             self.r
         )
     }
@@ -426,20 +758,55 @@ impl<'a, A, B, R> VecMergeState<'a, A, B, R> {
         state.into_vec()
     }
 
+    #[cfg(feature = "std")]
     pub fn merge<O: MergeOperation<A, B, Self>>(a: &'a [A], b: &'a [B], o: O) -> Vec<R> {
         let t: Vec<R> = Vec::new();
         let mut state = VecMergeState::new(a, b, t);
-        o.merge(&mut state);
+        NoShortcut(o).merge(&mut state);
         state.into_vec()
     }
+
+    /// Merge into the given vector, after clearing it. This reuses the capacity of the vector.
+    pub fn merge_shortcut_into<O: ShortcutMergeOperation<A, B, Self>>(
+        a: &'a [A],
+        b: &'a [B],
+        o: O,
+        out: &mut Vec<R>,
+    ) {
+        let mut t = core::mem::take(out);
+        t.clear();
+        let mut state = VecMergeState::new(a, b, t);
+        o.merge(&mut state);
+        *out = state.into_vec();
+    }
+
+    /// Merge into the given vector, after its current contents. Returns the range of the merged elements.
+    pub fn merge_shortcut_append<O: ShortcutMergeOperation<A, B, Self>>(
+        a: &'a [A],
+        b: &'a [B],
+        o: O,
+        out: &mut Vec<R>,
+    ) -> Range<usize> {
+        let start = out.len();
+        let mut state = VecMergeState::new(a, b, core::mem::take(out));
+        o.merge(&mut state);
+        *out = state.into_vec();
+        start..out.len()
+    }
 }
 
 impl<'a, A, B, R> MergeStateRead<A, B> for VecMergeState<'a, A, B, R> {
-    fn a_slice(&self) -> &[A] {
-        self.a.as_slice()
+    fn a_len(&self) -> usize {
+        self.a.as_slice().len()
     }
-    fn b_slice(&self) -> &[B] {
-        self.b.as_slice()
+    fn a_at(&self, i: usize) -> &A {
+        &self.a.as_slice()[i]
+    }
+    fn b_len(&self) -> usize {
+        self.b.as_slice().len()
+    }
+    fn b_at(&self, i: usize) -> &B {
+        &self.b.as_slice()[i]
     }
 }
 
@@ -462,74 +829,1068 @@ impl<'a, T: Clone> MergeStateMut<T, T> for VecMergeState<'a, T, T, T> {
     }
 }
 
-/// A merge state where we build into a new vector
-pub(crate) struct UnsafeSliceMergeState<T> {
-    a: *mut T,
-    an: usize,
-    b: *mut T,
-    bn: usize,
-    r: *mut T,
-    rn: usize,
+/// A three-way merge state where we build into a new vector
+pub(crate) struct VecMergeState3<'a, T> {
+    pub a: SliceIterator<'a, T>,
+    pub b: SliceIterator<'a, T>,
+    pub c: SliceIterator<'a, T>,
+    pub r: Vec<T>,
 }
 
-impl<T> UnsafeSliceMergeState<T> {
-    pub fn merge<O: ShortcutMergeOperation<T, T, Self>>(
-        v: &mut Vec<T>,
-        an: usize,
-        bn: usize,
-        o: O,
-    ) {
-        assert!(an + bn <= v.len());
-        let len = v.len();
-        let base = len - an - bn;
-        let rn = an + bn;
-        v.reserve(an + bn);
-        unsafe {
-            v.set_len(base);
-            let a = v.as_mut_ptr().add(base);
-            let b = v.as_mut_ptr().add(base + an);
-            let r = v.as_mut_ptr().add(base + an + bn);
-            let mut state = Self {
-                a,
-                an,
-                b,
-                bn,
-                r,
-                rn,
-            };
-            o.merge(&mut state);
-            let copied = rn - state.rn;
-            std::ptr::copy_nonoverlapping(r, a, copied);
-            v.set_len(base + copied);
+impl<'a, T> VecMergeState3<'a, T> {
+    pub fn new(a: &'a [T], b: &'a [T], c: &'a [T], r: Vec<T>) -> Self {
+        Self {
+            a: SliceIterator(a),
+            b: SliceIterator(b),
+            c: SliceIterator(c),
+            r,
         }
     }
+
+    pub fn merge<O: MergeOperation3<T, T, T, Self>>(
+        a: &'a [T],
+        b: &'a [T],
+        c: &'a [T],
+        o: O,
+    ) -> Vec<T> {
+        let mut state = Self::new(a, b, c, Vec::new());
+        o.merge(&mut state);
+        state.r
+    }
 }
 
-impl<T> MergeStateRead<T, T> for UnsafeSliceMergeState<T> {
+impl<'a, T> MergeStateRead3<T, T, T> for VecMergeState3<'a, T> {
     fn a_slice(&self) -> &[T] {
-        unsafe { std::slice::from_raw_parts(self.a, self.an) }
+        self.a.as_slice()
     }
     fn b_slice(&self) -> &[T] {
-        unsafe { std::slice::from_raw_parts(self.b, self.bn) }
+        self.b.as_slice()
+    }
+    fn c_slice(&self) -> &[T] {
+        self.c.as_slice()
     }
 }
 
-impl<T> MergeStateMut<T, T> for UnsafeSliceMergeState<T> {
-    fn advance_a(&mut self, n: usize, take: bool) -> EarlyOut {
-        if take {
-            unsafe {
-                std::ptr::copy_nonoverlapping(self.a, self.r, n);
-                self.a = self.a.add(n);
-                self.r = self.r.add(n);
-            }
-            self.rn -= n;
-        } else {
-            unsafe {
-                for i in 0..n {
-                    std::ptr::drop_in_place(self.a.add(i));
-                }
-                self.a = self.a.add(n);
-            }
+/// A merge state that builds into a new vector, and skips taken elements that are equal to the last one
+///
+/// This tolerates inputs that are sorted but contain runs of equal elements. The merge still produces its
+/// output in non-descending order for such inputs, so collapsing equal neighbours on emission gives a
+/// strictly ascending result. This works for union and intersection, but not for difference and symmetric
+/// difference, which would have to remove all elements of a run.
+pub(crate) struct DedupMergeState<'a, T> {
+    pub a: SliceIterator<'a, T>,
+    pub b: SliceIterator<'a, T>,
+    r: Vec<T>,
+}
+
+impl<'a, T: PartialEq + Clone> DedupMergeState<'a, T> {
+    pub fn new(a: &'a [T], b: &'a [T]) -> Self {
+        Self {
+            a: SliceIterator(a),
+            b: SliceIterator(b),
+            r: Vec::new(),
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        self.r
+    }
+
+    pub fn merge_shortcut<O: ShortcutMergeOperation<T, T, Self>>(
+        a: &'a [T],
+        b: &'a [T],
+        o: O,
+    ) -> Vec<T> {
+        let mut state = Self::new(a, b);
+        o.merge(&mut state);
+        state.into_vec()
+    }
+
+    fn push_dedup(r: &mut Vec<T>, elements: &[T]) {
+        for x in elements {
+            if r.last() != Some(x) {
+                r.push(x.clone());
+            }
+        }
+    }
+}
+
+impl<'a, T> MergeStateRead<T, T> for DedupMergeState<'a, T> {
+    fn a_len(&self) -> usize {
+        self.a.as_slice().len()
+    }
+    fn a_at(&self, i: usize) -> &T {
+        &self.a.as_slice()[i]
+    }
+    fn b_len(&self) -> usize {
+        self.b.as_slice().len()
+    }
+    fn b_at(&self, i: usize) -> &T {
+        &self.b.as_slice()[i]
+    }
+}
+
+impl<'a, T: PartialEq + Clone> MergeStateMut<T, T> for DedupMergeState<'a, T> {
+    fn advance_a(&mut self, n: usize, take: bool) -> EarlyOut {
+        if take {
+            Self::push_dedup(&mut self.r, self.a.take_front(n));
+        } else {
+            self.a.drop_front(n);
+        }
+        Some(())
+    }
+    fn advance_b(&mut self, n: usize, take: bool) -> EarlyOut {
+        if take {
+            Self::push_dedup(&mut self.r, self.b.take_front(n));
+        } else {
+            self.b.drop_front(n);
+        }
+        Some(())
+    }
+}
+
+/// A merge state for `Copy` types that builds into a new vector, allocated up front for the worst case
+///
+/// Since the result can never be larger than both inputs together, runs are copied into the reserved
+/// space without any capacity checks or clones. The result keeps the capacity of `a.len() + b.len()`.
+pub(crate) struct CopyVecMergeState<'a, T> {
+    pub a: SliceIterator<'a, T>,
+    pub b: SliceIterator<'a, T>,
+    r: Vec<T>,
+}
+
+impl<'a, T: Copy> CopyVecMergeState<'a, T> {
+    pub fn new(a: &'a [T], b: &'a [T]) -> Self {
+        Self {
+            a: SliceIterator(a),
+            b: SliceIterator(b),
+            r: Vec::with_capacity(a.len() + b.len()),
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        self.r
+    }
+
+    pub fn merge_shortcut<O: ShortcutMergeOperation<T, T, Self>>(
+        a: &'a [T],
+        b: &'a [T],
+        o: O,
+    ) -> Vec<T> {
+        let mut state = Self::new(a, b);
+        o.merge(&mut state);
+        state.into_vec()
+    }
+
+    fn push_slice(r: &mut Vec<T>, elements: &[T]) {
+        let len = r.len();
+        // the capacity covers all elements of a and b, and every element is taken at most once
+        assert!(elements.len() <= r.capacity() - len);
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                elements.as_ptr(),
+                r.as_mut_ptr().add(len),
+                elements.len(),
+            );
+            r.set_len(len + elements.len());
+        }
+    }
+}
+
+impl<'a, T> MergeStateRead<T, T> for CopyVecMergeState<'a, T> {
+    fn a_len(&self) -> usize {
+        self.a.as_slice().len()
+    }
+    fn a_at(&self, i: usize) -> &T {
+        &self.a.as_slice()[i]
+    }
+    fn b_len(&self) -> usize {
+        self.b.as_slice().len()
+    }
+    fn b_at(&self, i: usize) -> &T {
+        &self.b.as_slice()[i]
+    }
+}
+
+impl<'a, T: Copy> MergeStateMut<T, T> for CopyVecMergeState<'a, T> {
+    fn advance_a(&mut self, n: usize, take: bool) -> EarlyOut {
+        let elements = self.a.take_front(n);
+        if take {
+            Self::push_slice(&mut self.r, elements);
+        }
+        Some(())
+    }
+    fn advance_b(&mut self, n: usize, take: bool) -> EarlyOut {
+        let elements = self.b.take_front(n);
+        if take {
+            Self::push_slice(&mut self.r, elements);
+        }
+        Some(())
+    }
+}
+
+/// Filtering map entries by a set of keys. Only entries of a can be taken, since the keys in b are not entries.
+impl<'a, K: Clone, V: Clone> MergeStateMut<(K, V), K> for VecMergeState<'a, (K, V), K, (K, V)> {
+    fn advance_a(&mut self, n: usize, take: bool) -> EarlyOut {
+        if take {
+            self.r.extend_from_slice(self.a.take_front(n));
+        } else {
+            self.a.drop_front(n);
+        }
+        Some(())
+    }
+    fn advance_b(&mut self, n: usize, take: bool) -> EarlyOut {
+        assert!(!take, "elements of b can not be taken");
+        self.b.drop_front(n);
+        Some(())
+    }
+}
+
+/// A merge state where we build into a new vector that uses the given allocator
+#[cfg(feature = "allocator_api")]
+pub(crate) struct AllocVecMergeState<'a, T, A: Allocator> {
+    pub a: SliceIterator<'a, T>,
+    pub b: SliceIterator<'a, T>,
+    pub r: Vec<T, A>,
+}
+
+#[cfg(feature = "allocator_api")]
+impl<'a, T, A: Allocator> AllocVecMergeState<'a, T, A> {
+    pub fn merge<O: ShortcutMergeOperation<T, T, Self>>(
+        a: &'a [T],
+        b: &'a [T],
+        o: O,
+        alloc: A,
+    ) -> Vec<T, A> {
+        let mut state = Self {
+            a: SliceIterator(a),
+            b: SliceIterator(b),
+            r: Vec::new_in(alloc),
+        };
+        o.merge(&mut state);
+        state.r
+    }
+}
+
+#[cfg(feature = "allocator_api")]
+impl<'a, T, A: Allocator> MergeStateRead<T, T> for AllocVecMergeState<'a, T, A> {
+    fn a_len(&self) -> usize {
+        self.a.as_slice().len()
+    }
+    fn a_at(&self, i: usize) -> &T {
+        &self.a.as_slice()[i]
+    }
+    fn b_len(&self) -> usize {
+        self.b.as_slice().len()
+    }
+    fn b_at(&self, i: usize) -> &T {
+        &self.b.as_slice()[i]
+    }
+}
+
+#[cfg(feature = "allocator_api")]
+impl<'a, T: Clone, A: Allocator> MergeStateMut<T, T> for AllocVecMergeState<'a, T, A> {
+    fn advance_a(&mut self, n: usize, take: bool) -> EarlyOut {
+        if take {
+            self.r.extend_from_slice(self.a.take_front(n));
+        } else {
+            self.a.drop_front(n);
+        }
+        Some(())
+    }
+    fn advance_b(&mut self, n: usize, take: bool) -> EarlyOut {
+        if take {
+            self.r.extend_from_slice(self.b.take_front(n));
+        } else {
+            self.b.drop_front(n);
+        }
+        Some(())
+    }
+}
+
+/// A merge state that wraps another merge state, and aborts once k elements have been taken
+///
+/// Callbacks that would take more than the remaining number of elements take only as many as are needed.
+pub(crate) struct TakeMergeState<S> {
+    pub inner: S,
+    remaining: usize,
+}
+
+impl<S> TakeMergeState<S> {
+    pub fn new(inner: S, k: usize) -> Self {
+        Self {
+            inner,
+            remaining: k,
+        }
+    }
+
+    /// the number of elements to take out of n, None if we are done
+    fn take(&mut self, n: usize) -> Option<usize> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let n = n.min(self.remaining);
+        self.remaining -= n;
+        Some(n)
+    }
+}
+
+impl<A, B, S: MergeStateRead<A, B>> MergeStateRead<A, B> for TakeMergeState<S> {
+    fn a_len(&self) -> usize {
+        self.inner.a_len()
+    }
+    fn a_at(&self, i: usize) -> &A {
+        self.inner.a_at(i)
+    }
+    fn b_len(&self) -> usize {
+        self.inner.b_len()
+    }
+    fn b_at(&self, i: usize) -> &B {
+        self.inner.b_at(i)
+    }
+}
+
+impl<A, B, S: MergeStateMut<A, B>> MergeStateMut<A, B> for TakeMergeState<S> {
+    fn advance_a(&mut self, n: usize, take: bool) -> EarlyOut {
+        if take {
+            let n = self.take(n)?;
+            self.inner.advance_a(n, true)?;
+            if self.remaining == 0 {
+                return None;
+            }
+            Some(())
+        } else {
+            self.inner.advance_a(n, false)
+        }
+    }
+    fn advance_b(&mut self, n: usize, take: bool) -> EarlyOut {
+        if take {
+            let n = self.take(n)?;
+            self.inner.advance_b(n, true)?;
+            if self.remaining == 0 {
+                return None;
+            }
+            Some(())
+        } else {
+            self.inner.advance_b(n, false)
+        }
+    }
+}
+
+/// A merge state that wraps another merge state, and records whether the result differs from a
+///
+/// The result differs from a exactly if an element of a is dropped or an element of b is taken.
+pub(crate) struct ChangeTrackingMergeState<S> {
+    pub inner: S,
+    pub changed: bool,
+}
+
+impl<S> ChangeTrackingMergeState<S> {
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            changed: false,
+        }
+    }
+}
+
+impl<A, B, S: MergeStateRead<A, B>> MergeStateRead<A, B> for ChangeTrackingMergeState<S> {
+    fn a_len(&self) -> usize {
+        self.inner.a_len()
+    }
+    fn a_at(&self, i: usize) -> &A {
+        self.inner.a_at(i)
+    }
+    fn b_len(&self) -> usize {
+        self.inner.b_len()
+    }
+    fn b_at(&self, i: usize) -> &B {
+        self.inner.b_at(i)
+    }
+}
+
+impl<A, B, S: MergeStateMut<A, B>> MergeStateMut<A, B> for ChangeTrackingMergeState<S> {
+    fn advance_a(&mut self, n: usize, take: bool) -> EarlyOut {
+        self.changed |= !take && n > 0;
+        self.inner.advance_a(n, take)
+    }
+    fn advance_b(&mut self, n: usize, take: bool) -> EarlyOut {
+        self.changed |= take && n > 0;
+        self.inner.advance_b(n, take)
+    }
+}
+
+/// Which input(s) an element of a merge result came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Provenance {
+    /// only in the first input
+    A,
+    /// only in the second input
+    B,
+    /// in both inputs
+    Both,
+}
+
+/// A merge state that only records which kinds of elements occurred, without producing a result
+///
+/// `advance_a` and `advance_b` with take set record elements only in a and only in b. Operations mark
+/// collisions using `collide`. The merge is aborted as soon as all three kinds have been seen.
+pub(crate) struct RelationMergeState<'a, A, B> {
+    a: SliceIterator<'a, A>,
+    b: SliceIterator<'a, B>,
+    pub only_a: bool,
+    pub only_b: bool,
+    pub both: bool,
+}
+
+impl<'a, A, B> RelationMergeState<'a, A, B> {
+    pub fn new(a: &'a [A], b: &'a [B]) -> Self {
+        Self {
+            a: SliceIterator(a),
+            b: SliceIterator(b),
+            only_a: false,
+            only_b: false,
+            both: false,
+        }
+    }
+
+    fn check(&self) -> EarlyOut {
+        if self.only_a && self.only_b && self.both {
+            None
+        } else {
+            Some(())
+        }
+    }
+
+    /// Consume one element of both a and b, recording that there was a common element
+    pub fn collide(&mut self) -> EarlyOut {
+        self.a.drop_front(1);
+        self.b.drop_front(1);
+        self.both = true;
+        self.check()
+    }
+}
+
+impl<'a, A, B> MergeStateRead<A, B> for RelationMergeState<'a, A, B> {
+    fn a_len(&self) -> usize {
+        self.a.as_slice().len()
+    }
+    fn a_at(&self, i: usize) -> &A {
+        &self.a.as_slice()[i]
+    }
+    fn b_len(&self) -> usize {
+        self.b.as_slice().len()
+    }
+    fn b_at(&self, i: usize) -> &B {
+        &self.b.as_slice()[i]
+    }
+}
+
+impl<'a, A, B> MergeStateMut<A, B> for RelationMergeState<'a, A, B> {
+    fn advance_a(&mut self, n: usize, take: bool) -> EarlyOut {
+        self.a.drop_front(n);
+        self.only_a |= take && n > 0;
+        self.check()
+    }
+    fn advance_b(&mut self, n: usize, take: bool) -> EarlyOut {
+        self.b.drop_front(n);
+        self.only_b |= take && n > 0;
+        self.check()
+    }
+}
+
+/// A merge state that builds two new vectors, one for the taken elements of a and one for those of b
+pub(crate) struct SplitMergeState<'a, T> {
+    a: SliceIterator<'a, T>,
+    b: SliceIterator<'a, T>,
+    pub only_a: Vec<T>,
+    pub only_b: Vec<T>,
+}
+
+impl<'a, T: Clone> SplitMergeState<'a, T> {
+    pub fn new(a: &'a [T], b: &'a [T]) -> Self {
+        Self {
+            a: SliceIterator(a),
+            b: SliceIterator(b),
+            only_a: Vec::new(),
+            only_b: Vec::new(),
+        }
+    }
+
+    pub fn merge<O: ShortcutMergeOperation<T, T, Self>>(
+        a: &'a [T],
+        b: &'a [T],
+        o: O,
+    ) -> (Vec<T>, Vec<T>) {
+        let mut state = Self::new(a, b);
+        o.merge(&mut state);
+        (state.only_a, state.only_b)
+    }
+}
+
+impl<'a, T> MergeStateRead<T, T> for SplitMergeState<'a, T> {
+    fn a_len(&self) -> usize {
+        self.a.as_slice().len()
+    }
+    fn a_at(&self, i: usize) -> &T {
+        &self.a.as_slice()[i]
+    }
+    fn b_len(&self) -> usize {
+        self.b.as_slice().len()
+    }
+    fn b_at(&self, i: usize) -> &T {
+        &self.b.as_slice()[i]
+    }
+}
+
+impl<'a, T: Clone> MergeStateMut<T, T> for SplitMergeState<'a, T> {
+    fn advance_a(&mut self, n: usize, take: bool) -> EarlyOut {
+        if take {
+            self.only_a.extend_from_slice(self.a.take_front(n));
+        } else {
+            self.a.drop_front(n);
+        }
+        Some(())
+    }
+    fn advance_b(&mut self, n: usize, take: bool) -> EarlyOut {
+        if take {
+            self.only_b.extend_from_slice(self.b.take_front(n));
+        } else {
+            self.b.drop_front(n);
+        }
+        Some(())
+    }
+}
+
+/// A merge state that builds the union and the intersection of a and b at the same time
+///
+/// Taken elements of a and b go to the union. Operations handle collisions using `collide`, which adds
+/// the element of a to both results.
+pub(crate) struct UnionIntersectionMergeState<'a, T> {
+    a: SliceIterator<'a, T>,
+    b: SliceIterator<'a, T>,
+    pub union: Vec<T>,
+    pub intersection: Vec<T>,
+}
+
+impl<'a, T: Clone> UnionIntersectionMergeState<'a, T> {
+    pub fn new(a: &'a [T], b: &'a [T]) -> Self {
+        Self {
+            a: SliceIterator(a),
+            b: SliceIterator(b),
+            union: Vec::with_capacity(a.len().max(b.len())),
+            intersection: Vec::new(),
+        }
+    }
+
+    /// Consume one element of both a and b, adding the element of a to both results
+    pub fn collide(&mut self) -> EarlyOut {
+        let x = &self.a.take_front(1)[0];
+        self.union.push(x.clone());
+        self.intersection.push(x.clone());
+        self.b.drop_front(1);
+        Some(())
+    }
+}
+
+impl<'a, T> MergeStateRead<T, T> for UnionIntersectionMergeState<'a, T> {
+    fn a_len(&self) -> usize {
+        self.a.as_slice().len()
+    }
+    fn a_at(&self, i: usize) -> &T {
+        &self.a.as_slice()[i]
+    }
+    fn b_len(&self) -> usize {
+        self.b.as_slice().len()
+    }
+    fn b_at(&self, i: usize) -> &T {
+        &self.b.as_slice()[i]
+    }
+}
+
+impl<'a, T: Clone> MergeStateMut<T, T> for UnionIntersectionMergeState<'a, T> {
+    fn advance_a(&mut self, n: usize, take: bool) -> EarlyOut {
+        if take {
+            self.union.extend_from_slice(self.a.take_front(n));
+        } else {
+            self.a.drop_front(n);
+        }
+        Some(())
+    }
+    fn advance_b(&mut self, n: usize, take: bool) -> EarlyOut {
+        if take {
+            self.union.extend_from_slice(self.b.take_front(n));
+        } else {
+            self.b.drop_front(n);
+        }
+        Some(())
+    }
+}
+
+/// A merge state that builds into a new vector, and records for each taken element where it came from
+///
+/// `advance_a` and `advance_b` record `Provenance::A` and `Provenance::B`. Since the state can not tell a
+/// collision from two unrelated calls, operations mark collisions using `collide`.
+pub(crate) struct ProvenanceMergeState<'a, T> {
+    pub a: SliceIterator<'a, T>,
+    pub b: SliceIterator<'a, T>,
+    pub r: Vec<T>,
+    pub p: Vec<Provenance>,
+}
+
+impl<'a, T: Debug> Debug for ProvenanceMergeState<'a, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "a: {:?}, b: {:?}, r: {:?}, p: {:?}",
+            self.a.as_slice(),
+            self.b.as_slice(),
+            self.r,
+            self.p
+        )
+    }
+}
+
+impl<'a, T: Clone> ProvenanceMergeState<'a, T> {
+    pub fn new(a: &'a [T], b: &'a [T]) -> Self {
+        Self {
+            a: SliceIterator(a),
+            b: SliceIterator(b),
+            r: Vec::new(),
+            p: Vec::new(),
+        }
+    }
+
+    pub fn merge<O: ShortcutMergeOperation<T, T, Self>>(
+        a: &'a [T],
+        b: &'a [T],
+        o: O,
+    ) -> (Vec<T>, Vec<Provenance>) {
+        let mut state = Self::new(a, b);
+        o.merge(&mut state);
+        (state.r, state.p)
+    }
+
+    /// Consume one element of both a and b, taking the one from a and recording `Provenance::Both`
+    pub fn collide(&mut self) -> EarlyOut {
+        self.r.extend_from_slice(self.a.take_front(1));
+        self.b.drop_front(1);
+        self.p.push(Provenance::Both);
+        Some(())
+    }
+}
+
+impl<'a, T> MergeStateRead<T, T> for ProvenanceMergeState<'a, T> {
+    fn a_len(&self) -> usize {
+        self.a.as_slice().len()
+    }
+    fn a_at(&self, i: usize) -> &T {
+        &self.a.as_slice()[i]
+    }
+    fn b_len(&self) -> usize {
+        self.b.as_slice().len()
+    }
+    fn b_at(&self, i: usize) -> &T {
+        &self.b.as_slice()[i]
+    }
+}
+
+impl<'a, T: Clone> MergeStateMut<T, T> for ProvenanceMergeState<'a, T> {
+    fn advance_a(&mut self, n: usize, take: bool) -> EarlyOut {
+        if take {
+            self.r.extend_from_slice(self.a.take_front(n));
+            self.p.extend(core::iter::repeat_n(Provenance::A, n));
+        } else {
+            self.a.drop_front(n);
+        }
+        Some(())
+    }
+    fn advance_b(&mut self, n: usize, take: bool) -> EarlyOut {
+        if take {
+            self.r.extend_from_slice(self.b.take_front(n));
+            self.p.extend(core::iter::repeat_n(Provenance::B, n));
+        } else {
+            self.b.drop_front(n);
+        }
+        Some(())
+    }
+}
+
+/// An element of a merge result, given as indices into the inputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MergeIndex {
+    /// the element at this index of the first input
+    A(usize),
+    /// the element at this index of the second input
+    B(usize),
+    /// the elements at these indices of the first and the second input, which are equal
+    Both(usize, usize),
+}
+
+/// A merge state that records the indices of the taken elements instead of copying them
+///
+/// Like `ProvenanceMergeState`, but the result only refers to the inputs, so the elements do not have to be
+/// `Clone`. Operations mark collisions using `collide`.
+pub(crate) struct IndexMergeState<'a, T> {
+    pub a: SliceIterator<'a, T>,
+    pub b: SliceIterator<'a, T>,
+    a_offset: usize,
+    b_offset: usize,
+    pub r: Vec<MergeIndex>,
+}
+
+impl<'a, T: Debug> Debug for IndexMergeState<'a, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "a: {:?}, b: {:?}, r: {:?}",
+            self.a.as_slice(),
+            self.b.as_slice(),
+            self.r
+        )
+    }
+}
+
+impl<'a, T> IndexMergeState<'a, T> {
+    pub fn new(a: &'a [T], b: &'a [T]) -> Self {
+        Self {
+            a: SliceIterator(a),
+            b: SliceIterator(b),
+            a_offset: 0,
+            b_offset: 0,
+            r: Vec::new(),
+        }
+    }
+
+    pub fn merge<O: ShortcutMergeOperation<T, T, Self>>(
+        a: &'a [T],
+        b: &'a [T],
+        o: O,
+    ) -> Vec<MergeIndex> {
+        let mut state = Self::new(a, b);
+        o.merge(&mut state);
+        state.r
+    }
+
+    /// Consume one element of both a and b, recording `MergeIndex::Both`
+    pub fn collide(&mut self) -> EarlyOut {
+        self.r.push(MergeIndex::Both(self.a_offset, self.b_offset));
+        self.a.drop_front(1);
+        self.b.drop_front(1);
+        self.a_offset += 1;
+        self.b_offset += 1;
+        Some(())
+    }
+}
+
+impl<'a, T> MergeStateRead<T, T> for IndexMergeState<'a, T> {
+    fn a_len(&self) -> usize {
+        self.a.as_slice().len()
+    }
+    fn a_at(&self, i: usize) -> &T {
+        &self.a.as_slice()[i]
+    }
+    fn b_len(&self) -> usize {
+        self.b.as_slice().len()
+    }
+    fn b_at(&self, i: usize) -> &T {
+        &self.b.as_slice()[i]
+    }
+}
+
+impl<'a, T> MergeStateMut<T, T> for IndexMergeState<'a, T> {
+    fn advance_a(&mut self, n: usize, take: bool) -> EarlyOut {
+        if take {
+            self.r
+                .extend((self.a_offset..self.a_offset + n).map(MergeIndex::A));
+        }
+        self.a.drop_front(n);
+        self.a_offset += n;
+        Some(())
+    }
+    fn advance_b(&mut self, n: usize, take: bool) -> EarlyOut {
+        if take {
+            self.r
+                .extend((self.b_offset..self.b_offset + n).map(MergeIndex::B));
+        }
+        self.b.drop_front(n);
+        self.b_offset += n;
+        Some(())
+    }
+}
+
+/// A merge state for run-length-encoded sorted data
+///
+/// Elements are `(value, run_length)` pairs, standing for the values from `value` up to but excluding
+/// `value + run_length`. Runs are sorted by their start value. Taken runs are coalesced with the last run of
+/// the result if they overlap or touch, so the result is again non-overlapping and sorted, even though the
+/// merge only compares the start values. Empty runs are dropped. `value + run_length` must not overflow.
+pub(crate) struct RleMergeState<'a, T> {
+    pub a: SliceIterator<'a, (T, T)>,
+    pub b: SliceIterator<'a, (T, T)>,
+    pub r: Vec<(T, T)>,
+}
+
+impl<'a, T: Debug> Debug for RleMergeState<'a, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "a: {:?}, b: {:?}, r: {:?}",
+            self.a.as_slice(),
+            self.b.as_slice(),
+            self.r
+        )
+    }
+}
+
+impl<'a, T: PrimInt> RleMergeState<'a, T> {
+    pub fn new(a: &'a [(T, T)], b: &'a [(T, T)]) -> Self {
+        Self {
+            a: SliceIterator(a),
+            b: SliceIterator(b),
+            r: Vec::new(),
+        }
+    }
+
+    /// The union of two sets of runs
+    pub fn union(a: &'a [(T, T)], b: &'a [(T, T)]) -> Vec<(T, T)> {
+        let mut state = Self::new(a, b);
+        RleUnionOp.merge(&mut state);
+        state.r
+    }
+
+    fn push(&mut self, (start, len): (T, T)) {
+        if len.is_zero() {
+            return;
+        }
+        let end = start + len;
+        if let Some((last_start, last_len)) = self.r.last_mut() {
+            let last_end = *last_start + *last_len;
+            if start <= last_end {
+                if end > last_end {
+                    *last_len = end - *last_start;
+                }
+                return;
+            }
+        }
+        self.r.push((start, len));
+    }
+}
+
+impl<'a, T> MergeStateRead<(T, T), (T, T)> for RleMergeState<'a, T> {
+    fn a_len(&self) -> usize {
+        self.a.as_slice().len()
+    }
+    fn a_at(&self, i: usize) -> &(T, T) {
+        &self.a.as_slice()[i]
+    }
+    fn b_len(&self) -> usize {
+        self.b.as_slice().len()
+    }
+    fn b_at(&self, i: usize) -> &(T, T) {
+        &self.b.as_slice()[i]
+    }
+}
+
+impl<'a, T: PrimInt> MergeStateMut<(T, T), (T, T)> for RleMergeState<'a, T> {
+    fn advance_a(&mut self, n: usize, take: bool) -> EarlyOut {
+        if take {
+            for run in self.a.take_front(n) {
+                self.push(*run);
+            }
+        } else {
+            self.a.drop_front(n);
+        }
+        Some(())
+    }
+    fn advance_b(&mut self, n: usize, take: bool) -> EarlyOut {
+        if take {
+            for run in self.b.take_front(n) {
+                self.push(*run);
+            }
+        } else {
+            self.b.drop_front(n);
+        }
+        Some(())
+    }
+}
+
+/// union of runs, comparing by start value. On collision the runs are merged, keeping the longer run.
+struct RleUnionOp;
+
+impl<'a, T: PrimInt> ShortcutMergeOperation<(T, T), (T, T), RleMergeState<'a, T>> for RleUnionOp {
+    fn cmp(&self, a: &(T, T), b: &(T, T)) -> core::cmp::Ordering {
+        a.0.cmp(&b.0)
+    }
+    fn from_a(&self, m: &mut RleMergeState<'a, T>, n: usize) -> EarlyOut {
+        m.advance_a(n, true)
+    }
+    fn from_b(&self, m: &mut RleMergeState<'a, T>, n: usize) -> EarlyOut {
+        m.advance_b(n, true)
+    }
+    fn collision(&self, m: &mut RleMergeState<'a, T>) -> EarlyOut {
+        // both runs have the same start, so pushing both gives a single run of the maximum length
+        m.advance_a(1, true)?;
+        m.advance_b(1, true)
+    }
+}
+
+/// A merge state that builds a new vector, and resolves collisions using a tie break function
+///
+/// On collision, the tie break function is called with the two colliding elements. `Less` keeps the
+/// element from a, `Greater` keeps the element from b, and `Equal` keeps both, the element from a first.
+pub(crate) struct TieBreakMergeState<'a, T, F> {
+    pub inner: VecMergeState<'a, T, T, T>,
+    tie_break: F,
+}
+
+impl<'a, T: Clone, F: Fn(&T, &T) -> core::cmp::Ordering> TieBreakMergeState<'a, T, F> {
+    /// Merge a and b, which must be sorted according to cmp
+    pub fn merge<C: Fn(&T, &T) -> core::cmp::Ordering>(
+        a: &'a [T],
+        b: &'a [T],
+        cmp: C,
+        tie_break: F,
+    ) -> Vec<T> {
+        let mut state = Self {
+            inner: VecMergeState::new(a, b, Vec::new()),
+            tie_break,
+        };
+        NoShortcut(TieBreakOp(cmp)).merge(&mut state);
+        state.inner.into_vec()
+    }
+}
+
+impl<'a, T, F> MergeStateRead<T, T> for TieBreakMergeState<'a, T, F> {
+    fn a_len(&self) -> usize {
+        self.inner.a_len()
+    }
+    fn a_at(&self, i: usize) -> &T {
+        self.inner.a_at(i)
+    }
+    fn b_len(&self) -> usize {
+        self.inner.b_len()
+    }
+    fn b_at(&self, i: usize) -> &T {
+        self.inner.b_at(i)
+    }
+}
+
+impl<'a, T: Clone, F> MergeStateMut<T, T> for TieBreakMergeState<'a, T, F> {
+    fn advance_a(&mut self, n: usize, take: bool) -> EarlyOut {
+        self.inner.advance_a(n, take)
+    }
+    fn advance_b(&mut self, n: usize, take: bool) -> EarlyOut {
+        self.inner.advance_b(n, take)
+    }
+}
+
+/// merge using a custom comparison, resolving collisions with the tie break function of the state
+struct TieBreakOp<C>(C);
+
+impl<'a, T: Clone, C: Fn(&T, &T) -> core::cmp::Ordering, F: Fn(&T, &T) -> core::cmp::Ordering>
+    MergeOperation<T, T, TieBreakMergeState<'a, T, F>> for TieBreakOp<C>
+{
+    fn cmp(&self, a: &T, b: &T) -> core::cmp::Ordering {
+        (self.0)(a, b)
+    }
+    fn from_a(&self, m: &mut TieBreakMergeState<'a, T, F>, n: usize) {
+        m.advance_a(n, true);
+    }
+    fn from_b(&self, m: &mut TieBreakMergeState<'a, T, F>, n: usize) {
+        m.advance_b(n, true);
+    }
+    fn collision(&self, m: &mut TieBreakMergeState<'a, T, F>) {
+        let (keep_a, keep_b) = match (m.tie_break)(m.a_at(0), m.b_at(0)) {
+            core::cmp::Ordering::Less => (true, false),
+            core::cmp::Ordering::Greater => (false, true),
+            core::cmp::Ordering::Equal => (true, true),
+        };
+        m.advance_a(1, keep_a);
+        m.advance_b(1, keep_b);
+    }
+}
+
+/// A merge state where we build into a new vector
+#[allow(dead_code)]
+pub(crate) struct UnsafeSliceMergeState<T> {
+    a: *mut T,
+    an: usize,
+    b: *mut T,
+    bn: usize,
+    r: *mut T,
+    rn: usize,
+}
+
+#[allow(dead_code)]
+impl<T> UnsafeSliceMergeState<T> {
+    // set_len only ever shrinks the vec here, the reserved space is written by the merge
+    #[allow(clippy::uninit_vec)]
+    pub fn merge<O: ShortcutMergeOperation<T, T, Self>>(
+        v: &mut Vec<T>,
+        an: usize,
+        bn: usize,
+        o: O,
+    ) {
+        assert!(an + bn <= v.len());
+        let len = v.len();
+        let base = len - an - bn;
+        let rn = an + bn;
+        v.reserve(an + bn);
+        unsafe {
+            v.set_len(base);
+            let a = v.as_mut_ptr().add(base);
+            let b = v.as_mut_ptr().add(base + an);
+            let r = v.as_mut_ptr().add(base + an + bn);
+            let mut state = Self {
+                a,
+                an,
+                b,
+                bn,
+                r,
+                rn,
+            };
+            o.merge(&mut state);
+            let copied = rn - state.rn;
+            core::ptr::copy_nonoverlapping(r, a, copied);
+            v.set_len(base + copied);
+        }
+    }
+}
+
+impl<T> MergeStateRead<T, T> for UnsafeSliceMergeState<T> {
+    fn a_len(&self) -> usize {
+        self.an
+    }
+    fn a_at(&self, i: usize) -> &T {
+        debug_assert!(i < self.an);
+        unsafe { &*self.a.add(i) }
+    }
+    fn b_len(&self) -> usize {
+        self.bn
+    }
+    fn b_at(&self, i: usize) -> &T {
+        debug_assert!(i < self.bn);
+        unsafe { &*self.b.add(i) }
+    }
+}
+
+impl<T> MergeStateMut<T, T> for UnsafeSliceMergeState<T> {
+    fn advance_a(&mut self, n: usize, take: bool) -> EarlyOut {
+        if take {
+            unsafe {
+                core::ptr::copy_nonoverlapping(self.a, self.r, n);
+                self.a = self.a.add(n);
+                self.r = self.r.add(n);
+            }
+            self.rn -= n;
+        } else {
+            unsafe {
+                for i in 0..n {
+                    core::ptr::drop_in_place(self.a.add(i));
+                }
+                self.a = self.a.add(n);
+            }
         }
         self.an -= n;
         Some(())
@@ -537,7 +1898,7 @@ impl<T> MergeStateMut<T, T> for UnsafeSliceMergeState<T> {
     fn advance_b(&mut self, n: usize, take: bool) -> EarlyOut {
         if take {
             unsafe {
-                std::ptr::copy_nonoverlapping(self.b, self.r, n);
+                core::ptr::copy_nonoverlapping(self.b, self.r, n);
                 self.b = self.b.add(n);
                 self.r = self.r.add(n);
             }
@@ -545,7 +1906,7 @@ impl<T> MergeStateMut<T, T> for UnsafeSliceMergeState<T> {
         } else {
             unsafe {
                 for i in 0..n {
-                    std::ptr::drop_in_place(self.b.add(i));
+                    core::ptr::drop_in_place(self.b.add(i));
                 }
                 self.b = self.b.add(n);
             }
@@ -554,3 +1915,177 @@ impl<T> MergeStateMut<T, T> for UnsafeSliceMergeState<T> {
         Some(())
     }
 }
+
+/// A cursor that reads a fallible source in chunks, and exposes only a window of the buffered elements
+///
+/// The window is set by [RefillCursor::next_window], so that merging the windows of two cursors never
+/// has to look ahead.
+pub(crate) struct RefillCursor<T, S> {
+    source: S,
+    buf: Vec<T>,
+    done: bool,
+    chunk_size: usize,
+    window: usize,
+}
+
+impl<T: Ord, E, S: Iterator<Item = Result<T, E>>> RefillCursor<T, S> {
+    pub fn new(source: S, chunk_size: usize) -> Self {
+        assert!(chunk_size > 0);
+        Self {
+            source,
+            buf: Vec::with_capacity(chunk_size),
+            done: false,
+            chunk_size,
+            window: 0,
+        }
+    }
+
+    fn refill(&mut self) -> Result<(), E> {
+        while !self.done && self.buf.len() < self.chunk_size {
+            match self.source.next() {
+                Some(x) => self.buf.push(x?),
+                None => self.done = true,
+            }
+        }
+        Ok(())
+    }
+
+    /// Refills both cursors, and sets their windows to the elements that can be merged without looking ahead
+    ///
+    /// These are the elements that are less than or equal to the smaller of the last buffered elements of the
+    /// sides that are not yet exhausted. All elements still to be read are larger than that bound. Elements
+    /// that are still in the window of a cursor stay buffered. Returns false once both sources are exhausted.
+    pub fn next_window<S2: Iterator<Item = Result<T, E>>>(
+        a: &mut Self,
+        b: &mut RefillCursor<T, S2>,
+    ) -> Result<bool, E> {
+        a.refill()?;
+        b.refill()?;
+        if a.buf.is_empty() && b.buf.is_empty() {
+            return Ok(false);
+        }
+        // a side that is not exhausted has a full buffer, so last is never None for it
+        let bound = match (a.done, b.done) {
+            (false, false) => core::cmp::min(a.buf.last(), b.buf.last()),
+            (false, true) => a.buf.last(),
+            (true, false) => b.buf.last(),
+            (true, true) => None,
+        };
+        let (na, nb) = match bound {
+            Some(bound) => (
+                a.buf.partition_point(|x| x <= bound),
+                b.buf.partition_point(|x| x <= bound),
+            ),
+            None => (a.buf.len(), b.buf.len()),
+        };
+        a.window = na;
+        b.window = nb;
+        Ok(true)
+    }
+}
+
+impl<T, S> Cursor<T> for RefillCursor<T, S> {
+    fn head(&self) -> &[T] {
+        &self.buf[..self.window]
+    }
+    fn advance(&mut self, n: usize) {
+        self.buf.drain(..n);
+        self.window -= n;
+    }
+}
+
+/// A merge state for sorted sequences that are too large to be held in memory, that reads both sides in chunks
+///
+/// Both sources must be strictly sorted. Each call to [ChunkedMergeState::merge_window] tops up the buffers
+/// from the sources using [RefillCursor::next_window], and then merges the windows using the usual binary
+/// merge.
+pub(crate) struct ChunkedMergeState<T, A, B> {
+    a: RefillCursor<T, A>,
+    b: RefillCursor<T, B>,
+}
+
+impl<T: Ord, E, A: Iterator<Item = Result<T, E>>, B: Iterator<Item = Result<T, E>>>
+    ChunkedMergeState<T, A, B>
+{
+    pub fn new(a: A, b: B, chunk_size: usize) -> Self {
+        Self {
+            a: RefillCursor::new(a, chunk_size),
+            b: RefillCursor::new(b, chunk_size),
+        }
+    }
+
+    /// Merge the next window into out, after clearing it. Returns false once both sources are exhausted.
+    pub fn merge_window<O>(&mut self, o: O, out: &mut Vec<T>) -> Result<bool, E>
+    where
+        O: Copy + for<'x> ShortcutMergeOperation<T, T, VecMergeState<'x, T, T, T>>,
+    {
+        if !RefillCursor::next_window(&mut self.a, &mut self.b)? {
+            out.clear();
+            return Ok(false);
+        }
+        VecMergeState::merge_shortcut_into(self.a.head(), self.b.head(), o, out);
+        let (na, nb) = (self.a.remaining(), self.b.remaining());
+        self.a.advance(na);
+        self.b.advance(nb);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec_set::{SetDiffOp, SetIntersectionOp, SetUnionOp, SetXorOp};
+    use core::convert::Infallible;
+    use quickcheck_macros::quickcheck;
+    use std::collections::BTreeSet;
+
+    type Chunks = RefillCursor<i32, std::vec::IntoIter<Result<i32, Infallible>>>;
+
+    /// counts the result of o window by window, keeping a single merge state for the whole input
+    fn chunked_count<O>(a: &BTreeSet<i32>, b: &BTreeSet<i32>, chunk_size: usize, o: O) -> usize
+    where
+        O: ShortcutMergeOperation<i32, i32, CountMergeState<Chunks, Chunks>>,
+    {
+        let mut state = CountMergeState {
+            a: RefillCursor::new(
+                a.iter().map(|x| Ok(*x)).collect::<Vec<_>>().into_iter(),
+                chunk_size,
+            ),
+            b: RefillCursor::new(
+                b.iter().map(|x| Ok(*x)).collect::<Vec<_>>().into_iter(),
+                chunk_size,
+            ),
+            r: 0,
+        };
+        while RefillCursor::next_window(&mut state.a, &mut state.b).unwrap() {
+            o.merge(&mut state);
+            assert!(state.a.head().is_empty() && state.b.head().is_empty());
+        }
+        state.r
+    }
+
+    #[quickcheck]
+    fn refill_cursor_count(a: BTreeSet<i32>, b: BTreeSet<i32>, chunk_size: u8) -> bool {
+        let chunk_size = chunk_size as usize % 4 + 1;
+        chunked_count(&a, &b, chunk_size, SetUnionOp) == a.union(&b).count()
+            && chunked_count(&a, &b, chunk_size, SetIntersectionOp) == a.intersection(&b).count()
+            && chunked_count(&a, &b, chunk_size, SetDiffOp) == a.difference(&b).count()
+            && chunked_count(&a, &b, chunk_size, SetXorOp) == a.symmetric_difference(&b).count()
+    }
+
+    #[test]
+    fn refill_cursor_windows() {
+        let a: BTreeSet<i32> = (0..100).map(|x| x * 2).collect();
+        let b: BTreeSet<i32> = (0..100).map(|x| x * 3).collect();
+        // the buffers never hold more than the chunk size, but the count is the same as for the whole slices
+        assert_eq!(chunked_count(&a, &b, 7, SetUnionOp), a.union(&b).count());
+        assert_eq!(
+            chunked_count(&a, &b, 7, SetIntersectionOp),
+            CountMergeState::merge(
+                &a.iter().cloned().collect::<Vec<_>>(),
+                &b.iter().cloned().collect::<Vec<_>>(),
+                SetIntersectionOp
+            )
+        );
+    }
+}
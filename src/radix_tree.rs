@@ -1,5 +1,7 @@
 use num_traits::Zero;
+#[cfg(feature = "serde")]
 use serde::de::{Deserialize, Deserializer};
+#[cfg(feature = "serde")]
 use serde::ser::{Serialize, Serializer};
 use sorted_iter::*;
 use std::collections::BTreeMap;
@@ -11,6 +13,7 @@ pub struct TagTree<K: Ord, V> {
     children: BTreeMap<K, Self>,
 }
 
+#[cfg(feature = "serde")]
 impl<K: Ord + Clone + Serialize, V: Clone + Serialize> Serialize for TagTree<K, V> {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
         if let Some(v) = &self.value {
@@ -29,6 +32,7 @@ impl<K: Ord + Clone + Serialize, V: Clone + Serialize> Serialize for TagTree<K,
     }
 }
 
+#[cfg(feature = "serde")]
 impl<'de, K: Ord + Deserialize<'de>, V: Deserialize<'de>> Deserialize<'de> for TagTree<K, V> {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
         Ok(match DeserHelper::<K, V>::deserialize(deserializer)? {
@@ -40,6 +44,7 @@ impl<'de, K: Ord + Deserialize<'de>, V: Deserialize<'de>> Deserialize<'de> for T
     }
 }
 
+#[cfg(feature = "serde")]
 #[derive(Deserialize)]
 #[serde(untagged)]
 enum DeserHelper<K: Ord, V> {
@@ -199,6 +204,7 @@ mod tests {
         let r = &a + &b;
         let x = r.min(&b).is_empty();
         println!("{:?} {:?}", r, x);
+        #[cfg(feature = "serde")]
         println!("{}", serde_json::to_string_pretty(&r).unwrap());
     }
 }
@@ -75,6 +75,7 @@ use crate::binary_merge::{EarlyOut, MergeStateRead, ShortcutMergeOperation};
 use crate::flip_buffer::InPlaceVecBuilder;
 use std::cmp::Ordering;
 use std::fmt::Debug;
+use std::iter::FromIterator;
 use std::ops::Bound::*;
 use std::ops::{
     BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not, Range, RangeFrom, RangeTo,
@@ -145,7 +146,10 @@ impl<T> RangeSet<T> {
             boundaries,
         }
     }
-    fn iter(&self) -> Iter<T> {
+    /// An iterator over the disjoint ranges of this set in ascending order, as pairs of bounds
+    ///
+    /// Adjacent ranges are always merged, so the ranges returned are never empty and never touch.
+    pub fn iter(&self) -> Iter<'_, T> {
         Iter(self.below_all, self.boundaries.as_slice())
     }
     fn from_range_until(a: T) -> Self {
@@ -172,6 +176,7 @@ impl<T> RangeSet<T> {
 }
 
 impl<T: Ord + Clone> RangeSet<T> {
+    #[allow(dead_code)]
     fn from_range_bounds<R: RangeBounds<T>>(r: R) -> std::result::Result<Self, ()> {
         match (r.start_bound(), r.end_bound()) {
             (Bound::Unbounded, Bound::Unbounded) => Ok(Self::all()),
@@ -196,11 +201,14 @@ impl<T: Ord> RangeSet<T> {
     }
 
     pub fn is_disjoint(&self, that: &Self) -> bool {
-        !BoolMergeState::merge(self, that, IntersectionOp)
+        // the merge only looks at the boundaries, so check the region below all boundaries separately
+        let below_all = self.below_all && that.below_all;
+        !below_all && !BoolMergeState::merge(self, that, IntersectionOp)
     }
 
     pub fn is_subset(&self, that: &Self) -> bool {
-        !BoolMergeState::merge(&self, &that, DiffOp)
+        let below_all = self.below_all && !that.below_all;
+        !below_all && !BoolMergeState::merge(self, that, DiffOp)
     }
 
     pub fn contains(&self, value: &T) -> bool {
@@ -217,6 +225,31 @@ impl<T: Ord> From<Range<T>> for RangeSet<T> {
     }
 }
 
+impl<T: Ord> FromIterator<Range<T>> for RangeSet<T> {
+    /// The union of the given ranges, which may overlap and be in any order
+    ///
+    /// Empty ranges are ignored, and ranges that overlap or touch are coalesced into one.
+    fn from_iter<I: IntoIterator<Item = Range<T>>>(iter: I) -> Self {
+        let mut ranges: Vec<Range<T>> = iter.into_iter().filter(|r| r.start < r.end).collect();
+        ranges.sort_by(|a, b| a.start.cmp(&b.start));
+        let mut boundaries: Vec<T> = Vec::with_capacity(ranges.len() * 2);
+        for Range { start, end } in ranges {
+            match boundaries.last_mut() {
+                Some(last) if start <= *last => {
+                    if end > *last {
+                        *last = end;
+                    }
+                }
+                _ => {
+                    boundaries.push(start);
+                    boundaries.push(end);
+                }
+            }
+        }
+        Self::new(false, boundaries)
+    }
+}
+
 impl<T: Ord> From<RangeFrom<T>> for RangeSet<T> {
     fn from(value: RangeFrom<T>) -> Self {
         Self::from_range_from(value.start)
@@ -409,11 +442,17 @@ impl<'a, T> MergeStateMut<T> for BoolMergeState<'a, T> {
 }
 
 impl<'a, T> MergeStateRead<T, T> for BoolMergeState<'a, T> {
-    fn a_slice(&self) -> &[T] {
-        &self.a
+    fn a_len(&self) -> usize {
+        self.a.len()
     }
-    fn b_slice(&self) -> &[T] {
-        &self.b
+    fn a_at(&self, i: usize) -> &T {
+        &self.a[i]
+    }
+    fn b_len(&self) -> usize {
+        self.b.len()
+    }
+    fn b_at(&self, i: usize) -> &T {
+        &self.b[i]
     }
 }
 
@@ -476,13 +515,17 @@ impl<'a, T: Clone> MergeStateMut<T> for VecMergeState<'a, T> {
 }
 
 impl<'a, T> MergeStateRead<T, T> for VecMergeState<'a, T> {
-    /// The remaining data in a
-    fn a_slice(&self) -> &[T] {
-        &self.a
+    fn a_len(&self) -> usize {
+        self.a.len()
+    }
+    fn a_at(&self, i: usize) -> &T {
+        &self.a[i]
+    }
+    fn b_len(&self) -> usize {
+        self.b.len()
     }
-    /// The remaining data in b
-    fn b_slice(&self) -> &[T] {
-        &self.b
+    fn b_at(&self, i: usize) -> &T {
+        &self.b[i]
     }
 }
 
@@ -514,16 +557,22 @@ impl<T> InPlaceMergeState<T> {
     }
 }
 
-impl<'a, T> MergeStateRead<T, T> for InPlaceMergeState<T> {
-    fn a_slice(&self) -> &[T] {
-        self.a.source_slice()
+impl<T> MergeStateRead<T, T> for InPlaceMergeState<T> {
+    fn a_len(&self) -> usize {
+        self.a.source_slice().len()
     }
-    fn b_slice(&self) -> &[T] {
-        self.b.as_slice()
+    fn a_at(&self, i: usize) -> &T {
+        &self.a.source_slice()[i]
+    }
+    fn b_len(&self) -> usize {
+        self.b.as_slice().len()
+    }
+    fn b_at(&self, i: usize) -> &T {
+        &self.b.as_slice()[i]
     }
 }
 
-impl<'a, T> MergeStateMut<T> for InPlaceMergeState<T> {
+impl<T> MergeStateMut<T> for InPlaceMergeState<T> {
     fn advance_both(&mut self, copy: bool) -> EarlyOut {
         self.advance_a(1, copy);
         self.advance_b(1, false);
@@ -558,7 +607,7 @@ struct IntersectionOp;
 struct XorOp;
 struct DiffOp;
 
-impl<'a, T: Ord, M: MergeStateMut<T>> ShortcutMergeOperation<T, T, M> for UnionOp {
+impl<T: Ord, M: MergeStateMut<T>> ShortcutMergeOperation<T, T, M> for UnionOp {
     fn from_a(&self, m: &mut M, n: usize) -> EarlyOut {
         m.advance_a(n, !m.bc())
     }
@@ -573,7 +622,7 @@ impl<'a, T: Ord, M: MergeStateMut<T>> ShortcutMergeOperation<T, T, M> for UnionO
     }
 }
 
-impl<'a, T: Ord, M: MergeStateMut<T>> ShortcutMergeOperation<T, T, M> for IntersectionOp {
+impl<T: Ord, M: MergeStateMut<T>> ShortcutMergeOperation<T, T, M> for IntersectionOp {
     fn from_a(&self, m: &mut M, n: usize) -> EarlyOut {
         m.advance_a(n, m.bc())
     }
@@ -588,7 +637,7 @@ impl<'a, T: Ord, M: MergeStateMut<T>> ShortcutMergeOperation<T, T, M> for Inters
     }
 }
 
-impl<'a, T: Ord, M: MergeStateMut<T>> ShortcutMergeOperation<T, T, M> for DiffOp {
+impl<T: Ord, M: MergeStateMut<T>> ShortcutMergeOperation<T, T, M> for DiffOp {
     fn from_a(&self, m: &mut M, n: usize) -> EarlyOut {
         m.advance_a(n, !m.bc())
     }
@@ -596,14 +645,14 @@ impl<'a, T: Ord, M: MergeStateMut<T>> ShortcutMergeOperation<T, T, M> for DiffOp
         m.advance_b(n, m.ac())
     }
     fn collision(&self, m: &mut M) -> EarlyOut {
-        m.advance_both(m.ac() == !m.bc())
+        m.advance_both(m.ac() != m.bc())
     }
     fn cmp(&self, a: &T, b: &T) -> Ordering {
         a.cmp(b)
     }
 }
 
-impl<'a, T: Ord, M: MergeStateMut<T>> ShortcutMergeOperation<T, T, M> for XorOp {
+impl<T: Ord, M: MergeStateMut<T>> ShortcutMergeOperation<T, T, M> for XorOp {
     fn from_a(&self, m: &mut M, n: usize) -> EarlyOut {
         m.advance_a(n, true)
     }
@@ -698,6 +747,31 @@ mod tests {
         binary_property_test(&a, &b, a.is_disjoint(&b), |a, b| !(a & b))
     }
 
+    #[test]
+    fn is_disjoint_is_subset_below_all() {
+        let all = Test::all();
+        let empty = Test::empty();
+        // below 5, and below 0 or from 10
+        let until_5 = Test::new(true, vec![5]);
+        let outside = Test::new(true, vec![0, 10]);
+        let from_3 = Test::new(false, vec![3]);
+        assert!(!all.is_disjoint(&all));
+        assert!(all.is_subset(&all));
+        assert!(!all.is_subset(&empty));
+        assert!(empty.is_disjoint(&all));
+        assert!(empty.is_subset(&until_5));
+        // the sets only overlap below all boundaries
+        assert!(!until_5.is_disjoint(&outside));
+        assert!(!outside.is_disjoint(&until_5));
+        assert!(!until_5.is_subset(&from_3));
+        assert!(!until_5.is_disjoint(&from_3));
+        assert!(until_5.is_subset(&all));
+        assert!(!outside.is_subset(&until_5));
+        assert!(!until_5.is_subset(&outside));
+        assert!(Test::new(true, vec![-5]).is_subset(&outside));
+        assert!(Test::new(true, vec![-5]).is_disjoint(&from_3));
+    }
+
     #[quickcheck]
     fn is_subset_sample(a: Test, b: Test) -> bool {
         binary_property_test(&a, &b, a.is_subset(&b), |a, b| !a | b)
@@ -728,6 +802,69 @@ mod tests {
         binary_element_test(&a, &b, &a - &b, |a, b| a & !b)
     }
 
+    /// the ranges as a bitmask over the universe 0..32, by brute force point membership
+    fn points(ranges: &[Range<i64>]) -> u32 {
+        (0..32)
+            .filter(|x| ranges.iter().any(|r| r.contains(x)))
+            .fold(0, |acc, x| acc | (1 << x))
+    }
+
+    /// the set as a bitmask over the universe 0..32, using contains
+    fn mask(a: &Test) -> u32 {
+        (0..32)
+            .filter(|x| a.contains(x))
+            .fold(0, |acc, x| acc | (1 << x))
+    }
+
+    fn intervals(ranges: &[(u8, u8)]) -> Vec<Range<i64>> {
+        ranges
+            .iter()
+            .map(|(a, b)| (*a % 32) as i64..(*b % 33) as i64)
+            .collect()
+    }
+
+    /// the finite ranges of a, or None if it is unbounded
+    fn finite(a: &Test) -> Option<Vec<Range<i64>>> {
+        a.iter()
+            .map(|r| match r {
+                (Included(a), Excluded(b)) => Some(*a..*b),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[quickcheck]
+    fn from_intervals_membership(a: Vec<(u8, u8)>, b: Vec<(u8, u8)>) -> bool {
+        let (a, b) = (intervals(&a), intervals(&b));
+        let (ma, mb) = (points(&a), points(&b));
+        let a: Test = a.into_iter().collect();
+        let b: Test = b.into_iter().collect();
+        // the ranges are coalesced, so iterating them gives back the same set with no empty or touching ranges
+        let ranges = finite(&a).unwrap();
+        ranges.iter().all(|r| r.start < r.end)
+            && ranges.windows(2).all(|w| w[0].end < w[1].start)
+            && ranges.iter().cloned().collect::<Test>() == a
+            && mask(&a) == ma
+            && mask(&(&a | &b)) == ma | mb
+            && mask(&(&a & &b)) == ma & mb
+            && mask(&(&a - &b)) == ma & !mb
+            && mask(&(&a ^ &b)) == ma ^ mb
+    }
+
+    #[test]
+    fn from_intervals_cases() {
+        let a: Test = vec![5..8, 0..2, 1..3, 3..4, 10..10, 7..9]
+            .into_iter()
+            .collect();
+        assert_eq!(finite(&a).unwrap(), vec![0..4, 5..9]);
+        let b: Test = vec![2..6, 20..30].into_iter().collect();
+        assert_eq!(finite(&(&a | &b)).unwrap(), vec![0..9, 20..30]);
+        assert_eq!(finite(&(&a & &b)).unwrap(), vec![2..4, 5..6]);
+        assert_eq!(finite(&(&a - &b)).unwrap(), vec![0..2, 6..9]);
+        assert!(Test::from_iter(vec![3..3, Range { start: 5, end: 1 }]).is_empty());
+        assert_eq!(finite(&!a), None);
+    }
+
     bitop_assign_consistent!(Test);
     bitop_symmetry!(Test);
     bitop_empty!(Test);
@@ -0,0 +1,194 @@
+//! SIMD accelerated intersection of sorted slices of primitive integers
+//!
+//! This compares a block of elements of a with every rotation of a block of elements of b, so each step
+//! finds all common elements of the two blocks with a few vector instructions, and then advances the block
+//! with the smaller last element. Once one of the inputs has less than a full block left, the rest is done by
+//! the scalar merge.
+//!
+//! The vector path uses AVX2, and is only taken if the cpu supports it, which is checked at runtime. On other
+//! architectures, or on cpus without AVX2, the intersection is done entirely by the scalar merge.
+use crate::merge_state::VecMergeState;
+use crate::vec_set::SetIntersectionOp;
+use crate::VecSet;
+use std::vec::Vec;
+
+/// A primitive integer for which there is a vectorized block intersection
+pub(crate) trait SimdElement: Copy + Ord {
+    /// Intersects full blocks of a and b, returning the number of elements of a and b that were consumed
+    ///
+    /// The elements not consumed have to be intersected by the scalar merge.
+    fn intersect_blocks(a: &[Self], b: &[Self], r: &mut Vec<Self>) -> (usize, usize);
+}
+
+/// The intersection of two strictly sorted slices
+pub(crate) fn intersection<T: SimdElement>(a: &[T], b: &[T]) -> Vec<T> {
+    let mut r = Vec::with_capacity(a.len().min(b.len()));
+    let (i, j) = T::intersect_blocks(a, b, &mut r);
+    VecMergeState::merge_shortcut_append(&a[i..], &b[j..], SetIntersectionOp, &mut r);
+    r
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use std::arch::x86_64::*;
+    use std::vec::Vec;
+
+    /// Runs the block loop for blocks of `N` elements, pushing the elements of a found by `matches`
+    ///
+    /// `matches` returns a bit mask of the elements of the block of a that are contained in the block of b.
+    /// Both blocks are strictly sorted, so the block with the smaller last element can not contain any more
+    /// common elements, and the block with the larger last element can still match the next block of the other.
+    #[inline(always)]
+    fn blocks<T: Copy + Ord, const N: usize>(
+        a: &[T],
+        b: &[T],
+        r: &mut Vec<T>,
+        matches: impl Fn(&[T], &[T]) -> u32,
+    ) -> (usize, usize) {
+        let (mut i, mut j) = (0, 0);
+        while i + N <= a.len() && j + N <= b.len() {
+            let (ba, bb) = (&a[i..i + N], &b[j..j + N]);
+            let mut mask = matches(ba, bb);
+            while mask != 0 {
+                r.push(ba[mask.trailing_zeros() as usize]);
+                mask &= mask - 1;
+            }
+            let (amax, bmax) = (ba[N - 1], bb[N - 1]);
+            if amax <= bmax {
+                i += N;
+            }
+            if bmax <= amax {
+                j += N;
+            }
+        }
+        (i, j)
+    }
+
+    /// Safety: the cpu must support AVX2, and T must be a 32 bit integer
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn blocks32<T: Copy + Ord>(a: &[T], b: &[T], r: &mut Vec<T>) -> (usize, usize) {
+        debug_assert_eq!(std::mem::size_of::<T>(), 4);
+        blocks::<T, 8>(a, b, r, |ba, bb| {
+            let va = _mm256_loadu_si256(ba.as_ptr() as *const __m256i);
+            let mut vb = _mm256_loadu_si256(bb.as_ptr() as *const __m256i);
+            let rotate = _mm256_setr_epi32(1, 2, 3, 4, 5, 6, 7, 0);
+            let mut eq = _mm256_cmpeq_epi32(va, vb);
+            for _ in 1..8 {
+                vb = _mm256_permutevar8x32_epi32(vb, rotate);
+                eq = _mm256_or_si256(eq, _mm256_cmpeq_epi32(va, vb));
+            }
+            _mm256_movemask_ps(_mm256_castsi256_ps(eq)) as u32
+        })
+    }
+
+    /// Safety: the cpu must support AVX2, and T must be a 64 bit integer
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn blocks64<T: Copy + Ord>(a: &[T], b: &[T], r: &mut Vec<T>) -> (usize, usize) {
+        debug_assert_eq!(std::mem::size_of::<T>(), 8);
+        blocks::<T, 4>(a, b, r, |ba, bb| {
+            let va = _mm256_loadu_si256(ba.as_ptr() as *const __m256i);
+            let mut vb = _mm256_loadu_si256(bb.as_ptr() as *const __m256i);
+            let mut eq = _mm256_cmpeq_epi64(va, vb);
+            for _ in 1..4 {
+                vb = _mm256_permute4x64_epi64(vb, 0b00_11_10_01);
+                eq = _mm256_or_si256(eq, _mm256_cmpeq_epi64(va, vb));
+            }
+            _mm256_movemask_pd(_mm256_castsi256_pd(eq)) as u32
+        })
+    }
+}
+
+macro_rules! simd_element {
+    ($t:ty, $blocks:ident) => {
+        impl SimdElement for $t {
+            fn intersect_blocks(a: &[Self], b: &[Self], r: &mut Vec<Self>) -> (usize, usize) {
+                #[cfg(target_arch = "x86_64")]
+                {
+                    if std::is_x86_feature_detected!("avx2") {
+                        // safe since we just checked that the cpu supports avx2
+                        return unsafe { avx2::$blocks(a, b, r) };
+                    }
+                }
+                let _ = (a, b, r);
+                (0, 0)
+            }
+        }
+
+        impl VecSet<$t> {
+            /// The elements that are in both self and that, using SIMD block comparisons if the cpu supports them
+            ///
+            /// The result is the same as for [VecSet::intersection].
+            pub fn intersection_simd(&self, that: &VecSet<$t>) -> Self {
+                VecSet::from_sorted_unchecked(intersection(self.as_slice(), that.as_slice()))
+            }
+        }
+    };
+}
+
+simd_element!(u32, blocks32);
+simd_element!(i32, blocks32);
+simd_element!(u64, blocks64);
+simd_element!(i64, blocks64);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+    use std::collections::BTreeSet;
+
+    fn check<T: SimdElement + std::fmt::Debug>(a: BTreeSet<T>, b: BTreeSet<T>) -> bool {
+        let a: Vec<T> = a.into_iter().collect();
+        let b: Vec<T> = b.into_iter().collect();
+        let expected: Vec<T> = VecMergeState::merge_shortcut(&a, &b, SetIntersectionOp);
+        intersection(&a, &b) == expected && intersection(&b, &a) == expected
+    }
+
+    #[quickcheck]
+    fn intersection_u32(a: BTreeSet<u32>, b: BTreeSet<u32>) -> bool {
+        check(a, b)
+    }
+
+    #[quickcheck]
+    fn intersection_i32(a: BTreeSet<i32>, b: BTreeSet<i32>) -> bool {
+        check(a, b)
+    }
+
+    #[quickcheck]
+    fn intersection_u64(a: BTreeSet<u64>, b: BTreeSet<u64>) -> bool {
+        check(a, b)
+    }
+
+    /// small values, so there is a lot of overlap, and enough elements for many full blocks
+    #[quickcheck]
+    fn intersection_dense(a: Vec<u8>, b: Vec<u8>, offset: u8) -> bool {
+        let a: BTreeSet<i64> = a.into_iter().map(|x| x as i64 - 128).collect();
+        let b: BTreeSet<i64> = b
+            .into_iter()
+            .map(|x| (x / 2) as i64 + (offset % 64) as i64 - 128)
+            .collect();
+        check(a.clone(), b.clone())
+            && check(
+                a.iter().map(|x| *x as i32).collect(),
+                b.iter().map(|x| *x as i32).collect(),
+            )
+    }
+
+    #[test]
+    fn intersection_cases() {
+        let a: VecSet<u32> = (0..1000).map(|x| x * 2).collect();
+        let b: VecSet<u32> = (0..1000).map(|x| x * 3).collect();
+        assert_eq!(a.intersection_simd(&b), a.intersection(&b));
+        assert_eq!(a.intersection_simd(&a), a);
+        assert!(a.intersection_simd(&VecSet::empty()).is_empty());
+        let a: VecSet<i64> = (-500..500).collect();
+        let b: VecSet<i64> = (0..1000).collect();
+        assert_eq!(
+            a.intersection_simd(&b).as_slice(),
+            &(0..500).collect::<Vec<_>>()[..]
+        );
+        // a short tail after the full blocks
+        let a: VecSet<i32> = (0..19).collect();
+        let b: VecSet<i32> = (5..23).collect();
+        assert_eq!(a.intersection_simd(&b), a.intersection(&b));
+    }
+}
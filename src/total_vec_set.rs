@@ -1,4 +1,4 @@
-use crate::{VecSet, VecSet2};
+use crate::VecSet2;
 use std::fmt::Debug;
 use std::fmt::Write;
 use std::ops::BitAndAssign;
@@ -282,6 +282,7 @@ mod tests {
     }
 
     /// just a helper to get good output when a check fails
+    #[allow(dead_code)]
     fn print_on_failure_unary<E: Debug, R: Eq + Debug>(x: E, expected: R, actual: R) -> bool {
         let res = expected == actual;
         if !res {
@@ -294,7 +295,7 @@ mod tests {
         let mut samples: BTreeSet<i64> = BTreeSet::new();
         samples.extend(a.elements.as_slice().iter().cloned());
         samples.extend(b.elements.as_slice().iter().cloned());
-        samples.insert(std::i64::MIN);
+        samples.insert(i64::MIN);
         samples.iter().all(|e| {
             let expected = op(a.contains(e), b.contains(e));
             let actual = r.contains(e);
@@ -312,7 +313,7 @@ mod tests {
         let mut samples: BTreeSet<i64> = BTreeSet::new();
         samples.extend(a.elements.as_slice().iter().cloned());
         samples.extend(b.elements.as_slice().iter().cloned());
-        samples.insert(std::i64::MIN);
+        samples.insert(i64::MIN);
         if r {
             samples.iter().all(|e| {
                 let expected = op(a.contains(e), b.contains(e));
@@ -0,0 +1,279 @@
+use crate::binary_merge::{EarlyOut, MergeStateRead, ShortcutMergeOperation};
+use crate::iterators::SortedIter;
+use crate::merge_state::{MergeStateMut, VecMergeState};
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt::Debug;
+use core::iter::FromIterator;
+
+struct BagUnionOp;
+struct BagIntersectionOp;
+struct BagDiffOp;
+
+/// A multiset backed by a sorted `Vec` of elements and their counts
+///
+/// Every element is stored once, together with the number of times it is contained in the bag. The counts
+/// are always positive, so an element with a count of zero is not stored at all.
+#[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct VecBag<T>(Vec<(T, usize)>);
+
+impl<T: Debug> Debug for VecBag<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_map()
+            .entries(self.0.iter().map(|(k, v)| (k, v)))
+            .finish()
+    }
+}
+
+impl<T> VecBag<T> {
+    pub fn empty() -> Self {
+        Self(Vec::new())
+    }
+    /// The number of distinct elements
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    /// The number of elements, counting each element as often as it is contained
+    pub fn total(&self) -> usize {
+        self.0.iter().map(|(_, n)| n).sum()
+    }
+    /// The distinct elements and their counts, sorted by element
+    pub fn as_slice(&self) -> &[(T, usize)] {
+        &self.0
+    }
+    /// An iterator that returns the distinct elements and their counts in sorted order
+    pub fn iter(&self) -> SortedIter<core::slice::Iter<'_, (T, usize)>> {
+        SortedIter::new(self.0.iter())
+    }
+}
+
+impl<T: Ord> VecBag<T> {
+    /// The number of times value is contained in the bag
+    pub fn count(&self, value: &T) -> usize {
+        match self.0.binary_search_by(|(k, _)| k.cmp(value)) {
+            Ok(index) => self.0[index].1,
+            Err(_) => 0,
+        }
+    }
+
+    /// Adds one occurrence of value
+    pub fn insert(&mut self, value: T) {
+        match self.0.binary_search_by(|(k, _)| k.cmp(&value)) {
+            Ok(index) => self.0[index].1 += 1,
+            Err(index) => self.0.insert(index, (value, 1)),
+        }
+    }
+}
+
+impl<T: Ord + Clone> VecBag<T> {
+    /// The combined bag, where the counts of elements in both bags are added
+    pub fn union(&self, that: &VecBag<T>) -> Self {
+        Self(VecMergeState::merge_shortcut(&self.0, &that.0, BagUnionOp))
+    }
+
+    /// The elements that are in both bags, with the smaller of the two counts
+    pub fn intersection(&self, that: &VecBag<T>) -> Self {
+        Self(VecMergeState::merge_shortcut(
+            &self.0,
+            &that.0,
+            BagIntersectionOp,
+        ))
+    }
+
+    /// The elements of self, with the counts in that subtracted. Elements whose count drops to zero are removed.
+    pub fn difference(&self, that: &VecBag<T>) -> Self {
+        Self(VecMergeState::merge_shortcut(&self.0, &that.0, BagDiffOp))
+    }
+}
+
+impl<T: Ord> From<BTreeMap<T, usize>> for VecBag<T> {
+    fn from(value: BTreeMap<T, usize>) -> Self {
+        Self(value.into_iter().filter(|(_, n)| *n > 0).collect())
+    }
+}
+
+impl<T: Ord> FromIterator<T> for VecBag<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut elements: Vec<T> = iter.into_iter().collect();
+        elements.sort();
+        let mut res: Vec<(T, usize)> = Vec::new();
+        for x in elements {
+            match res.last_mut() {
+                Some((k, n)) if *k == x => *n += 1,
+                _ => res.push((x, 1)),
+            }
+        }
+        Self(res)
+    }
+}
+
+impl<T> From<VecBag<T>> for Vec<(T, usize)> {
+    fn from(value: VecBag<T>) -> Self {
+        value.0
+    }
+}
+
+type BagMergeState<'a, T> = VecMergeState<'a, (T, usize), (T, usize), (T, usize)>;
+
+impl<'a, T: Ord + Clone> ShortcutMergeOperation<(T, usize), (T, usize), BagMergeState<'a, T>>
+    for BagUnionOp
+{
+    fn cmp(&self, a: &(T, usize), b: &(T, usize)) -> Ordering {
+        a.0.cmp(&b.0)
+    }
+    fn from_a(&self, m: &mut BagMergeState<'a, T>, n: usize) -> EarlyOut {
+        m.advance_a(n, true)
+    }
+    fn from_b(&self, m: &mut BagMergeState<'a, T>, n: usize) -> EarlyOut {
+        m.advance_b(n, true)
+    }
+    fn collision(&self, m: &mut BagMergeState<'a, T>) -> EarlyOut {
+        let (k, a) = m.a_at(0);
+        let (_, b) = m.b_at(0);
+        m.r.push((k.clone(), a + b));
+        m.advance_a(1, false)?;
+        m.advance_b(1, false)
+    }
+}
+
+impl<'a, T: Ord + Clone> ShortcutMergeOperation<(T, usize), (T, usize), BagMergeState<'a, T>>
+    for BagIntersectionOp
+{
+    fn cmp(&self, a: &(T, usize), b: &(T, usize)) -> Ordering {
+        a.0.cmp(&b.0)
+    }
+    fn from_a(&self, m: &mut BagMergeState<'a, T>, n: usize) -> EarlyOut {
+        m.advance_a(n, false)
+    }
+    fn from_b(&self, m: &mut BagMergeState<'a, T>, n: usize) -> EarlyOut {
+        m.advance_b(n, false)
+    }
+    fn collision(&self, m: &mut BagMergeState<'a, T>) -> EarlyOut {
+        let (k, a) = m.a_at(0);
+        let (_, b) = m.b_at(0);
+        m.r.push((k.clone(), *a.min(b)));
+        m.advance_a(1, false)?;
+        m.advance_b(1, false)
+    }
+}
+
+impl<'a, T: Ord + Clone> ShortcutMergeOperation<(T, usize), (T, usize), BagMergeState<'a, T>>
+    for BagDiffOp
+{
+    fn cmp(&self, a: &(T, usize), b: &(T, usize)) -> Ordering {
+        a.0.cmp(&b.0)
+    }
+    fn from_a(&self, m: &mut BagMergeState<'a, T>, n: usize) -> EarlyOut {
+        m.advance_a(n, true)
+    }
+    fn from_b(&self, m: &mut BagMergeState<'a, T>, n: usize) -> EarlyOut {
+        m.advance_b(n, false)
+    }
+    fn collision(&self, m: &mut BagMergeState<'a, T>) -> EarlyOut {
+        let (k, a) = m.a_at(0);
+        let (_, b) = m.b_at(0);
+        if a > b {
+            m.r.push((k.clone(), a - b));
+        }
+        m.advance_a(1, false)?;
+        m.advance_b(1, false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::*;
+    use std::collections::BTreeMap;
+
+    type Test = VecBag<i64>;
+    type Reference = BTreeMap<i64, usize>;
+
+    fn reference(elements: &[i64]) -> Reference {
+        let mut res = Reference::new();
+        for x in elements {
+            *res.entry(*x).or_default() += 1;
+        }
+        res
+    }
+
+    fn union_reference(a: &Reference, b: &Reference) -> Reference {
+        let mut res = a.clone();
+        for (k, n) in b {
+            *res.entry(*k).or_default() += n;
+        }
+        res
+    }
+
+    fn intersection_reference(a: &Reference, b: &Reference) -> Reference {
+        a.iter()
+            .filter_map(|(k, n)| b.get(k).map(|m| (*k, *n.min(m))))
+            .collect()
+    }
+
+    fn difference_reference(a: &Reference, b: &Reference) -> Reference {
+        a.iter()
+            .map(|(k, n)| (*k, n.saturating_sub(b.get(k).cloned().unwrap_or_default())))
+            .filter(|(_, n)| *n > 0)
+            .collect()
+    }
+
+    quickcheck! {
+        fn from_iter(a: Vec<i64>) -> bool {
+            let bag: Test = a.iter().cloned().collect();
+            let expected = reference(&a);
+            bag == expected.clone().into() && bag.total() == a.len()
+                && a.iter().all(|x| bag.count(x) == expected[x])
+        }
+
+        fn insert(a: Vec<i64>) -> bool {
+            let mut bag = Test::empty();
+            for x in a.iter() {
+                bag.insert(*x);
+            }
+            bag == a.iter().cloned().collect()
+        }
+
+        fn union(a: Vec<i64>, b: Vec<i64>) -> bool {
+            let (a, b) = (reference(&a), reference(&b));
+            let expected: Test = union_reference(&a, &b).into();
+            let actual = Test::from(a).union(&b.into());
+            expected == actual
+        }
+
+        fn intersection(a: Vec<i64>, b: Vec<i64>) -> bool {
+            let (a, b) = (reference(&a), reference(&b));
+            let expected: Test = intersection_reference(&a, &b).into();
+            let actual = Test::from(a).intersection(&b.into());
+            expected == actual
+        }
+
+        fn difference(a: Vec<i64>, b: Vec<i64>) -> bool {
+            let (a, b) = (reference(&a), reference(&b));
+            let expected: Test = difference_reference(&a, &b).into();
+            let actual = Test::from(a).difference(&b.into());
+            expected == actual
+        }
+    }
+
+    #[test]
+    fn bag_ops() {
+        let a: Test = vec![1, 1, 1, 2, 3, 3].into_iter().collect();
+        let b: Test = vec![1, 3, 3, 3, 4].into_iter().collect();
+        assert_eq!(a.as_slice(), &[(1, 3), (2, 1), (3, 2)]);
+        assert_eq!(a.len(), 3);
+        assert_eq!(a.total(), 6);
+        assert_eq!(a.count(&1), 3);
+        assert_eq!(a.count(&4), 0);
+        assert_eq!(a.union(&b).as_slice(), &[(1, 4), (2, 1), (3, 5), (4, 1)]);
+        assert_eq!(a.intersection(&b).as_slice(), &[(1, 1), (3, 2)]);
+        // the count of 3 drops to zero, so it is removed
+        assert_eq!(a.difference(&b).as_slice(), &[(1, 2), (2, 1)]);
+        assert!(b.difference(&b).is_empty());
+        assert_eq!(format!("{:?}", a), "{1: 3, 2: 1, 3: 2}");
+    }
+}
@@ -1,8 +1,12 @@
-use crate::binary_merge::MergeOperation;
+use crate::binary_merge::{EarlyOut, MergeOperation, MergeStateRead, ShortcutMergeOperation};
 use crate::dedup::{sort_and_dedup_by_key, Keep};
 use crate::iterators::SliceIterator;
-use crate::merge_state::{MergeStateMut, UnsafeInPlaceMergeState, VecMergeState};
+use crate::merge_state::{
+    FoldMergeState, InPlaceFilterMergeState, MergeStateMut, UnsafeInPlaceMergeState, VecMergeState,
+};
+use crate::{CollisionPolicy, VecSet};
 use std::borrow::Borrow;
+use std::cell::RefCell;
 use std::cmp::Ordering;
 use std::collections::BTreeMap;
 use std::fmt::Debug;
@@ -47,9 +51,76 @@ impl<K: Ord, V, F: Fn(V, V) -> V>
     }
 }
 
+struct FoldOp<F, K>(F, std::marker::PhantomData<K>);
+
+impl<K: Ord, V, F: Fn(&mut V, V)>
+    MergeOperation<(K, V), (K, V), UnsafeInPlaceMergeState<(K, V), (K, V)>> for FoldOp<F, K>
+{
+    fn cmp(&self, a: &(K, V), b: &(K, V)) -> Ordering {
+        a.0.cmp(&b.0)
+    }
+    fn from_a(&self, m: &mut UnsafeInPlaceMergeState<(K, V), (K, V)>, n: usize) {
+        m.advance_a(n, true);
+    }
+    fn from_b(&self, m: &mut UnsafeInPlaceMergeState<(K, V), (K, V)>, n: usize) {
+        m.advance_b(n, true);
+    }
+    fn collision(&self, m: &mut UnsafeInPlaceMergeState<(K, V), (K, V)>) {
+        if let (Some((ak, mut av)), Some((_, bv))) = (m.a.pop_front(), m.b.next()) {
+            (self.0)(&mut av, bv);
+            m.a.push((ak, av));
+        }
+    }
+}
+
+struct AxpyOp(f64);
+
+type AxpyMergeState<'a, K> = InPlaceFilterMergeState<'a, (K, f64), (K, f64)>;
+
+impl<'a, K: Ord + Clone> MergeOperation<(K, f64), (K, f64), AxpyMergeState<'a, K>> for AxpyOp {
+    fn cmp(&self, a: &(K, f64), b: &(K, f64)) -> Ordering {
+        a.0.cmp(&b.0)
+    }
+    fn from_a(&self, m: &mut AxpyMergeState<'a, K>, n: usize) {
+        m.advance_a(n, true);
+    }
+    fn from_b(&self, m: &mut AxpyMergeState<'a, K>, n: usize) {
+        for (bk, bv) in m.b.take_front(n) {
+            m.a.push((bk.clone(), self.0 * bv));
+        }
+    }
+    fn collision(&self, m: &mut AxpyMergeState<'a, K>) {
+        if let (Some((ak, av)), Some((_, bv))) = (m.a.pop_front(), m.b.next()) {
+            m.a.push((ak, av + self.0 * bv));
+        }
+    }
+}
+
+struct DotOp;
+
+type DotMergeState<'a, K> =
+    FoldMergeState<SliceIterator<'a, (K, f64)>, SliceIterator<'a, (K, f64)>, f64>;
+
+impl<'a, K: Ord> ShortcutMergeOperation<(K, f64), (K, f64), DotMergeState<'a, K>> for DotOp {
+    fn cmp(&self, a: &(K, f64), b: &(K, f64)) -> Ordering {
+        a.0.cmp(&b.0)
+    }
+    fn from_a(&self, m: &mut DotMergeState<'a, K>, n: usize) -> EarlyOut {
+        m.advance_a(n, false)
+    }
+    fn from_b(&self, m: &mut DotMergeState<'a, K>, n: usize) -> EarlyOut {
+        m.advance_b(n, false)
+    }
+    fn collision(&self, m: &mut DotMergeState<'a, K>) -> EarlyOut {
+        m.r += m.a_at(0).1 * m.b_at(0).1;
+        m.advance_a(1, false)?;
+        m.advance_b(1, false)
+    }
+}
+
 struct RightBiasedUnionOp;
 
-impl<'a, K: Ord, V, I: MergeStateMut<(K, V), (K, V)>> MergeOperation<(K, V), (K, V), I>
+impl<K: Ord, V, I: MergeStateMut<(K, V), (K, V)>> MergeOperation<(K, V), (K, V), I>
     for RightBiasedUnionOp
 {
     fn cmp(&self, a: &(K, V), b: &(K, V)) -> Ordering {
@@ -67,6 +138,108 @@ impl<'a, K: Ord, V, I: MergeStateMut<(K, V), (K, V)>> MergeOperation<(K, V), (K,
     }
 }
 
+/// keeps the entry of a and/or the entry of b on key collisions, see `CollisionPolicy`
+struct PolicyUnionOp(bool, bool);
+struct PolicyIntersectionOp(bool, bool);
+
+impl<K: Ord, V, I: MergeStateMut<(K, V), (K, V)>> MergeOperation<(K, V), (K, V), I>
+    for PolicyUnionOp
+{
+    fn cmp(&self, a: &(K, V), b: &(K, V)) -> Ordering {
+        a.0.cmp(&b.0)
+    }
+    fn from_a(&self, m: &mut I, n: usize) {
+        m.advance_a(n, true);
+    }
+    fn from_b(&self, m: &mut I, n: usize) {
+        m.advance_b(n, true);
+    }
+    fn collision(&self, m: &mut I) {
+        m.advance_a(1, self.0);
+        m.advance_b(1, self.1);
+    }
+}
+
+impl<K: Ord, V, I: MergeStateMut<(K, V), (K, V)>> MergeOperation<(K, V), (K, V), I>
+    for PolicyIntersectionOp
+{
+    fn cmp(&self, a: &(K, V), b: &(K, V)) -> Ordering {
+        a.0.cmp(&b.0)
+    }
+    fn from_a(&self, m: &mut I, n: usize) {
+        m.advance_a(n, false);
+    }
+    fn from_b(&self, m: &mut I, n: usize) {
+        m.advance_b(n, false);
+    }
+    fn collision(&self, m: &mut I) {
+        m.advance_a(1, self.0);
+        m.advance_b(1, self.1);
+    }
+}
+
+/// Like `RightBiasedUnionOp`, but records the key and both values of every collision
+struct ReportingUnionOp<'a, K, V>(&'a RefCell<Vec<(K, V, V)>>);
+
+impl<'a, K: Ord + Clone, V: Clone>
+    MergeOperation<(K, V), (K, V), UnsafeInPlaceMergeState<(K, V), (K, V)>>
+    for ReportingUnionOp<'a, K, V>
+{
+    fn cmp(&self, a: &(K, V), b: &(K, V)) -> Ordering {
+        a.0.cmp(&b.0)
+    }
+    fn from_a(&self, m: &mut UnsafeInPlaceMergeState<(K, V), (K, V)>, n: usize) {
+        m.advance_a(n, true);
+    }
+    fn from_b(&self, m: &mut UnsafeInPlaceMergeState<(K, V), (K, V)>, n: usize) {
+        m.advance_b(n, true);
+    }
+    fn collision(&self, m: &mut UnsafeInPlaceMergeState<(K, V), (K, V)>) {
+        if let (Some((ak, av)), Some((_, bv))) = (m.a.pop_front(), m.b.next()) {
+            self.0.borrow_mut().push((ak.clone(), av, bv.clone()));
+            m.a.push((ak, bv));
+        }
+    }
+}
+
+/// keeps the entries of a whose keys are in b
+struct RetainKeysOp;
+
+impl<K: Ord, V, I: MergeStateMut<(K, V), K>> MergeOperation<(K, V), K, I> for RetainKeysOp {
+    fn cmp(&self, a: &(K, V), b: &K) -> Ordering {
+        a.0.cmp(b)
+    }
+    fn from_a(&self, m: &mut I, n: usize) {
+        m.advance_a(n, false);
+    }
+    fn from_b(&self, m: &mut I, n: usize) {
+        m.advance_b(n, false);
+    }
+    fn collision(&self, m: &mut I) {
+        m.advance_a(1, true);
+        m.advance_b(1, false);
+    }
+}
+
+/// keeps the entries of a whose keys are not in b
+struct RemoveKeysOp;
+
+impl<K: Ord, V, I: MergeStateMut<(K, V), K>> MergeOperation<(K, V), K, I> for RemoveKeysOp {
+    fn cmp(&self, a: &(K, V), b: &K) -> Ordering {
+        a.0.cmp(b)
+    }
+    fn from_a(&self, m: &mut I, n: usize) {
+        m.advance_a(n, true);
+    }
+    fn from_b(&self, m: &mut I, n: usize) {
+        m.advance_b(n, false);
+    }
+    fn collision(&self, m: &mut I) {
+        m.advance_a(1, false);
+        m.advance_b(1, false);
+    }
+}
+
 pub enum OuterJoinArg<A, B> {
     Left(A),
     Right(B),
@@ -232,7 +405,7 @@ impl<K, V> VecMap<K, V> {
         self.0.retain(|entry| f((&entry.0, &entry.1)))
     }
 
-    pub(crate) fn slice_iter(&self) -> SliceIterator<(K, V)> {
+    pub(crate) fn slice_iter(&self) -> SliceIterator<'_, (K, V)> {
         SliceIterator(self.0.as_slice())
     }
 
@@ -255,14 +428,38 @@ impl<K, V> VecMap<K, V> {
 }
 
 impl<K: Ord, V> VecMap<K, V> {
+    /// Merge the entries of rhs into self. On key collision, the value from rhs wins.
     pub fn merge_with(&mut self, rhs: VecMap<K, V>) {
         UnsafeInPlaceMergeState::merge(&mut self.0, rhs.0, RightBiasedUnionOp)
     }
 
+    /// Merge the entries of that into self. On key collision, the two values are combined using f.
     pub fn combine_with<F: Fn(V, V) -> V>(&mut self, that: VecMap<K, V>, f: F) {
         UnsafeInPlaceMergeState::merge(&mut self.0, that.0, CombineOp(f, std::marker::PhantomData));
     }
 
+    /// Merge the entries of that into self. On key collision, the value from that is folded into the value
+    /// of self in place using fold.
+    ///
+    /// Unlike [VecMap::combine_with], this does not have to produce a new value, which is convenient for
+    /// accumulators such as `Vec<T>`.
+    pub fn merge_grouped<F: Fn(&mut V, V)>(&mut self, that: VecMap<K, V>, fold: F) {
+        UnsafeInPlaceMergeState::merge(&mut self.0, that.0, FoldOp(fold, std::marker::PhantomData));
+    }
+
+    /// Retain only the entries whose keys are contained in the given set, in place
+    pub fn retain_keys_in(&mut self, keys: &VecSet<K>) {
+        InPlaceFilterMergeState::merge(&mut self.0, keys.as_slice(), RetainKeysOp)
+    }
+
+    /// Remove all entries whose keys are contained in the given set, in place
+    ///
+    /// This is a single `O(n + m)` merge, whereas removing the keys one by one shifts the remaining
+    /// entries for every key.
+    pub fn remove_all(&mut self, keys: &VecSet<K>) {
+        InPlaceFilterMergeState::merge(&mut self.0, keys.as_slice(), RemoveKeysOp)
+    }
+
     pub fn get<Q>(&self, key: &Q) -> Option<&V>
     where
         K: Borrow<Q>,
@@ -286,9 +483,189 @@ impl<K: Ord, V> VecMap<K, V> {
             Err(_) => None,
         }
     }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.0.binary_search_by(|p| p.0.borrow().cmp(key)).is_ok()
+    }
+
+    /// The entry for the given key, for in-place manipulation
+    ///
+    /// Inserting into a vacant entry shifts all entries with larger keys, so this is `O(n)` like insertion into
+    /// a sorted vec, but lookup and modification of an occupied entry are `O(log n)`.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        match self.0.binary_search_by(|p| p.0.cmp(&key)) {
+            Ok(index) => Entry::Occupied(OccupiedEntry {
+                elements: &mut self.0,
+                index,
+            }),
+            Err(index) => Entry::Vacant(VacantEntry {
+                elements: &mut self.0,
+                index,
+                key,
+            }),
+        }
+    }
+}
+
+/// A view into a single entry of a [VecMap], returned by [VecMap::entry]
+pub enum Entry<'a, K, V> {
+    Occupied(OccupiedEntry<'a, K, V>),
+    Vacant(VacantEntry<'a, K, V>),
+}
+
+/// An entry of a [VecMap] whose key is present
+pub struct OccupiedEntry<'a, K, V> {
+    elements: &'a mut Vec<(K, V)>,
+    index: usize,
+}
+
+/// An entry of a [VecMap] whose key is not present, with the position where it would be inserted
+pub struct VacantEntry<'a, K, V> {
+    elements: &'a mut Vec<(K, V)>,
+    index: usize,
+    key: K,
+}
+
+impl<'a, K, V> Entry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(e) => e.key(),
+            Entry::Vacant(e) => e.key(),
+        }
+    }
+
+    /// The value of the entry, after inserting default if it was vacant
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(default),
+        }
+    }
+
+    /// The value of the entry, after inserting the result of f if it was vacant
+    pub fn or_insert_with<F: FnOnce() -> V>(self, f: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(f()),
+        }
+    }
+
+    /// Calls f with the value if the entry is occupied
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(mut e) => {
+                f(e.get_mut());
+                Entry::Occupied(e)
+            }
+            Entry::Vacant(e) => Entry::Vacant(e),
+        }
+    }
+}
+
+impl<'a, K, V: Default> Entry<'a, K, V> {
+    /// The value of the entry, after inserting the default value if it was vacant
+    pub fn or_default(self) -> &'a mut V {
+        self.or_insert_with(Default::default)
+    }
+}
+
+impl<'a, K, V> OccupiedEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        &self.elements[self.index].0
+    }
+    pub fn get(&self) -> &V {
+        &self.elements[self.index].1
+    }
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.elements[self.index].1
+    }
+    /// The value, with the lifetime of the map
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.elements[self.index].1
+    }
+    /// Replaces the value, returning the old one
+    pub fn insert(&mut self, value: V) -> V {
+        std::mem::replace(self.get_mut(), value)
+    }
+    /// Removes the entry from the map, returning its value
+    pub fn remove(self) -> V {
+        self.elements.remove(self.index).1
+    }
+}
+
+impl<'a, K, V> VacantEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+    pub fn into_key(self) -> K {
+        self.key
+    }
+    /// Inserts the value at the position that keeps the map sorted
+    pub fn insert(self, value: V) -> &'a mut V {
+        self.elements.insert(self.index, (self.key, value));
+        &mut self.elements[self.index].1
+    }
+}
+
+impl<K: Ord + Clone> VecMap<K, f64> {
+    /// Computes `self += alpha * that`, treating the map as a sparse vector where missing keys are zero
+    ///
+    /// The keys of the result are the union of the keys of both maps. Entries that become zero, or that
+    /// were zero before, are kept as explicit zeros and not removed. Use [VecMap::retain] to drop them.
+    /// `that` is only read, so only the keys of `that` that are not in self are cloned.
+    pub fn axpy(&mut self, alpha: f64, that: &VecMap<K, f64>) {
+        InPlaceFilterMergeState::merge(&mut self.0, &that.0, AxpyOp(alpha));
+    }
+
+    /// The inner product of self and that, treating both maps as sparse vectors
+    ///
+    /// Only the keys contained in both maps contribute. This does not allocate.
+    pub fn dot(&self, that: &VecMap<K, f64>) -> f64 {
+        FoldMergeState::merge(&self.0, &that.0, 0.0, DotOp)
+    }
 }
 
 impl<K: Ord + Clone, V: Clone> VecMap<K, V> {
+    /// The entries whose keys are contained in the given set, as a new map
+    ///
+    /// This merges the entries directly with the keys, comparing `(K, V)` with `K`.
+    pub fn filter_keys(&self, keys: &VecSet<K>) -> Self {
+        Self(VecMergeState::merge(&self.0, keys.as_slice(), RetainKeysOp))
+    }
+
+    /// Merge the entries of that into self like [VecMap::merge_with], and report the collisions
+    ///
+    /// On key collision, the value from that wins. The key and both values, first the one from self and then
+    /// the one from that, are returned for every collision in key order, so conflicts can be audited.
+    pub fn merge_reporting_conflicts(self, that: VecMap<K, V>) -> (Self, Vec<(K, V, V)>) {
+        let mut res = self.0;
+        let conflicts = RefCell::new(Vec::new());
+        UnsafeInPlaceMergeState::merge(&mut res, that.0, ReportingUnionOp(&conflicts));
+        (Self(res), conflicts.into_inner())
+    }
+
+    /// The union of self and that, with the given policy for which entry survives a key collision
+    ///
+    /// [VecMap::merge_with] always keeps the value of rhs, this lets the caller choose, like
+    /// [VecSet::union_with_policy]. A map can not contain a key twice, so there is no policy to keep both
+    /// entries, use [VecMap::combine_with] to combine both values instead.
+    pub fn union_with_policy(&self, that: &VecMap<K, V>, policy: CollisionPolicy) -> Self {
+        let (take_a, take_b) = policy.takes();
+        let op = PolicyUnionOp(take_a, take_b);
+        Self(VecMergeState::merge(&self.0, &that.0, op))
+    }
+
+    /// The entries of self and that whose keys are in both, with the given policy for which entry is kept
+    pub fn intersection_with_policy(&self, that: &VecMap<K, V>, policy: CollisionPolicy) -> Self {
+        let (take_a, take_b) = policy.takes();
+        let op = PolicyIntersectionOp(take_a, take_b);
+        Self(VecMergeState::merge(&self.0, &that.0, op))
+    }
+
     pub fn single(k: K, v: V) -> Self {
         Self::from_sorted_vec(vec![(k, v)])
     }
@@ -359,7 +736,7 @@ mod tests {
     use super::*;
     use maplit::btreemap;
     use quickcheck::*;
-    use std::collections::BTreeMap;
+    use std::collections::{BTreeMap, BTreeSet};
     use OuterJoinArg::*;
 
     type Test = VecMap<i32, i32>;
@@ -380,6 +757,18 @@ mod tests {
         r
     }
 
+    fn combine_reference(a: &Ref, b: &Ref, f: impl Fn(i32, i32) -> i32) -> Ref {
+        let mut r = a.clone();
+        for (k, v) in b.clone().into_iter() {
+            let v = match r.get(&k) {
+                Some(w) => f(*w, v),
+                None => v,
+            };
+            r.insert(k, v);
+        }
+        r
+    }
+
     fn inner_join_reference(a: &Ref, b: &Ref) -> Ref {
         let mut r: Ref = BTreeMap::new();
         for (k, v) in a.clone().into_iter() {
@@ -390,28 +779,300 @@ mod tests {
         r
     }
 
+    /// the dense vector for a sparse map with u8 keys
+    fn dense(a: &VecMap<u8, f64>) -> Vec<f64> {
+        let mut r = vec![0.0; 256];
+        for (k, v) in a.as_slice() {
+            r[*k as usize] = *v;
+        }
+        r
+    }
+
     quickcheck! {
+        fn axpy_dense(a: BTreeMap<u8, i8>, b: BTreeMap<u8, i8>, alpha: i8) -> bool {
+            // small integer values, so the results are exact
+            let a: VecMap<u8, f64> = a.into_iter().map(|(k, v)| (k, v as f64)).collect();
+            let b: VecMap<u8, f64> = b.into_iter().map(|(k, v)| (k, v as f64)).collect();
+            let alpha = alpha as f64;
+            let expected: Vec<f64> = dense(&a)
+                .iter()
+                .zip(dense(&b))
+                .map(|(a, b)| a + alpha * b)
+                .collect();
+            let mut actual = a.clone();
+            actual.axpy(alpha, &b);
+            let keys_expected: BTreeSet<u8> =
+                a.as_slice().iter().chain(b.as_slice()).map(|(k, _)| *k).collect();
+            let keys_actual: BTreeSet<u8> = actual.as_slice().iter().map(|(k, _)| *k).collect();
+            dense(&actual) == expected && keys_actual == keys_expected
+        }
+
+        fn merge_reporting_conflicts(a: Ref, b: Ref) -> bool {
+            let expected: Test = outer_join_reference(&a, &b).into();
+            let expected_conflicts: Vec<(i32, i32, i32)> = a
+                .iter()
+                .filter_map(|(k, v)| b.get(k).map(|w| (*k, *v, *w)))
+                .collect();
+            let (actual, conflicts) = Test::from(a).merge_reporting_conflicts(b.into());
+            actual == expected && conflicts == expected_conflicts
+        }
+
+        fn dot_dense(a: BTreeMap<u8, i8>, b: BTreeMap<u8, i8>) -> bool {
+            let a: VecMap<u8, f64> = a.into_iter().map(|(k, v)| (k, v as f64)).collect();
+            let b: VecMap<u8, f64> = b.into_iter().map(|(k, v)| (k, v as f64)).collect();
+            let expected: f64 = dense(&a).iter().zip(dense(&b)).map(|(a, b)| a * b).sum();
+            a.dot(&b) == expected && b.dot(&a) == expected
+        }
+
+        fn entry_counts(keys: Vec<i32>) -> bool {
+            let mut actual = Test::default();
+            let mut expected = Ref::new();
+            for k in keys {
+                actual.entry(k).and_modify(|v| *v += 1).or_insert(1);
+                expected.entry(k).and_modify(|v| *v += 1).or_insert(1);
+            }
+            actual == expected.into()
+        }
+
         fn outer_join(a: Ref, b: Ref) -> bool {
             let expected: Test = outer_join_reference(&a, &b).into();
             let a: Test = a.into();
             let b: Test = b.into();
             let actual = a.outer_join(&b, |arg| match arg {
-                Left(a) => a.clone(),
-                Right(b) => b.clone(),
-                Both(_, b) => b.clone(),
+                Left(a) => *a,
+                Right(b) => *b,
+                Both(_, b) => *b,
             });
             expected == actual
         }
 
+        fn combine_left_biased(a: Ref, b: Ref) -> bool {
+            let expected: Test = combine_reference(&a, &b, |a, _| a).into();
+            let mut a: Test = a.into();
+            a.combine_with(b.into(), |a, _| a);
+            expected == a
+        }
+
+        fn combine_right_biased(a: Ref, b: Ref) -> bool {
+            let expected: Test = combine_reference(&a, &b, |_, b| b).into();
+            let mut a1: Test = a.clone().into();
+            a1.combine_with(b.clone().into(), |_, b| b);
+            let mut a2: Test = a.into();
+            a2.merge_with(b.into());
+            expected == a1 && expected == a2
+        }
+
+        fn combine_additive(a: Ref, b: Ref) -> bool {
+            let expected: Test = combine_reference(&a, &b, i32::wrapping_add).into();
+            let mut a: Test = a.into();
+            a.combine_with(b.into(), i32::wrapping_add);
+            expected == a
+        }
+
+        fn merge_grouped(a: Ref, b: Ref) -> bool {
+            let expected: Test = combine_reference(&a, &b, i32::wrapping_add).into();
+            let mut a: Test = a.into();
+            a.merge_grouped(b.into(), |a, b| *a = a.wrapping_add(b));
+            expected == a
+        }
+
+        fn retain_keys_in(a: Ref, b: BTreeSet<i32>) -> bool {
+            let expected: Test = a.clone().into_iter().filter(|(k, _)| b.contains(k)).collect();
+            let mut a: Test = a.into();
+            a.retain_keys_in(&b.into());
+            expected == a
+        }
+
+        fn remove_all(a: Ref, b: BTreeSet<i32>) -> bool {
+            let expected: Test = a.clone().into_iter().filter(|(k, _)| !b.contains(k)).collect();
+            let mut a: Test = a.into();
+            a.remove_all(&b.into());
+            expected == a
+        }
+
+        fn get(a: Ref, k: i32) -> bool {
+            let a1: Test = a.clone().into();
+            a1.get(&k) == a.get(&k) && a1.contains_key(&k) == a.contains_key(&k)
+        }
+
+        fn filter_keys(a: Ref, b: BTreeSet<i32>) -> bool {
+            let expected: Test = a.clone().into_iter().filter(|(k, _)| b.contains(k)).collect();
+            let a: Test = a.into();
+            let keys: VecSet<i32> = b.into();
+            let actual = a.filter_keys(&keys);
+            let mut in_place = a;
+            in_place.retain_keys_in(&keys);
+            expected == actual && actual == in_place
+        }
+
         fn inner_join(a: Ref, b: Ref) -> bool {
             let expected: Test = inner_join_reference(&a, &b).into();
             let a: Test = a.into();
             let b: Test = b.into();
-            let actual = a.inner_join(&b, |a,_| a.clone());
+            let actual = a.inner_join(&b, |a,_| *a);
             expected == actual
         }
     }
 
+    #[test]
+    fn borrowed_lookup() {
+        let mut a: VecMap<String, u32> = vec![("b".to_string(), 2), ("a".to_string(), 1)]
+            .into_iter()
+            .collect();
+        assert_eq!(a.get("a"), Some(&1));
+        assert_eq!(a.get("b"), Some(&2));
+        assert_eq!(a.get("c"), None);
+        assert!(a.contains_key("a"));
+        assert!(!a.contains_key("c"));
+        *a.get_mut("b").unwrap() += 10;
+        assert_eq!(a.get("b"), Some(&12));
+        assert!(a.get_mut("c").is_none());
+    }
+
+    #[test]
+    fn collision_policy_cases() {
+        let a: VecMap<i32, char> = btreemap! { 1 => 'a', 2 => 'a' }.into();
+        let b: VecMap<i32, char> = btreemap! { 2 => 'b', 3 => 'b' }.into();
+        let take_a: VecMap<i32, char> = btreemap! { 1 => 'a', 2 => 'a', 3 => 'b' }.into();
+        let take_b: VecMap<i32, char> = btreemap! { 1 => 'a', 2 => 'b', 3 => 'b' }.into();
+        assert_eq!(a.union_with_policy(&b, CollisionPolicy::TakeA), take_a);
+        assert_eq!(a.union_with_policy(&b, CollisionPolicy::TakeB), take_b);
+        let mut merged = a.clone();
+        merged.merge_with(b.clone());
+        assert_eq!(merged, take_b);
+        let both_a: VecMap<i32, char> = btreemap! { 2 => 'a' }.into();
+        let both_b: VecMap<i32, char> = btreemap! { 2 => 'b' }.into();
+        assert_eq!(
+            a.intersection_with_policy(&b, CollisionPolicy::TakeA),
+            both_a
+        );
+        assert_eq!(
+            a.intersection_with_policy(&b, CollisionPolicy::TakeB),
+            both_b
+        );
+    }
+
+    #[test]
+    fn axpy_cases() {
+        let mut a: VecMap<i32, f64> = btreemap! { 1 => 1.0, 2 => 2.0, 4 => 0.0 }.into();
+        let b: VecMap<i32, f64> = btreemap! { 2 => 1.0, 3 => 3.0 }.into();
+        a.axpy(-2.0, &b);
+        // 2 cancels out, and the existing zero at 4 is kept
+        let expected: VecMap<i32, f64> =
+            btreemap! { 1 => 1.0, 2 => 0.0, 3 => -6.0, 4 => 0.0 }.into();
+        assert_eq!(a, expected);
+        // alpha = 0 still adds the keys of b, as explicit zeros
+        let mut c: VecMap<i32, f64> = btreemap! { 1 => 1.0 }.into();
+        c.axpy(0.0, &b);
+        let expected: VecMap<i32, f64> = btreemap! { 1 => 1.0, 2 => 0.0, 3 => 0.0 }.into();
+        assert_eq!(c, expected);
+        c.retain(|(_, v)| *v != 0.0);
+        assert_eq!(c.as_slice(), &[(1, 1.0)]);
+        // into an empty map
+        let mut d = VecMap::<i32, f64>::default();
+        d.axpy(0.5, &b);
+        assert_eq!(d.as_slice(), &[(2, 0.5), (3, 1.5)]);
+    }
+
+    #[test]
+    fn merge_reporting_conflicts_cases() {
+        let a: Test = btreemap! { 1 => 10, 2 => 20, 4 => 40 }.into();
+        let b: Test = btreemap! { 2 => 21, 3 => 31, 4 => 41 }.into();
+        let (merged, conflicts) = a.clone().merge_reporting_conflicts(b.clone());
+        let expected: Test = btreemap! { 1 => 10, 2 => 21, 3 => 31, 4 => 41 }.into();
+        assert_eq!(merged, expected);
+        assert_eq!(conflicts, vec![(2, 20, 21), (4, 40, 41)]);
+        // the conflicting keys are exactly the key intersection
+        let keys: Vec<i32> = conflicts.iter().map(|(k, _, _)| *k).collect();
+        let intersection = a.inner_join(&b, |a, _| *a);
+        let expected_keys: Vec<i32> = intersection.as_slice().iter().map(|(k, _)| *k).collect();
+        assert_eq!(keys, expected_keys);
+        let (_, conflicts) = a.merge_reporting_conflicts(Test::default());
+        assert!(conflicts.is_empty());
+    }
+
+    #[test]
+    fn remove_all_cases() {
+        let mut a: Test = btreemap! { 1 => 10, 2 => 20, 3 => 30, 4 => 40 }.into();
+        // keys that are not in the map are ignored
+        a.remove_all(&vec![0, 2, 4, 5].into());
+        let expected: Test = btreemap! { 1 => 10, 3 => 30 }.into();
+        assert_eq!(a, expected);
+        a.remove_all(&VecSet::empty());
+        assert_eq!(a, expected);
+        a.remove_all(&vec![1, 3].into());
+        assert!(a.is_empty());
+    }
+
+    #[test]
+    fn dot_cases() {
+        let a: VecMap<i32, f64> = btreemap! { 1 => 1.0, 2 => 2.0, 4 => 3.0 }.into();
+        let b: VecMap<i32, f64> = btreemap! { 2 => 5.0, 3 => 7.0, 4 => -1.0 }.into();
+        assert_eq!(a.dot(&b), 7.0);
+        // self dot is the sum of squares
+        assert_eq!(a.dot(&a), 14.0);
+        // orthogonal vectors with disjoint keys
+        let c: VecMap<i32, f64> = btreemap! { 0 => 1.0, 3 => 2.0, 5 => 3.0 }.into();
+        assert_eq!(a.dot(&c), 0.0);
+        assert_eq!(a.dot(&VecMap::default()), 0.0);
+    }
+
+    #[test]
+    fn entry_cases() {
+        let mut a: Test = btreemap! { 1 => 10, 3 => 30 }.into();
+        // occupied
+        assert_eq!(*a.entry(1).or_insert(0), 10);
+        *a.entry(3).or_insert(0) += 1;
+        a.entry(1).and_modify(|v| *v += 5).or_insert(0);
+        assert_eq!(a.entry(3).key(), &3);
+        // vacant, inserted in the middle, at the start and at the end
+        assert_eq!(*a.entry(2).or_insert(20), 20);
+        a.entry(0).and_modify(|v| *v += 5).or_insert_with(|| 7);
+        *a.entry(4).or_default() += 40;
+        let expected: Test = btreemap! { 0 => 7, 1 => 15, 2 => 20, 3 => 31, 4 => 40 }.into();
+        assert_eq!(a, expected);
+        match a.entry(2) {
+            Entry::Occupied(mut e) => {
+                assert_eq!(e.insert(21), 20);
+                assert_eq!(e.remove(), 21);
+            }
+            Entry::Vacant(_) => panic!(),
+        }
+        match a.entry(2) {
+            Entry::Occupied(_) => panic!(),
+            Entry::Vacant(e) => assert_eq!(e.into_key(), 2),
+        }
+        assert!(!a.contains_key(&2));
+    }
+
+    #[test]
+    fn merge_grouped_vec() {
+        let mut a: VecMap<i32, Vec<u32>> = btreemap! { 1 => vec![1], 2 => vec![2, 3] }.into();
+        let b: VecMap<i32, Vec<u32>> = btreemap! { 2 => vec![4], 3 => vec![5] }.into();
+        let c: VecMap<i32, Vec<u32>> = btreemap! { 1 => vec![], 3 => vec![6, 7] }.into();
+        a.merge_grouped(b, |a, b| a.extend(b));
+        a.merge_grouped(c, |a, b| a.extend(b));
+        let expected: VecMap<i32, Vec<u32>> = btreemap! {
+            1 => vec![1],
+            2 => vec![2, 3, 4],
+            3 => vec![5, 6, 7],
+        }
+        .into();
+        assert_eq!(a, expected);
+        a.merge_grouped(VecMap::default(), |a, b| a.extend(b));
+        assert_eq!(a, expected);
+    }
+
+    #[test]
+    fn filter_keys_cases() {
+        let a: Test = btreemap! { 1 => 10, 2 => 20, 4 => 40, 5 => 50 }.into();
+        let keys: VecSet<i32> = vec![0, 2, 3, 5, 6].into();
+        let expected: Test = btreemap! { 2 => 20, 5 => 50 }.into();
+        assert_eq!(a.filter_keys(&keys), expected);
+        assert!(a.filter_keys(&VecSet::empty()).is_empty());
+        assert!(Test::default().filter_keys(&keys).is_empty());
+    }
+
     #[test]
     fn smoke_test() {
         let a = btreemap! {
@@ -427,9 +1088,9 @@ mod tests {
         let b: Test = b.into();
         let expected: Test = r.into();
         let actual = a.outer_join(&b, |arg| match arg {
-            Left(a) => a.clone(),
-            Right(b) => b.clone(),
-            Both(_, b) => b.clone(),
+            Left(a) => *a,
+            Right(b) => *b,
+            Both(_, b) => *b,
         });
         assert_eq!(actual, expected);
         println!("{:?}", actual);
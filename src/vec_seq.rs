@@ -23,9 +23,9 @@ impl<T> From<Vec<T>> for VecSeq<T> {
     }
 }
 
-impl<T> Into<Vec<T>> for VecSeq<T> {
-    fn into(self) -> Vec<T> {
-        self.0
+impl<T> From<VecSeq<T>> for Vec<T> {
+    fn from(value: VecSeq<T>) -> Self {
+        value.0
     }
 }
 
@@ -59,9 +59,9 @@ impl<T: Clone> AbstractMagma<Additive> for VecSeq<T> {
 
 impl<T: Clone + Eq> AbstractSemigroup<Additive> for VecSeq<T> {}
 impl<T: Clone + Eq> AbstractMonoid<Additive> for VecSeq<T> {}
-impl<'a, 'b, T: Clone + Eq> Add<&'b VecSeq<T>> for &'a VecSeq<T> {
+impl<T: Clone + Eq> Add<&VecSeq<T>> for &VecSeq<T> {
     type Output = VecSeq<T>;
-    fn add(self, rhs: &'b VecSeq<T>) -> VecSeq<T> {
+    fn add(self, rhs: &VecSeq<T>) -> VecSeq<T> {
         self.operate(rhs)
     }
 }
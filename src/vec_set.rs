@@ -1,24 +1,170 @@
-use crate::binary_merge::{EarlyOut, ShortcutMergeOperation};
+use crate::binary_merge::{
+    gallop_search_by, CmpMergeOperation, CountingMergeOperation, EarlyOut, MergeOperation3,
+    MergeStateRead, ResumableMergeStack, ShortcutMergeOperation,
+};
 use crate::dedup::sort_and_dedup;
-use crate::iterators::SortedIter;
+use crate::iterators::{kmerge, SortedIter};
+#[cfg(feature = "std")]
+use crate::merge_state::ChunkedMergeState;
 use crate::merge_state::{
-    BoolOpMergeState, InPlaceMergeState, MergeStateMut, SmallVecMergeState,
-    UnsafeInPlaceMergeState, UnsafeSliceMergeState, VecMergeState, SmallVecInPlaceMergeState,
+    BoolOpMergeState, CopyVecMergeState, CountMergeState, DedupMergeState, InPlaceMergeState,
+    IndexMergeState, MergeIndex, MergeStateMut, Provenance, ProvenanceMergeState,
+    RelationMergeState, RleMergeState, SmallVecInPlaceMergeState, SmallVecMergeState,
+    SplitMergeState, TakeMergeState, ThresholdMergeState, TieBreakMergeState,
+    UnionIntersectionMergeState, UnsafeInPlaceMergeState, VecMergeState, VecMergeState3,
+};
+use alloc::borrow::Cow;
+use alloc::collections::BTreeSet;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::cmp::Ordering;
+use core::fmt::Debug;
+use core::iter::FromIterator;
+use core::marker::PhantomData;
+use core::ops::{
+    BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Range, Sub, SubAssign,
 };
+use core::task::Poll;
+use num_traits::PrimInt;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use smallvec::{Array, SmallVec};
-use std::cmp::Ordering;
-use std::collections::BTreeSet;
-use std::fmt::Debug;
-use std::iter::FromIterator;
-use std::marker::PhantomData;
-use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Sub, SubAssign};
-
-struct SetUnionOp;
-struct SetIntersectionOp;
-struct SetXorOp;
-struct SetDiffOpt;
-
-#[derive(Debug, Hash, Clone, PartialEq, Eq, Default)]
+
+/// The union of two sets, as a [SetMergeOp]
+#[derive(Clone, Copy)]
+pub struct SetUnionOp;
+/// The intersection of two sets, as a [SetMergeOp]
+#[derive(Clone, Copy)]
+pub struct SetIntersectionOp;
+/// The symmetric difference of two sets, as a [SetMergeOp]
+#[derive(Clone, Copy)]
+pub struct SetXorOp;
+/// The difference of two sets, as a [SetMergeOp]
+#[derive(Clone, Copy)]
+pub struct SetDiffOp;
+struct SetProvenanceUnionOp;
+struct SetIndexUnionOp;
+struct SetRelationOp;
+struct SetUnionIntersectionOp;
+struct SetUnion3Op;
+/// keeps the element of a and/or the element of b on collisions
+struct SetPolicyUnionOp(bool, bool);
+struct SetPolicyIntersectionOp(bool, bool);
+struct SetObserverUnionOp<F>(RefCell<F>);
+
+/// Which element survives when an element of self and an element of that compare equal
+///
+/// This only makes a difference for element types whose `Ord` does not look at all fields, such as
+/// `(key, tag)` pairs that are compared by key only. The plain set operations always use `TakeA`.
+///
+/// To keep both elements, use [VecSet::union_keep_both] or [VecSet::intersection_keep_both]. They return
+/// a `Vec`, since a set can not contain two elements that compare equal. For maps, the policy decides which
+/// entry survives a key collision, see `VecMap::union_with_policy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CollisionPolicy {
+    /// keep the element of self
+    TakeA,
+    /// keep the element of that
+    TakeB,
+}
+
+impl CollisionPolicy {
+    /// whether to take the element of a and the element of b on a collision
+    pub(crate) fn takes(self) -> (bool, bool) {
+        match self {
+            CollisionPolicy::TakeA => (true, false),
+            CollisionPolicy::TakeB => (false, true),
+        }
+    }
+}
+
+/// How a set operation finds the positions of the elements of one set in the other
+///
+/// All strategies give the same result, they only differ in the number of comparisons they need for a
+/// given input. The plain set operations use `Binary`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MergeStrategy {
+    /// bisect the smaller set and search in the larger one, with a fast path for nearly identical sets
+    ///
+    /// This needs O(m log(n/m)) comparisons for sets of sizes m <= n, and only a few comparisons per run for
+    /// sets that do not interleave.
+    Binary,
+    /// bisect like `Binary`, but switch to a linear scan once the sizes are within a ratio of 8
+    ///
+    /// This is faster for sets of similar size whose elements interleave closely, since a linear scan has
+    /// less overhead per comparison than a binary search.
+    Adaptive,
+    /// probe exponentially from the current position of each set for the first element of the other
+    ///
+    /// This is faster for a small set whose elements are close to the start of a large set, or that
+    /// interleave with it in long runs.
+    Galloping,
+    /// compare the first remaining elements of both sets and take the smaller one
+    ///
+    /// This always needs about as many comparisons as there are elements, so it is mostly useful as the
+    /// baseline when counting comparisons, see e.g. [VecSet::union_comparisons].
+    Linear,
+    /// bisect like `Binary` without the fast path, keeping the pending work on the heap instead of the stack
+    ///
+    /// This needs more comparisons than `Binary` when the first set is much larger than the second, but its
+    /// stack usage does not depend on the sizes of the sets, e.g. for very large sets on a thread with a small
+    /// stack.
+    Iterative,
+}
+
+impl MergeStrategy {
+    fn merge<'a, T, O>(self, a: &'a [T], b: &'a [T], o: &O) -> Vec<T>
+    where
+        O: ShortcutMergeOperation<T, T, VecMergeState<'a, T, T, T>>,
+    {
+        let mut state = VecMergeState::new(a, b, Vec::new());
+        match self {
+            MergeStrategy::Binary => o.merge(&mut state),
+            MergeStrategy::Adaptive => o.merge_adaptive(&mut state),
+            MergeStrategy::Galloping => o.merge_galloping(&mut state),
+            MergeStrategy::Iterative => o.merge_iterative(&mut state),
+            MergeStrategy::Linear => {
+                let (an, bn) = (a.len(), b.len());
+                o.merge_linear0(&mut state, an, bn);
+            }
+        }
+        state.into_vec()
+    }
+
+    /// The number of comparisons that merging a and b with this strategy needs
+    fn comparisons<'a, T, O>(self, a: &'a [T], b: &'a [T], o: O) -> usize
+    where
+        O: ShortcutMergeOperation<T, T, VecMergeState<'a, T, T, T>>,
+    {
+        let o = CountingMergeOperation::new(o);
+        self.merge(a, b, &o);
+        o.comparisons()
+    }
+}
+
+/// How two sets relate to each other, see [VecSet::relation]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SetRelation {
+    /// both sets contain the same elements
+    Equal,
+    /// all elements of self are in that, but not the other way round
+    ///
+    /// This includes an empty self and a non-empty that, even though they are also disjoint.
+    Subset,
+    /// all elements of that are in self, but not the other way round
+    Superset,
+    /// the sets have common elements, and each also has elements the other does not have
+    Overlapping,
+    /// both sets are non-empty and have no common elements
+    Disjoint,
+}
+
+/// A set backed by a `SmallVec` instead of a `Vec`
+///
+/// Up to the capacity of the array type `A` elements are stored inline, so small sets do not need a heap
+/// allocation. All operations use the same merge operations as `VecSet`.
+#[derive(Debug, Hash, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
 pub struct VecSet2<T, A: Array<Item = T> = [T; 2]>(SmallVec<A>, PhantomData<T>);
 
 impl<T, A: Array<Item = T>> VecSet2<T, A> {
@@ -34,7 +180,7 @@ impl<T, A: Array<Item = T>> VecSet2<T, A> {
         Self::new(SmallVec::new())
     }
     /// An iterator that returns the items of this vec set in sorted order
-    pub fn iter(&self) -> SortedIter<std::slice::Iter<T>> {
+    pub fn iter(&self) -> SortedIter<core::slice::Iter<'_, T>> {
         SortedIter::new(self.0.iter())
     }
     pub fn as_slice(&self) -> &[T] {
@@ -60,7 +206,7 @@ impl<T: Ord, A: Array<Item = T>> VecSet2<T, A> {
     }
 
     pub fn remove(&mut self, that: &T) {
-        if let Ok(index) = self.0.binary_search(&that) {
+        if let Ok(index) = self.0.binary_search(that) {
             self.0.remove(index);
         };
     }
@@ -70,7 +216,7 @@ impl<T: Ord, A: Array<Item = T>> VecSet2<T, A> {
     }
 
     pub fn is_subset(&self, that: &Self) -> bool {
-        !BoolOpMergeState::merge(&self.0, &that.0, SetDiffOpt)
+        !BoolOpMergeState::merge(&self.0, &that.0, SetDiffOp)
     }
 
     pub fn is_superset(&self, that: &Self) -> bool {
@@ -89,9 +235,9 @@ impl<T: Ord, A: Array<Item = T>> VecSet2<T, A> {
     }
 }
 
-impl<A: Array<Item = T>, T> Into<Vec<T>> for VecSet2<T, A> {
-    fn into(self) -> Vec<T> {
-        self.0.into_vec()
+impl<A: Array<Item = T>, T> From<VecSet2<T, A>> for Vec<T> {
+    fn from(value: VecSet2<T, A>) -> Self {
+        value.0.into_vec()
     }
 }
 
@@ -128,68 +274,271 @@ impl<T: Ord + Clone, Arr: Array<Item = T>> Sub for &VecSet2<T, Arr> {
     type Output = VecSet2<T, Arr>;
     fn sub(self, that: Self) -> Self::Output {
         Self::Output::new(SmallVecMergeState::merge_shortcut(
-            &self.0, &that.0, SetDiffOpt,
+            &self.0, &that.0, SetDiffOp,
         ))
     }
 }
 
-impl<T: Ord> BitAndAssign for VecSet2<T> {
+impl<T: Ord, A: Array<Item = T>> BitAndAssign for VecSet2<T, A> {
     fn bitand_assign(&mut self, that: Self) {
         SmallVecInPlaceMergeState::merge_shortcut(&mut self.0, that.0, SetIntersectionOp);
     }
 }
 
-impl<T: Ord> BitOrAssign for VecSet2<T> {
+impl<T: Ord, A: Array<Item = T>> BitOrAssign for VecSet2<T, A> {
     fn bitor_assign(&mut self, that: Self) {
         SmallVecInPlaceMergeState::merge_shortcut(&mut self.0, that.0, SetUnionOp);
     }
 }
 
-impl<T: Ord> BitXorAssign for VecSet2<T> {
+impl<T: Ord, A: Array<Item = T>> BitXorAssign for VecSet2<T, A> {
     fn bitxor_assign(&mut self, that: Self) {
         SmallVecInPlaceMergeState::merge_shortcut(&mut self.0, that.0, SetXorOp);
     }
 }
 
-impl<T: Ord> SubAssign for VecSet2<T> {
+impl<T: Ord, A: Array<Item = T>> SubAssign for VecSet2<T, A> {
     fn sub_assign(&mut self, that: Self) {
-        SmallVecInPlaceMergeState::merge_shortcut(&mut self.0, that.0, SetDiffOpt);
+        SmallVecInPlaceMergeState::merge_shortcut(&mut self.0, that.0, SetDiffOp);
     }
 }
 
-impl<T: Ord> From<Vec<T>> for VecSet2<T> {
+impl<T: Ord, A: Array<Item = T>> From<Vec<T>> for VecSet2<T, A> {
     fn from(vec: Vec<T>) -> Self {
         Self::from_vec(vec)
     }
 }
 
-impl<T: Ord> From<BTreeSet<T>> for VecSet2<T> {
+impl<T: Ord, A: Array<Item = T>> From<BTreeSet<T>> for VecSet2<T, A> {
     fn from(value: BTreeSet<T>) -> Self {
         Self::new(value.into_iter().collect())
     }
 }
 
-impl<T: Ord> FromIterator<T> for VecSet2<T> {
+impl<T: Ord, A: Array<Item = T>> FromIterator<T> for VecSet2<T, A> {
     fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
         Self::from_vec(sort_and_dedup(iter.into_iter()))
     }
 }
 
-impl<T: Ord> Extend<T> for VecSet2<T> {
+impl<T: Ord, A: Array<Item = T>> Extend<T> for VecSet2<T, A> {
     fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
-        *self &= Self::from_iter(iter);
+        let mut batch = sort_and_dedup(iter.into_iter());
+        if batch.len() == 1 {
+            self.insert(batch.pop().unwrap());
+        } else if !batch.is_empty() {
+            *self |= Self::new(SmallVec::from_vec(batch));
+        }
     }
 }
 
-impl<'a, T: 'a + Ord + Copy> Extend<&'a T> for VecSet2<T> {
+impl<'a, T: 'a + Ord + Copy, A: Array<Item = T>> Extend<&'a T> for VecSet2<T, A> {
     fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
         self.extend(iter.into_iter().cloned())
     }
 }
 
-#[derive(Clone, Hash, PartialEq, Eq)]
+/// Writes the union of two sorted slices into `out`, reusing its allocation
+///
+/// `a` and `b` must be sorted in ascending order and must not contain duplicates, like the contents
+/// of a `VecSet`. `out` is cleared first. To avoid a reallocation, `out` must have a capacity of at least
+/// `a.len() + b.len()`, the size of the union in the worst case.
+pub fn merge_into<T: Ord + Clone>(a: &[T], b: &[T], out: &mut Vec<T>) {
+    VecMergeState::merge_shortcut_into(a, b, SetUnionOp, out)
+}
+
+/// Appends the union of two sorted slices to `out`, and returns the range of `out` it occupies
+///
+/// Unlike [merge_into], the existing contents of `out` are kept. This allows building many sets into a
+/// single flat buffer, where each set is identified by its range. `a` and `b` must be sorted in ascending
+/// order and must not contain duplicates.
+pub fn merge_append<T: Ord + Clone>(a: &[T], b: &[T], out: &mut Vec<T>) -> Range<usize> {
+    VecMergeState::merge_shortcut_append(a, b, SetUnionOp, out)
+}
+
+/// The union of two sorted slices that may contain duplicates, without duplicates
+///
+/// Unlike [merge_into], `a` and `b` only have to be sorted in ascending order, and may contain runs of equal
+/// elements. Those are collapsed, so the union of `[1, 1, 2]` and `[2, 2, 3]` is `[1, 2, 3]`.
+pub fn merge_dedup<T: Ord + Clone>(a: &[T], b: &[T]) -> Vec<T> {
+    DedupMergeState::merge_shortcut(a, b, SetUnionOp)
+}
+
+/// Like [merge_into], but in debug builds checks that the result is sorted
+///
+/// The merge relies on `a` and `b` being sorted, and silently produces garbage otherwise. In debug builds,
+/// this panics with the index of the first element of the result that is smaller than its predecessor, and
+/// the first unsorted indices of `a` and `b`. In release builds, this is exactly as fast as [merge_into].
+pub fn merge_checked<T: Ord + Clone>(a: &[T], b: &[T], out: &mut Vec<T>) {
+    merge_into(a, b, out);
+    if cfg!(debug_assertions) {
+        fn first_unsorted<T: Ord>(elements: &[T]) -> Option<usize> {
+            elements.windows(2).position(|w| w[0] > w[1]).map(|i| i + 1)
+        }
+        if let Some(index) = first_unsorted(out) {
+            panic!(
+                "merge result is not sorted at index {}, first unsorted index in a: {:?}, in b: {:?}",
+                index,
+                first_unsorted(a),
+                first_unsorted(b)
+            );
+        }
+    }
+}
+
+/// Merges two slices sorted by `cmp`, using `tie_break` to decide which element survives a collision
+///
+/// When an element of `a` and an element of `b` are equal according to `cmp`, `tie_break` is called with
+/// both. `Less` keeps the element of `a`, `Greater` keeps the element of `b`, and `Equal` keeps both, the
+/// element of `a` first. Elements within each slice should be distinct according to `cmp`.
+pub fn merge_tie_break<T: Clone>(
+    a: &[T],
+    b: &[T],
+    cmp: impl Fn(&T, &T) -> Ordering,
+    tie_break: impl Fn(&T, &T) -> Ordering,
+) -> Vec<T> {
+    TieBreakMergeState::merge(a, b, cmp, tie_break)
+}
+
+/// Merges two sorted slices like [merge_into], but returns indices into the inputs instead of the elements
+///
+/// Elements only in `a` or only in `b` are returned as `MergeIndex::A` and `MergeIndex::B`, and equal elements
+/// as a `MergeIndex::Both` pair. Nothing is copied, so this is useful for joins of large values that are
+/// resolved later.
+pub fn merge_indices<T: Ord>(a: &[T], b: &[T]) -> Vec<MergeIndex> {
+    IndexMergeState::merge(a, b, SetIndexUnionOp)
+}
+
+/// The union of two run-length-encoded sets of integers
+///
+/// Each run `(start, len)` stands for the values from `start` up to but excluding `start + len`. Both inputs
+/// must be sorted by start value, and the runs of an input must have distinct starts, but they may overlap or
+/// touch. The result is normalized: the runs are sorted and non-empty, and neither overlap nor touch. This
+/// needs comparisons only for the runs, not for the values they stand for. `start + len` must not overflow.
+pub fn union_runs<T: PrimInt>(a: &[(T, T)], b: &[(T, T)]) -> Vec<(T, T)> {
+    RleMergeState::union(a, b)
+}
+
+/// Writes the union of two files of sorted numbers, one per line, to `out`
+///
+/// The inputs are read in chunks, so they do not have to fit into memory. Both inputs must be strictly
+/// sorted. Empty lines are ignored, and a line that is not a number is reported as
+/// [std::io::ErrorKind::InvalidData].
+#[cfg(feature = "std")]
+pub fn union_sorted_lines(
+    a: impl std::io::BufRead,
+    b: impl std::io::BufRead,
+    mut out: impl std::io::Write,
+) -> std::io::Result<()> {
+    fn parse(line: std::io::Result<String>) -> Option<std::io::Result<u64>> {
+        match line {
+            Ok(line) if line.trim().is_empty() => None,
+            Ok(line) => Some(
+                line.trim()
+                    .parse()
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+            ),
+            Err(e) => Some(Err(e)),
+        }
+    }
+    let a = a.lines().filter_map(parse);
+    let b = b.lines().filter_map(parse);
+    let mut state = ChunkedMergeState::new(a, b, 1 << 16);
+    let mut window = Vec::new();
+    while state.merge_window(SetUnionOp, &mut window)? {
+        for x in &window {
+            writeln!(out, "{}", x)?;
+        }
+    }
+    out.flush()
+}
+
+/// A set backed by a sorted `Vec` without duplicates
+///
+/// Since the elements are always sorted, sets compare lexicographically by their elements, just like
+/// a `BTreeSet`. Equality and hashing only depend on the elements, not on how the set was built.
+#[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct VecSet<T>(Vec<T>);
 
+/// Error when creating a `VecSet` from a vec that is not strictly sorted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotSortedError {
+    /// index of the first element that is not greater than the element before it
+    pub index: usize,
+}
+
+impl core::fmt::Display for NotSortedError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "element at index {} is not greater than the previous element",
+            self.index
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for NotSortedError {}
+
+/// Like `binary_search`, but galloping from hint, so it is fast when value is close to the element at hint
+fn gallop_search_from<T: Ord>(elements: &[T], value: &T, hint: usize) -> Result<usize, usize> {
+    let hint = hint.min(elements.len());
+    if hint < elements.len() && elements[hint] < *value {
+        // gallop forward from hint
+        match gallop_search_by(elements.len() - hint, |i| elements[hint + i].cmp(value)) {
+            Ok(i) => Ok(hint + i),
+            Err(i) => Err(hint + i),
+        }
+    } else {
+        // gallop backward from hint, the elements up to hint in reverse are sorted in descending order
+        let end = (hint + 1).min(elements.len());
+        match gallop_search_by(end, |i| value.cmp(&elements[end - 1 - i])) {
+            Ok(i) => Ok(end - 1 - i),
+            Err(i) => Err(end - i),
+        }
+    }
+}
+
+/// A cursor for membership queries that remembers the position of the last query
+///
+/// Each query gallops from the position of the previous one, so a sequence of queries that are close to
+/// each other, such as an ascending sequence, takes `O(log d)` per query, where d is the distance to the
+/// previous position. Queries in arbitrary order are still correct.
+#[derive(Debug, Clone)]
+pub struct ContainsCursor<'a, T> {
+    elements: &'a [T],
+    position: usize,
+}
+
+impl<'a, T: Ord> ContainsCursor<'a, T> {
+    /// true if the set contains value
+    pub fn contains(&mut self, value: &T) -> bool {
+        match gallop_search_from(self.elements, value, self.position) {
+            Ok(i) => {
+                self.position = i;
+                true
+            }
+            Err(i) => {
+                self.position = i;
+                false
+            }
+        }
+    }
+
+    /// The index where the last query was found, or would be inserted
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+/// index of the first element that is not less than value
+fn lower_bound<T: Ord>(elements: &[T], value: &T) -> usize {
+    match elements.binary_search(value) {
+        Ok(i) => i,
+        Err(i) => i,
+    }
+}
+
 impl<T: Ord, I: MergeStateMut<T, T>> ShortcutMergeOperation<T, T, I> for SetUnionOp {
     fn cmp(&self, a: &T, b: &T) -> Ordering {
         a.cmp(b)
@@ -206,6 +555,127 @@ impl<T: Ord, I: MergeStateMut<T, T>> ShortcutMergeOperation<T, T, I> for SetUnio
     }
 }
 
+impl<T: Ord, F: FnMut(&T, &T), I: MergeStateMut<T, T>> ShortcutMergeOperation<T, T, I>
+    for SetObserverUnionOp<F>
+{
+    fn cmp(&self, a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+    fn from_a(&self, m: &mut I, n: usize) -> EarlyOut {
+        m.advance_a(n, true)
+    }
+    fn from_b(&self, m: &mut I, n: usize) -> EarlyOut {
+        m.advance_b(n, true)
+    }
+    fn collision(&self, m: &mut I) -> EarlyOut {
+        (self.0.borrow_mut())(m.a_at(0), m.b_at(0));
+        m.advance_a(1, true)?;
+        m.advance_b(1, false)
+    }
+}
+
+impl<'a, T: Ord + Clone> MergeOperation3<T, T, T, VecMergeState3<'a, T>> for SetUnion3Op {
+    fn from_a(&self, m: &mut VecMergeState3<'a, T>, n: usize) {
+        m.r.extend_from_slice(m.a.take_front(n));
+    }
+    fn from_b(&self, m: &mut VecMergeState3<'a, T>, n: usize) {
+        m.r.extend_from_slice(m.b.take_front(n));
+    }
+    fn from_c(&self, m: &mut VecMergeState3<'a, T>, n: usize) {
+        m.r.extend_from_slice(m.c.take_front(n));
+    }
+    fn collision_ab(&self, m: &mut VecMergeState3<'a, T>) {
+        self.from_a(m, 1);
+        m.b.drop_front(1);
+    }
+    fn collision_ac(&self, m: &mut VecMergeState3<'a, T>) {
+        self.from_a(m, 1);
+        m.c.drop_front(1);
+    }
+    fn collision_bc(&self, m: &mut VecMergeState3<'a, T>) {
+        self.from_b(m, 1);
+        m.c.drop_front(1);
+    }
+    fn collision_abc(&self, m: &mut VecMergeState3<'a, T>) {
+        self.from_a(m, 1);
+        m.b.drop_front(1);
+        m.c.drop_front(1);
+    }
+    fn cmp_ab(&self, a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+    fn cmp_ac(&self, a: &T, c: &T) -> Ordering {
+        a.cmp(c)
+    }
+    fn cmp_bc(&self, b: &T, c: &T) -> Ordering {
+        b.cmp(c)
+    }
+}
+
+impl<'a, T: Ord + Clone> ShortcutMergeOperation<T, T, UnionIntersectionMergeState<'a, T>>
+    for SetUnionIntersectionOp
+{
+    fn cmp(&self, a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+    fn from_a(&self, m: &mut UnionIntersectionMergeState<'a, T>, n: usize) -> EarlyOut {
+        m.advance_a(n, true)
+    }
+    fn from_b(&self, m: &mut UnionIntersectionMergeState<'a, T>, n: usize) -> EarlyOut {
+        m.advance_b(n, true)
+    }
+    fn collision(&self, m: &mut UnionIntersectionMergeState<'a, T>) -> EarlyOut {
+        m.collide()
+    }
+}
+
+impl<'a, T: Ord + Clone> ShortcutMergeOperation<T, T, ProvenanceMergeState<'a, T>>
+    for SetProvenanceUnionOp
+{
+    fn cmp(&self, a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+    fn from_a(&self, m: &mut ProvenanceMergeState<'a, T>, n: usize) -> EarlyOut {
+        m.advance_a(n, true)
+    }
+    fn from_b(&self, m: &mut ProvenanceMergeState<'a, T>, n: usize) -> EarlyOut {
+        m.advance_b(n, true)
+    }
+    fn collision(&self, m: &mut ProvenanceMergeState<'a, T>) -> EarlyOut {
+        m.collide()
+    }
+}
+
+impl<'a, T: Ord> ShortcutMergeOperation<T, T, IndexMergeState<'a, T>> for SetIndexUnionOp {
+    fn cmp(&self, a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+    fn from_a(&self, m: &mut IndexMergeState<'a, T>, n: usize) -> EarlyOut {
+        m.advance_a(n, true)
+    }
+    fn from_b(&self, m: &mut IndexMergeState<'a, T>, n: usize) -> EarlyOut {
+        m.advance_b(n, true)
+    }
+    fn collision(&self, m: &mut IndexMergeState<'a, T>) -> EarlyOut {
+        m.collide()
+    }
+}
+
+impl<'a, T: Ord> ShortcutMergeOperation<T, T, RelationMergeState<'a, T, T>> for SetRelationOp {
+    fn cmp(&self, a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+    fn from_a(&self, m: &mut RelationMergeState<'a, T, T>, n: usize) -> EarlyOut {
+        m.advance_a(n, true)
+    }
+    fn from_b(&self, m: &mut RelationMergeState<'a, T, T>, n: usize) -> EarlyOut {
+        m.advance_b(n, true)
+    }
+    fn collision(&self, m: &mut RelationMergeState<'a, T, T>) -> EarlyOut {
+        m.collide()
+    }
+}
+
 impl<T: Ord, I: MergeStateMut<T, T>> ShortcutMergeOperation<T, T, I> for SetIntersectionOp {
     fn cmp(&self, a: &T, b: &T) -> Ordering {
         a.cmp(b)
@@ -222,7 +692,39 @@ impl<T: Ord, I: MergeStateMut<T, T>> ShortcutMergeOperation<T, T, I> for SetInte
     }
 }
 
-impl<T: Ord, I: MergeStateMut<T, T>> ShortcutMergeOperation<T, T, I> for SetDiffOpt {
+impl<T: Ord, I: MergeStateMut<T, T>> ShortcutMergeOperation<T, T, I> for SetPolicyUnionOp {
+    fn cmp(&self, a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+    fn from_a(&self, m: &mut I, n: usize) -> EarlyOut {
+        m.advance_a(n, true)
+    }
+    fn from_b(&self, m: &mut I, n: usize) -> EarlyOut {
+        m.advance_b(n, true)
+    }
+    fn collision(&self, m: &mut I) -> EarlyOut {
+        m.advance_a(1, self.0)?;
+        m.advance_b(1, self.1)
+    }
+}
+
+impl<T: Ord, I: MergeStateMut<T, T>> ShortcutMergeOperation<T, T, I> for SetPolicyIntersectionOp {
+    fn cmp(&self, a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+    fn from_a(&self, m: &mut I, n: usize) -> EarlyOut {
+        m.advance_a(n, false)
+    }
+    fn from_b(&self, m: &mut I, n: usize) -> EarlyOut {
+        m.advance_b(n, false)
+    }
+    fn collision(&self, m: &mut I) -> EarlyOut {
+        m.advance_a(1, self.0)?;
+        m.advance_b(1, self.1)
+    }
+}
+
+impl<T: Ord, I: MergeStateMut<T, T>> ShortcutMergeOperation<T, T, I> for SetDiffOp {
     fn cmp(&self, a: &T, b: &T) -> Ordering {
         a.cmp(b)
     }
@@ -254,15 +756,198 @@ impl<T: Ord, I: MergeStateMut<T, T>> ShortcutMergeOperation<T, T, I> for SetXorO
     }
 }
 
+/// The merge state that is passed to the callbacks of a [SetMergeOp]
+///
+/// It gives access to the remaining elements of both inputs, and lets the operation take them into the
+/// result or skip them.
+pub struct SetMergeState<'a, T>(VecMergeState<'a, T, T, T>);
+
+impl<'a, T> SetMergeState<'a, T> {
+    fn new(a: &'a [T], b: &'a [T]) -> Self {
+        Self(VecMergeState::new(a, b, Vec::new()))
+    }
+    /// The remaining elements of a
+    pub fn a_slice(&self) -> &[T] {
+        self.0.a.as_slice()
+    }
+    /// The remaining elements of b
+    pub fn b_slice(&self) -> &[T] {
+        self.0.b.as_slice()
+    }
+    /// The result so far
+    pub fn result(&self) -> &[T] {
+        &self.0.r
+    }
+    /// Skips the next n elements of a
+    pub fn skip_a(&mut self, n: usize) {
+        self.0.a.drop_front(n)
+    }
+    /// Skips the next n elements of b
+    pub fn skip_b(&mut self, n: usize) {
+        self.0.b.drop_front(n)
+    }
+}
+
+impl<'a, T: Clone> SetMergeState<'a, T> {
+    /// Adds the next n elements of a to the result
+    pub fn take_a(&mut self, n: usize) {
+        self.0.r.extend_from_slice(self.0.a.take_front(n))
+    }
+    /// Adds the next n elements of b to the result
+    pub fn take_b(&mut self, n: usize) {
+        self.0.r.extend_from_slice(self.0.b.take_front(n))
+    }
+}
+
+impl<'a, T> MergeStateRead<T, T> for SetMergeState<'a, T> {
+    fn a_len(&self) -> usize {
+        self.0.a_len()
+    }
+    fn b_len(&self) -> usize {
+        self.0.b_len()
+    }
+    fn a_at(&self, i: usize) -> &T {
+        self.0.a_at(i)
+    }
+    fn b_at(&self, i: usize) -> &T {
+        self.0.b_at(i)
+    }
+}
+
+/// A set operation that can be chosen at runtime, e.g. as a `Box<dyn SetMergeOp<T>>`
+///
+/// The merge operations of this crate are generic over the merge state, so they can not be used as trait
+/// objects. This fixes the merge state to [SetMergeState], so the callbacks are object safe, and the binary
+/// merge can call them on a `dyn SetMergeOp<T>`. Implementing the three callbacks is enough to define a
+/// new operation. Each callback has to either take or skip every element it is called for, and `collision`
+/// must take at most one of the two elements, since the result is a set.
+#[allow(clippy::wrong_self_convention)]
+pub trait SetMergeOp<T> {
+    /// Called for the next n elements of a, which are not in b
+    fn from_a(&self, m: &mut SetMergeState<'_, T>, n: usize);
+    /// Called for the next n elements of b, which are not in a
+    fn from_b(&self, m: &mut SetMergeState<'_, T>, n: usize);
+    /// Called for the next element of a and the next element of b, which are equal
+    fn collision(&self, m: &mut SetMergeState<'_, T>);
+    /// Merges a and b into a new set
+    ///
+    /// The built-in operations override this to use the faster monomorphized merge. In debug builds, this
+    /// panics if the callbacks did not produce a strictly sorted result, e.g. because `collision` took both
+    /// elements.
+    fn apply(&self, a: &VecSet<T>, b: &VecSet<T>) -> VecSet<T>
+    where
+        T: Ord + Clone,
+    {
+        let mut m = SetMergeState::new(&a.0, &b.0);
+        DynOp(self).merge(&mut m);
+        VecSet::from_sorted_unchecked(m.0.into_vec())
+    }
+}
+
+/// Adapts a possibly unsized [SetMergeOp] to the binary merge
+struct DynOp<'o, O: ?Sized>(&'o O);
+
+impl<'a, 'o, T: Ord, O: SetMergeOp<T> + ?Sized> ShortcutMergeOperation<T, T, SetMergeState<'a, T>>
+    for DynOp<'o, O>
+{
+    fn cmp(&self, a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+    fn from_a(&self, m: &mut SetMergeState<'a, T>, n: usize) -> EarlyOut {
+        self.0.from_a(m, n);
+        Some(())
+    }
+    fn from_b(&self, m: &mut SetMergeState<'a, T>, n: usize) -> EarlyOut {
+        self.0.from_b(m, n);
+        Some(())
+    }
+    fn collision(&self, m: &mut SetMergeState<'a, T>) -> EarlyOut {
+        self.0.collision(m);
+        Some(())
+    }
+}
+
+macro_rules! set_merge_op {
+    ($op:ty, $method:ident) => {
+        // the callbacks forward to the merge operation on the wrapped merge state
+        impl<T: Ord + Clone> SetMergeOp<T> for $op {
+            fn from_a(&self, m: &mut SetMergeState<'_, T>, n: usize) {
+                let _ = ShortcutMergeOperation::from_a(self, &mut m.0, n);
+            }
+            fn from_b(&self, m: &mut SetMergeState<'_, T>, n: usize) {
+                let _ = ShortcutMergeOperation::from_b(self, &mut m.0, n);
+            }
+            fn collision(&self, m: &mut SetMergeState<'_, T>) {
+                let _ = ShortcutMergeOperation::collision(self, &mut m.0);
+            }
+            fn apply(&self, a: &VecSet<T>, b: &VecSet<T>) -> VecSet<T> {
+                a.$method(b)
+            }
+        }
+    };
+}
+
+set_merge_op!(SetUnionOp, union);
+set_merge_op!(SetIntersectionOp, intersection);
+set_merge_op!(SetDiffOp, difference);
+set_merge_op!(SetXorOp, symmetric_difference);
+
+/// A set operation that runs in steps, so that a large merge can be interleaved with other work
+///
+/// Each call to `step` continues where the previous one stopped. The progress is kept as the positions in
+/// both inputs and the stack of pending work of the iterative binary merge, so the result is exactly the
+/// same as for running the operation to completion.
+pub struct ResumableMerge<'a, T, O> {
+    op: O,
+    state: SetMergeState<'a, T>,
+    stack: ResumableMergeStack,
+}
+
+impl<'a, T, O: SetMergeOp<T>> ResumableMerge<'a, T, O> {
+    /// A merge of a and b using op, that has not done any work yet
+    pub fn new(a: &'a VecSet<T>, b: &'a VecSet<T>, op: O) -> Self {
+        let state = SetMergeState::new(&a.0, &b.0);
+        let stack = ResumableMergeStack::new(state.a_len(), state.b_len());
+        Self { op, state, stack }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.stack.is_done()
+    }
+
+    /// The part of the result that has been produced so far
+    pub fn result(&self) -> &[T] {
+        self.state.result()
+    }
+}
+
+impl<'a, T: Ord, O: SetMergeOp<T>> ResumableMerge<'a, T, O> {
+    /// Adds up to n elements to the result, returning `Poll::Ready` once the merge is complete
+    ///
+    /// Elements that are skipped do not count, so a step only returns `Poll::Pending` after adding exactly n
+    /// elements.
+    pub fn step(&mut self, n: usize) -> Poll<()> {
+        let len = |m: &SetMergeState<'a, T>| m.result().len();
+        self.stack.step(&DynOp(&self.op), &mut self.state, n, len)
+    }
+
+    /// The result, which is only complete if `step` has returned `Poll::Ready`
+    ///
+    /// Like [SetMergeOp::apply], this panics in debug builds if the result is not strictly sorted.
+    pub fn into_result(self) -> VecSet<T> {
+        VecSet::from_sorted_unchecked(self.state.0.into_vec())
+    }
+}
+
 impl<T: Debug> Debug for VecSet<T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_set().entries(self.0.iter()).finish()
     }
 }
 
-impl<T> Into<Vec<T>> for VecSet<T> {
-    fn into(self) -> Vec<T> {
-        self.0
+impl<T> From<VecSet<T>> for Vec<T> {
+    fn from(value: VecSet<T>) -> Self {
+        value.0
     }
 }
 
@@ -285,13 +970,74 @@ impl<T> VecSet<T> {
     pub fn empty() -> Self {
         Self(Vec::new())
     }
+    /// The sorted elements without duplicates, reusing the storage of the set
+    pub fn into_vec(self) -> Vec<T> {
+        self.0
+    }
+    /// An empty set, same as `empty`
+    pub fn new() -> Self {
+        Self::empty()
+    }
+    /// An empty set with room for at least capacity elements
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self(Vec::with_capacity(capacity))
+    }
+    /// The number of elements the set can hold without reallocating
+    pub fn capacity(&self) -> usize {
+        self.0.capacity()
+    }
+    /// Removes all elements, keeping the allocated capacity
+    pub fn clear(&mut self) {
+        self.0.clear()
+    }
     /// An iterator that returns the items of this vec set in sorted order
-    pub fn iter(&self) -> SortedIter<std::slice::Iter<T>> {
+    pub fn iter(&self) -> SortedIter<core::slice::Iter<'_, T>> {
         SortedIter::new(self.0.iter())
     }
+    /// Retains only the elements for which f returns true, keeping the allocation
+    ///
+    /// Removing elements from a sorted vec keeps it sorted, so this is just `Vec::retain`.
     pub fn retain<F: FnMut(&T) -> bool>(&mut self, f: F) {
         self.0.retain(f)
     }
+    /// Removes the elements for which pred returns true, and returns them in sorted order
+    ///
+    /// The remaining elements stay in the allocation of self, and are still sorted.
+    pub fn drain_filter<F: FnMut(&T) -> bool>(&mut self, mut pred: F) -> Vec<T> {
+        self.0.extract_if(.., |x| pred(x)).collect()
+    }
+    /// The smallest element
+    ///
+    /// There is no `min` alias, since `VecSet` implements `Ord`, so `set.min(other)` is already taken.
+    pub fn first(&self) -> Option<&T> {
+        self.0.first()
+    }
+    /// The largest element
+    pub fn last(&self) -> Option<&T> {
+        self.0.last()
+    }
+    /// The element at position i in sorted order
+    pub fn nth(&self, i: usize) -> Option<&T> {
+        self.0.get(i)
+    }
+}
+
+impl<T> IntoIterator for VecSet<T> {
+    type Item = T;
+    type IntoIter = SortedIter<alloc::vec::IntoIter<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        SortedIter::new(self.0.into_iter())
+    }
+}
+
+impl<'a, T> IntoIterator for &'a VecSet<T> {
+    type Item = &'a T;
+    type IntoIter = SortedIter<core::slice::Iter<'a, T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
 }
 
 impl<T> Default for VecSet<T> {
@@ -320,73 +1066,69 @@ impl<T: Ord> BitXorAssign for VecSet<T> {
 
 impl<T: Ord> SubAssign for VecSet<T> {
     fn sub_assign(&mut self, that: Self) {
-        UnsafeInPlaceMergeState::merge_shortcut(&mut self.0, that.0, SetDiffOpt);
+        UnsafeInPlaceMergeState::merge_shortcut(&mut self.0, that.0, SetDiffOp);
     }
 }
 
 impl<T: Ord + Clone> BitAnd for &VecSet<T> {
     type Output = VecSet<T>;
     fn bitand(self, that: Self) -> Self::Output {
-        VecSet(VecMergeState::merge_shortcut(
-            &self.0,
-            &that.0,
-            SetIntersectionOp,
-        ))
+        self.intersection(that)
     }
 }
 
-// impl<T: Ord> BitAnd for VecSet<T> {
-//     type Output = VecSet<T>;
-//     fn bitand(mut self, that: Self) -> Self::Output {
-//         self &= that;
-//         self
-//     }
-// }
+impl<T: Ord> BitAnd for VecSet<T> {
+    type Output = VecSet<T>;
+    fn bitand(mut self, that: Self) -> Self::Output {
+        self &= that;
+        self
+    }
+}
 
 impl<T: Ord + Clone> BitOr for &VecSet<T> {
     type Output = VecSet<T>;
     fn bitor(self, that: Self) -> Self::Output {
-        VecSet(VecMergeState::merge_shortcut(&self.0, &that.0, SetUnionOp))
+        self.union(that)
     }
 }
 
-// impl<T: Ord> BitOr for VecSet<T> {
-//     type Output = VecSet<T>;
-//     fn bitor(mut self, that: Self) -> Self::Output {
-//         self |= that;
-//         self
-//     }
-// }
+impl<T: Ord> BitOr for VecSet<T> {
+    type Output = VecSet<T>;
+    fn bitor(mut self, that: Self) -> Self::Output {
+        self |= that;
+        self
+    }
+}
 
 impl<T: Ord + Clone> BitXor for &VecSet<T> {
     type Output = VecSet<T>;
     fn bitxor(self, that: Self) -> Self::Output {
-        VecSet(VecMergeState::merge_shortcut(&self.0, &that.0, SetXorOp))
+        self.symmetric_difference(that)
     }
 }
 
-// impl<T: Ord> BitXor for VecSet<T> {
-//     type Output = VecSet<T>;
-//     fn bitxor(mut self, that: Self) -> Self::Output {
-//         self ^= that;
-//         self
-//     }
-// }
+impl<T: Ord> BitXor for VecSet<T> {
+    type Output = VecSet<T>;
+    fn bitxor(mut self, that: Self) -> Self::Output {
+        self ^= that;
+        self
+    }
+}
 
 impl<T: Ord + Clone> Sub for &VecSet<T> {
     type Output = VecSet<T>;
     fn sub(self, that: Self) -> Self::Output {
-        VecSet(VecMergeState::merge_shortcut(&self.0, &that.0, SetDiffOpt))
+        self.difference(that)
     }
 }
 
-// impl<T: Ord> Sub for VecSet<T> {
-//     type Output = VecSet<T>;
-//     fn sub(mut self, that: Self) -> Self::Output {
-//         self -= that;
-//         self
-//     }
-// }
+impl<T: Ord> Sub for VecSet<T> {
+    type Output = VecSet<T>;
+    fn sub(mut self, that: Self) -> Self::Output {
+        self -= that;
+        self
+    }
+}
 
 impl<T: Ord> From<Vec<T>> for VecSet<T> {
     fn from(vec: Vec<T>) -> Self {
@@ -406,9 +1148,16 @@ impl<T: Ord> FromIterator<T> for VecSet<T> {
     }
 }
 
+/// Sorts just the new elements and then merges them in, so extending a set of size n with m
+/// elements is `O(m log m + n)` instead of re-sorting everything. A single element is inserted directly.
 impl<T: Ord> Extend<T> for VecSet<T> {
     fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
-        *self &= Self::from_iter(iter);
+        let mut batch = sort_and_dedup(iter.into_iter());
+        if batch.len() == 1 {
+            self.insert(batch.pop().unwrap());
+        } else if !batch.is_empty() {
+            *self |= Self(batch);
+        }
     }
 }
 
@@ -418,18 +1167,77 @@ impl<'a, T: 'a + Ord + Copy> Extend<&'a T> for VecSet<T> {
     }
 }
 
-impl<T: Ord> VecSet<T> {
-    pub fn insert(&mut self, that: T) {
-        match self.0.binary_search(&that) {
-            Ok(index) => self.0[index] = that,
-            Err(index) => self.0.insert(index, that),
+/// Serializes as a plain sequence of the elements in ascending order
+#[cfg(feature = "serde")]
+impl<T: Serialize> Serialize for VecSet<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+/// Deserializes from a sequence of elements
+///
+/// The sequence is not trusted to be sorted and deduplicated. It is sorted and deduplicated after reading,
+/// just like when converting from a `Vec`, so an unsorted sequence or one containing duplicates is accepted.
+/// This costs very little if the sequence is already sorted.
+#[cfg(feature = "serde")]
+impl<'de, T: Ord + Deserialize<'de>> Deserialize<'de> for VecSet<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<T>::deserialize(deserializer).map(Self::from_vec)
+    }
+}
+
+impl<T: Ord> VecSet<T> {
+    /// Inserts a single element, returning true if it was not already contained
+    ///
+    /// An equal element that is already contained is replaced. This shifts all larger elements, so it is
+    /// `O(n)`. To add many elements, collect them into a set and use [VecSet::union_with], which is
+    /// `O(n + m)` for all of them.
+    pub fn insert(&mut self, that: T) -> bool {
+        match self.0.binary_search_by(|x| x.cmp(&that)) {
+            Ok(index) => {
+                self.0[index] = that;
+                false
+            }
+            Err(index) => {
+                self.0.insert(index, that);
+                true
+            }
         }
     }
 
-    pub fn remove(&mut self, that: &T) {
-        if let Ok(index) = self.0.binary_search(&that) {
-            self.0.remove(index);
-        };
+    /// Removes a single element, returning true if it was contained
+    ///
+    /// Like [VecSet::insert], this shifts all larger elements, so prefer [VecSet::difference_with] to
+    /// remove many elements at once.
+    pub fn remove(&mut self, that: &T) -> bool {
+        match self.0.binary_search_by(|x| x.cmp(that)) {
+            Ok(index) => {
+                self.0.remove(index);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// The union of all given sets
+    ///
+    /// The sets are merged pairwise in a balanced tree, so each element takes part in `O(log k)` merges
+    /// for `k` sets, instead of `O(k)` merges when folding the sets from left to right.
+    pub fn union_all(sets: Vec<VecSet<T>>) -> Self {
+        let mut sets = sets;
+        while sets.len() > 1 {
+            let mut merged = Vec::with_capacity(sets.len().div_ceil(2));
+            let mut iter = sets.into_iter();
+            while let Some(mut a) = iter.next() {
+                if let Some(b) = iter.next() {
+                    a |= b;
+                }
+                merged.push(a);
+            }
+            sets = merged;
+        }
+        sets.pop().unwrap_or_default()
     }
 
     pub fn is_disjoint(&self, that: &VecSet<T>) -> bool {
@@ -437,16 +1245,150 @@ impl<T: Ord> VecSet<T> {
     }
 
     pub fn is_subset(&self, that: &VecSet<T>) -> bool {
-        !BoolOpMergeState::merge(&self.0, &that.0, SetDiffOpt)
+        !BoolOpMergeState::merge(&self.0, &that.0, SetDiffOp)
     }
 
     pub fn is_superset(&self, that: &VecSet<T>) -> bool {
         that.is_subset(self)
     }
+
+    /// Classifies how self and that relate to each other, in a single merge pass
+    ///
+    /// This is cheaper than calling `is_subset`, `is_superset` and `is_disjoint` when all of them are
+    /// needed. The pass stops early once the sets are known to be `Overlapping`.
+    pub fn relation(&self, that: &VecSet<T>) -> SetRelation {
+        let mut state = RelationMergeState::new(&self.0, &that.0);
+        SetRelationOp.merge(&mut state);
+        match (state.only_a, state.only_b, state.both) {
+            (false, false, _) => SetRelation::Equal,
+            (false, true, _) => SetRelation::Subset,
+            (true, false, _) => SetRelation::Superset,
+            (true, true, true) => SetRelation::Overlapping,
+            (true, true, false) => SetRelation::Disjoint,
+        }
+    }
     pub fn contains(&self, value: &T) -> bool {
         self.0.binary_search(value).is_ok()
     }
 
+    /// Like `contains`, but galloping from the index hint instead of bisecting the entire set
+    ///
+    /// This is faster than `contains` if value is close to the element at hint, e.g. when hint is the
+    /// index found for a previous, slightly smaller value. See [ContainsCursor] for a way to keep track of it.
+    pub fn contains_from_hint(&self, value: &T, hint: usize) -> bool {
+        gallop_search_from(&self.0, value, hint).is_ok()
+    }
+
+    /// A cursor for a sequence of `contains` queries that are close to each other
+    pub fn contains_cursor(&self) -> ContainsCursor<'_, T> {
+        ContainsCursor {
+            elements: &self.0,
+            position: 0,
+        }
+    }
+
+    /// The number of elements in the intersection of self and that, without creating the intersection
+    pub fn intersection_count(&self, that: &VecSet<T>) -> usize {
+        CountMergeState::merge(&self.0, &that.0, SetIntersectionOp)
+    }
+
+    /// The number of elements in self but not in that, without creating the difference
+    pub fn difference_count(&self, that: &VecSet<T>) -> usize {
+        CountMergeState::merge(&self.0, &that.0, SetDiffOp)
+    }
+
+    /// The number of elements in exactly one of self and that, without creating the symmetric difference
+    pub fn symmetric_difference_count(&self, that: &VecSet<T>) -> usize {
+        CountMergeState::merge(&self.0, &that.0, SetXorOp)
+    }
+
+    /// Creates a set from a vec that is already sorted in strictly ascending order
+    ///
+    /// This checks the order in `O(n)` instead of sorting, and never reorders the elements. If the vec is
+    /// not sorted or contains duplicates, the error contains the index of the first offending element.
+    pub fn try_from_sorted(vec: Vec<T>) -> Result<Self, NotSortedError> {
+        match vec.windows(2).position(|w| w[0] >= w[1]) {
+            Some(i) => Err(NotSortedError { index: i + 1 }),
+            None => Ok(Self(vec)),
+        }
+    }
+
+    /// Creates a set from a vec that is known to be sorted in strictly ascending order, without checking
+    ///
+    /// If the vec is not strictly sorted, this will not cause undefined behaviour, but the set operations
+    /// will return unspecified results. The order is only checked in debug builds.
+    pub fn from_sorted_unchecked(vec: Vec<T>) -> Self {
+        debug_assert!(vec.windows(2).all(|w| w[0] < w[1]));
+        Self(vec)
+    }
+
+    /// Splits the set into the elements less than pivot and the elements greater or equal to pivot
+    pub fn split_at_value(self, pivot: &T) -> (VecSet<T>, VecSet<T>) {
+        let mut lo = self.0;
+        let hi = lo.split_off(lower_bound(&lo, pivot));
+        (VecSet(lo), VecSet(hi))
+    }
+
+    /// The elements in the half open range `[lo, hi)` as a slice
+    pub fn range(&self, lo: &T, hi: &T) -> &[T] {
+        &self.0[self.range_indices(lo, hi)]
+    }
+
+    /// The indices into [VecSet::as_slice] of the elements in the half open range `[lo, hi)`
+    ///
+    /// The range is empty if `hi <= lo`.
+    pub fn range_indices(&self, lo: &T, hi: &T) -> Range<usize> {
+        let start = lower_bound(&self.0, lo);
+        let end = lower_bound(&self.0, hi).max(start);
+        start..end
+    }
+
+    /// The number of elements that are strictly less than value
+    pub fn rank(&self, value: &T) -> usize {
+        lower_bound(&self.0, value)
+    }
+
+    /// Searches the set for value, like `slice::binary_search` on [VecSet::as_slice]
+    ///
+    /// Returns the index of value if it is contained, and otherwise the index where it would be inserted.
+    pub fn binary_search(&self, value: &T) -> Result<usize, usize> {
+        self.0.binary_search(value)
+    }
+
+    /// The index of value in [VecSet::as_slice], if it is contained
+    pub fn position(&self, value: &T) -> Option<usize> {
+        self.binary_search(value).ok()
+    }
+
+    /// The number of distinct elements in all given sets, without creating the union
+    ///
+    /// This does a k-way merge of the sets, counting each distinct element once.
+    pub fn union_count_all(sets: &[VecSet<T>]) -> usize {
+        kmerge(sets.iter().map(|set| set.iter()).collect()).count()
+    }
+
+    /// The jaccard similarity `|self ∩ that| / |self ∪ that|` of self and that
+    ///
+    /// This needs just one merge pass to count the intersection, since the size of the union follows from
+    /// the sizes of the sets and the intersection. Two empty sets are considered identical, with a
+    /// similarity of 1.0.
+    pub fn jaccard(&self, that: &VecSet<T>) -> f64 {
+        let intersection = self.intersection_count(that);
+        let union = self.len() + that.len() - intersection;
+        if union == 0 {
+            1.0
+        } else {
+            intersection as f64 / union as f64
+        }
+    }
+
+    /// true if self and that have at least k elements in common
+    ///
+    /// Unlike `intersection_count`, this stops as soon as k common elements have been found.
+    pub fn shares_at_least(&self, that: &VecSet<T>, k: usize) -> bool {
+        ThresholdMergeState::merge(&self.0, &that.0, k, SetIntersectionOp)
+    }
+
     fn from_vec(vec: Vec<T>) -> Self {
         let mut vec = vec;
         vec.sort();
@@ -455,23 +1397,319 @@ impl<T: Ord> VecSet<T> {
     }
 }
 
-// impl<T: Ord + Default + Copy> VecSet<T> {
-//     pub fn union_with(&mut self, that: &VecSet<T>) {
-//         InPlaceMergeState::merge(&mut self.0, &that.0, SetUnionOp());
-//     }
+impl<T: Ord + Clone> VecSet<T> {
+    /// The elements that are in self or that, as a new set
+    ///
+    /// The result is allocated up front for the worst case, so the merge never has to reallocate. This is up
+    /// to twice as large as needed, so if the sets overlap so much that more than a quarter of it is unused,
+    /// the result is shrunk to fit.
+    pub fn union(&self, that: &VecSet<T>) -> Self {
+        let mut elements = Vec::with_capacity(self.len() + that.len());
+        VecMergeState::merge_shortcut_into(&self.0, &that.0, SetUnionOp, &mut elements);
+        shrink_slack(&mut elements);
+        VecSet(elements)
+    }
+
+    /// The union of self and that, borrowing one of the inputs if it already is the union
+    ///
+    /// If one set is a subset of the other, the union is just the bigger set, so no allocation is needed.
+    /// The subset checks use the shortcut merge, so they usually abort early if the sets are unrelated.
+    pub fn union_cow<'a>(&'a self, that: &'a VecSet<T>) -> Cow<'a, VecSet<T>> {
+        if that.is_subset(self) {
+            Cow::Borrowed(self)
+        } else if self.is_subset(that) {
+            Cow::Borrowed(that)
+        } else {
+            Cow::Owned(self.union(that))
+        }
+    }
+
+    /// The union of self and that, together with the provenance of each element of the union
+    ///
+    /// The provenance has the same length as the union, and tells for each element whether it was only in
+    /// self (`Provenance::A`), only in that (`Provenance::B`), or in both (`Provenance::Both`).
+    pub fn union_with_provenance(&self, that: &VecSet<T>) -> (Self, Vec<Provenance>) {
+        let (r, p) = ProvenanceMergeState::merge(&self.0, &that.0, SetProvenanceUnionOp);
+        (VecSet(r), p)
+    }
+
+    /// The elements that are in both self and that, as a new set
+    pub fn intersection(&self, that: &VecSet<T>) -> Self {
+        VecSet(VecMergeState::merge_shortcut(
+            &self.0,
+            &that.0,
+            SetIntersectionOp,
+        ))
+    }
+
+    /// The elements that are in self but not in that, as a new set
+    pub fn difference(&self, that: &VecSet<T>) -> Self {
+        VecSet(VecMergeState::merge_shortcut(&self.0, &that.0, SetDiffOp))
+    }
+
+    /// The union of self and that, where elements collide if they have the same key
+    ///
+    /// On a collision the element of self is kept. This allows e.g. to merge pairs by their first component.
+    ///
+    /// Precondition: the order of the keys must be consistent with the order of the elements, and no two
+    /// elements of the same set may have the same key. If not, the result is unspecified.
+    pub fn union_by_key<K: Ord>(&self, that: &VecSet<T>, key: impl Fn(&T) -> K) -> Self {
+        let op = CmpMergeOperation::new(SetUnionOp, |a: &T, b: &T| key(a).cmp(&key(b)));
+        VecSet(VecMergeState::merge_shortcut(&self.0, &that.0, op))
+    }
+
+    /// The elements of self whose key is also the key of an element of that
+    ///
+    /// This has the same precondition as [VecSet::union_by_key].
+    pub fn intersection_by_key<K: Ord>(&self, that: &VecSet<T>, key: impl Fn(&T) -> K) -> Self {
+        let op = CmpMergeOperation::new(SetIntersectionOp, |a: &T, b: &T| key(a).cmp(&key(b)));
+        VecSet(VecMergeState::merge_shortcut(&self.0, &that.0, op))
+    }
+
+    /// The elements of self whose key is not the key of any element of that
+    ///
+    /// This has the same precondition as [VecSet::union_by_key].
+    pub fn difference_by_key<K: Ord>(&self, that: &VecSet<T>, key: impl Fn(&T) -> K) -> Self {
+        let op = CmpMergeOperation::new(SetDiffOp, |a: &T, b: &T| key(a).cmp(&key(b)));
+        VecSet(VecMergeState::merge_shortcut(&self.0, &that.0, op))
+    }
+
+    /// The elements that are in exactly one of self and that, as a new set
+    pub fn symmetric_difference(&self, that: &VecSet<T>) -> Self {
+        VecSet(VecMergeState::merge_shortcut(&self.0, &that.0, SetXorOp))
+    }
+
+    /// Like [VecSet::union], but using the given [MergeStrategy]
+    pub fn union_with_strategy(&self, that: &VecSet<T>, strategy: MergeStrategy) -> Self {
+        VecSet(strategy.merge(&self.0, &that.0, &SetUnionOp))
+    }
+
+    /// Like [VecSet::intersection], but using the given [MergeStrategy]
+    pub fn intersection_with_strategy(&self, that: &VecSet<T>, strategy: MergeStrategy) -> Self {
+        VecSet(strategy.merge(&self.0, &that.0, &SetIntersectionOp))
+    }
+
+    /// Like [VecSet::difference], but using the given [MergeStrategy]
+    pub fn difference_with_strategy(&self, that: &VecSet<T>, strategy: MergeStrategy) -> Self {
+        VecSet(strategy.merge(&self.0, &that.0, &SetDiffOp))
+    }
+
+    /// Like [VecSet::symmetric_difference], but using the given [MergeStrategy]
+    pub fn symmetric_difference_with_strategy(
+        &self,
+        that: &VecSet<T>,
+        strategy: MergeStrategy,
+    ) -> Self {
+        VecSet(strategy.merge(&self.0, &that.0, &SetXorOp))
+    }
+
+    /// The number of comparisons [VecSet::union_with_strategy] needs for self and that
+    ///
+    /// This is meant for tuning, e.g. to check how many comparisons `MergeStrategy::Binary` saves compared
+    /// to `MergeStrategy::Linear` for the data at hand. The union itself is discarded.
+    pub fn union_comparisons(&self, that: &VecSet<T>, strategy: MergeStrategy) -> usize {
+        strategy.comparisons(&self.0, &that.0, SetUnionOp)
+    }
+
+    /// The number of comparisons [VecSet::intersection_with_strategy] needs for self and that
+    pub fn intersection_comparisons(&self, that: &VecSet<T>, strategy: MergeStrategy) -> usize {
+        strategy.comparisons(&self.0, &that.0, SetIntersectionOp)
+    }
+
+    /// The number of comparisons [VecSet::difference_with_strategy] needs for self and that
+    pub fn difference_comparisons(&self, that: &VecSet<T>, strategy: MergeStrategy) -> usize {
+        strategy.comparisons(&self.0, &that.0, SetDiffOp)
+    }
 
-//     pub fn intersection_with(&mut self, that: &VecSet<T>) {
-//         InPlaceMergeState::merge(&mut self.0, &that.0, SetIntersectionOp());
-//     }
+    /// The number of comparisons [VecSet::symmetric_difference_with_strategy] needs for self and that
+    pub fn symmetric_difference_comparisons(
+        &self,
+        that: &VecSet<T>,
+        strategy: MergeStrategy,
+    ) -> usize {
+        strategy.comparisons(&self.0, &that.0, SetXorOp)
+    }
 
-//     pub fn xor_with(&mut self, that: &VecSet<T>) {
-//         InPlaceMergeState::merge(&mut self.0, &that.0, SetIntersectionOp());
-//     }
+    /// The union of self, b and c, computed in a single three-way merge
+    ///
+    /// On collisions the element of self is kept, then the element of b. This does not build the intermediate
+    /// result of calling [VecSet::union] twice.
+    pub fn union3(&self, b: &VecSet<T>, c: &VecSet<T>) -> Self {
+        VecSet(VecMergeState3::merge(&self.0, &b.0, &c.0, SetUnion3Op))
+    }
 
-//     pub fn difference_with(&mut self, that: &VecSet<T>) {
-//         InPlaceMergeState::merge(&mut self.0, &that.0, SetDiffOpt());
-//     }
-// }
+    /// The union and the intersection of self and that, computed in a single merge
+    ///
+    /// This needs half the comparisons of calling [VecSet::union] and [VecSet::intersection] separately.
+    pub fn union_and_intersection(&self, that: &VecSet<T>) -> (Self, Self) {
+        let mut state = UnionIntersectionMergeState::new(&self.0, &that.0);
+        SetUnionIntersectionOp.merge(&mut state);
+        (VecSet(state.union), VecSet(state.intersection))
+    }
+
+    /// The changes from self to new, as `(removed, added)`
+    ///
+    /// `removed` are the elements only in self, and `added` the elements only in new. Both are computed in
+    /// a single merge, and unchanged elements are skipped.
+    pub fn diff(&self, new: &VecSet<T>) -> (Self, Self) {
+        let (removed, added) = SplitMergeState::merge(&self.0, &new.0, SetXorOp);
+        (VecSet(removed), VecSet(added))
+    }
+
+    /// The union of self and that, with the given policy for elements that are in both
+    pub fn union_with_policy(&self, that: &VecSet<T>, policy: CollisionPolicy) -> Self {
+        let (take_a, take_b) = policy.takes();
+        let op = SetPolicyUnionOp(take_a, take_b);
+        VecSet(VecMergeState::merge_shortcut(&self.0, &that.0, op))
+    }
+
+    /// The intersection of self and that, with the given policy for which elements are kept
+    pub fn intersection_with_policy(&self, that: &VecSet<T>, policy: CollisionPolicy) -> Self {
+        let (take_a, take_b) = policy.takes();
+        let op = SetPolicyIntersectionOp(take_a, take_b);
+        VecSet(VecMergeState::merge_shortcut(&self.0, &that.0, op))
+    }
+
+    /// The union of self and that, keeping both elements for elements that are in both
+    ///
+    /// Of two elements that compare equal, the element of self comes first. The result is sorted, but not
+    /// strictly sorted, so it is returned as a `Vec`.
+    pub fn union_keep_both(&self, that: &VecSet<T>) -> Vec<T> {
+        VecMergeState::merge_shortcut(&self.0, &that.0, SetPolicyUnionOp(true, true))
+    }
+
+    /// The elements of self and that that are in both, keeping the element of self followed by the element
+    /// of that
+    ///
+    /// Like for [VecSet::union_keep_both], the result is returned as a `Vec`.
+    pub fn intersection_keep_both(&self, that: &VecSet<T>) -> Vec<T> {
+        VecMergeState::merge_shortcut(&self.0, &that.0, SetPolicyIntersectionOp(true, true))
+    }
+
+    /// The elements that are in self or in the sorted slice that, as a new set
+    ///
+    /// This merges directly against the slice, without creating a set from it first. `that` must be sorted.
+    /// It should not contain duplicates, but if it does they are removed, so the result is always a set.
+    pub fn union_with_slice(&self, that: &[T]) -> Self {
+        let mut elements = VecMergeState::merge_shortcut(&self.0, that, SetUnionOp);
+        elements.dedup();
+        VecSet(elements)
+    }
+
+    /// The elements that are in both self and the sorted slice that, as a new set
+    ///
+    /// `that` must be sorted. Duplicates in `that` are ignored.
+    pub fn intersection_with_slice(&self, that: &[T]) -> Self {
+        VecSet(VecMergeState::merge_shortcut(
+            &self.0,
+            that,
+            SetIntersectionOp,
+        ))
+    }
+
+    /// The elements that are in self but not in the sorted slice that, as a new set
+    ///
+    /// `that` must be sorted. Duplicates in `that` are ignored.
+    pub fn difference_with_slice(&self, that: &[T]) -> Self {
+        VecSet(VecMergeState::merge_shortcut(&self.0, that, SetDiffOp))
+    }
+
+    /// Adds the elements of that within `[lo, hi)` to self, leaving all other elements of self untouched
+    ///
+    /// The window is found by binary search in both sets, and only the elements of self within the window
+    /// are merged and spliced back. Returns true if self was changed.
+    pub fn union_with_in_range(&mut self, that: &VecSet<T>, lo: &T, hi: &T) -> bool {
+        let window = |elements: &[T]| {
+            let start = elements.partition_point(|x| x < lo);
+            let end = start + elements[start..].partition_point(|x| x < hi);
+            start..end
+        };
+        let a = window(&self.0);
+        let b = window(&that.0);
+        if b.is_empty() {
+            return false;
+        }
+        let merged = VecMergeState::merge_shortcut(&self.0[a.clone()], &that.0[b], SetUnionOp);
+        if merged.len() == a.len() {
+            return false;
+        }
+        self.0.splice(a, merged);
+        true
+    }
+
+    /// The k smallest elements of the union of self and that
+    ///
+    /// The merge stops as soon as k elements have been produced, so this is cheap for small k.
+    pub fn union_take(&self, that: &VecSet<T>, k: usize) -> Self {
+        if k == 0 {
+            return Self::empty();
+        }
+        let mut state = TakeMergeState::new(VecMergeState::new(&self.0, &that.0, Vec::new()), k);
+        SetUnionOp.merge(&mut state);
+        VecSet(state.inner.into_vec())
+    }
+
+    /// The complement of self within the given universe: the elements of universe that are not in self
+    ///
+    /// This is the same as `universe.difference(self)`, done in a single merge. Elements of self that are
+    /// not in the universe are ignored.
+    pub fn complement_within(&self, universe: &VecSet<T>) -> Self {
+        VecSet(VecMergeState::merge_shortcut(
+            &universe.0,
+            &self.0,
+            SetDiffOp,
+        ))
+    }
+}
+
+/// Operations for `Copy` elements that copy runs without capacity checks or clones
+impl<T: Ord + Copy> VecSet<T> {
+    /// Like [VecSet::union], but copying the runs found by the merge directly into the result
+    ///
+    /// The result is allocated for the worst case and shrunk if much of it is unused, like for `union`.
+    pub fn union_copy(&self, that: &VecSet<T>) -> Self {
+        let mut elements = CopyVecMergeState::merge_shortcut(&self.0, &that.0, SetUnionOp);
+        shrink_slack(&mut elements);
+        VecSet(elements)
+    }
+}
+
+/// Shrinks a result that was allocated for the worst case if more than a quarter of it is unused
+fn shrink_slack<T>(elements: &mut Vec<T>) {
+    if elements.capacity() - elements.len() > elements.capacity() / 4 {
+        elements.shrink_to_fit()
+    }
+}
+
+/// In-place operations that reuse the allocation of self where possible
+///
+/// They return true if self was changed, which is useful for iterating until a fixpoint is reached.
+impl<T: Ord + Clone + Default> VecSet<T> {
+    pub fn union_with(&mut self, that: &VecSet<T>) -> bool {
+        InPlaceMergeState::merge_shortcut_tracked(&mut self.0, &that.0, SetUnionOp)
+    }
+
+    /// Like [VecSet::union_with], but calls observer with the element of self and the element of that
+    /// for every collision, before the element of self is kept
+    ///
+    /// The result is the same as for `union_with`. This is useful to log or count merges.
+    pub fn union_with_observer<F: FnMut(&T, &T)>(&mut self, that: &VecSet<T>, observer: F) -> bool {
+        let op = SetObserverUnionOp(RefCell::new(observer));
+        InPlaceMergeState::merge_shortcut_tracked(&mut self.0, &that.0, op)
+    }
+
+    pub fn intersection_with(&mut self, that: &VecSet<T>) -> bool {
+        InPlaceMergeState::merge_shortcut_tracked(&mut self.0, &that.0, SetIntersectionOp)
+    }
+
+    pub fn symmetric_difference_with(&mut self, that: &VecSet<T>) -> bool {
+        InPlaceMergeState::merge_shortcut_tracked(&mut self.0, &that.0, SetXorOp)
+    }
+
+    pub fn difference_with(&mut self, that: &VecSet<T>) -> bool {
+        InPlaceMergeState::merge_shortcut_tracked(&mut self.0, &that.0, SetDiffOp)
+    }
+}
 
 // cargo asm vec_set::array_set::union_u32
 pub fn union_u32(a: &mut Vec<u32>, b: &[u32]) {
@@ -481,10 +1719,15 @@ pub fn union_u32(a: &mut Vec<u32>, b: &[u32]) {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::binary_merge::DescendingMergeOperation;
+    use crate::merge_state::UnsafeSliceMergeState;
     use crate::obey::*;
     use num_traits::PrimInt;
     use quickcheck::*;
-    use std::collections::BTreeSet;
+    use std::cmp::Reverse;
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::hash::{Hash, Hasher};
 
     impl<T: Arbitrary + Ord + Copy + Default + Debug> Arbitrary for VecSet<T> {
         fn arbitrary<G: Gen>(g: &mut G) -> Self {
@@ -511,6 +1754,26 @@ mod test {
     type Test = VecSet<i64>;
     type Reference = BTreeSet<i64>;
 
+    /// runs a resumable merge in steps of n elements, returning the result and the lengths after each step
+    fn resume<O: SetMergeOp<i64>>(a: &Test, b: &Test, op: O, n: usize) -> (Test, Vec<usize>) {
+        let mut merge = ResumableMerge::new(a, b, op);
+        let mut lengths = vec![0];
+        while merge.step(n).is_pending() {
+            lengths.push(merge.result().len());
+        }
+        lengths.push(merge.result().len());
+        (merge.into_result(), lengths)
+    }
+
+    /// true if each step produced n elements, except for the last one, which produced at most n
+    fn bounded(lengths: &[usize], n: usize) -> bool {
+        let steps: Vec<usize> = lengths.windows(2).map(|w| w[1] - w[0]).collect();
+        match steps.split_last() {
+            Some((last, pending)) => *last <= n && pending.iter().all(|k| *k == n),
+            None => true,
+        }
+    }
+
     quickcheck! {
 
         fn is_disjoint_sample(a: Test, b: Test) -> bool {
@@ -603,6 +1866,453 @@ mod test {
             let actual = a1.contains(&b);
             expected == actual
         }
+
+        fn intersection_count(a: Test, b: Test) -> bool {
+            a.intersection_count(&b) == a.intersection(&b).len()
+        }
+
+        fn difference_count(a: Test, b: Test) -> bool {
+            a.difference_count(&b) == a.difference(&b).len()
+                && a.symmetric_difference_count(&b) == a.symmetric_difference(&b).len()
+        }
+
+        fn shares_at_least(a: Test, b: Test, k: usize) -> bool {
+            let k = k % 8;
+            a.shares_at_least(&b, k) == (a.intersection_count(&b) >= k)
+        }
+
+        fn contains_from_hint(a: Test, b: Vec<i64>, hint: usize) -> bool {
+            let hint = hint % (a.len() + 2);
+            b.iter().all(|x| a.contains_from_hint(x, hint) == a.contains(x))
+                && a.iter().enumerate().all(|(i, x)| a.contains_from_hint(x, i))
+        }
+
+        fn contains_cursor(a: Test, b: Vec<i64>) -> bool {
+            let mut cursor = a.contains_cursor();
+            let mut sorted = b.clone();
+            sorted.sort();
+            // arbitrary order, then ascending order
+            b.iter().chain(sorted.iter()).all(|x| cursor.contains(x) == a.contains(x))
+        }
+
+        fn collision_policy_consistent(a: Test, b: Test) -> bool {
+            a.union_with_policy(&b, CollisionPolicy::TakeA) == a.union(&b)
+                && a.union_with_policy(&b, CollisionPolicy::TakeB) == a.union(&b)
+                && a.intersection_with_policy(&b, CollisionPolicy::TakeB) == a.intersection(&b)
+                && a.union_keep_both(&b).len() == a.len() + b.len()
+                && a.intersection_keep_both(&b).len() == 2 * a.intersection(&b).len()
+        }
+
+        fn in_place_ops(a: Test, b: Test) -> bool {
+            let mut union = a.clone();
+            let union_changed = union.union_with(&b);
+            let mut intersection = a.clone();
+            let intersection_changed = intersection.intersection_with(&b);
+            let mut difference = a.clone();
+            let difference_changed = difference.difference_with(&b);
+            let mut symmetric_difference = a.clone();
+            let symmetric_difference_changed = symmetric_difference.symmetric_difference_with(&b);
+            union == a.union(&b)
+                && intersection == a.intersection(&b)
+                && difference == a.difference(&b)
+                && symmetric_difference == a.symmetric_difference(&b)
+                && union_changed == (union != a)
+                && intersection_changed == (intersection != a)
+                && difference_changed == (difference != a)
+                && symmetric_difference_changed == (symmetric_difference != a)
+        }
+
+        fn named_ops(a: Reference, b: Reference) -> bool {
+            let a1: Test = a.iter().cloned().collect();
+            let b1: Test = b.iter().cloned().collect();
+            let union: Vec<i64> = a1.union(&b1).into();
+            let intersection: Vec<i64> = a1.intersection(&b1).into();
+            let difference: Vec<i64> = a1.difference(&b1).into();
+            let symmetric_difference: Vec<i64> = a1.symmetric_difference(&b1).into();
+            union == a.union(&b).cloned().collect::<Vec<_>>()
+                && intersection == a.intersection(&b).cloned().collect::<Vec<_>>()
+                && difference == a.difference(&b).cloned().collect::<Vec<_>>()
+                && symmetric_difference == a.symmetric_difference(&b).cloned().collect::<Vec<_>>()
+        }
+
+        fn operators(a: Test, b: Test) -> bool {
+            &a | &b == a.union(&b)
+                && &a & &b == a.intersection(&b)
+                && &a - &b == a.difference(&b)
+                && &a ^ &b == a.symmetric_difference(&b)
+                && a.clone() | b.clone() == a.union(&b)
+                && a.clone() & b.clone() == a.intersection(&b)
+                && a.clone() - b.clone() == a.difference(&b)
+                && a.clone() ^ b.clone() == a.symmetric_difference(&b)
+        }
+
+        fn descending_ops(a: Reference, b: Reference) -> bool {
+            let a1: VecSet<Reverse<i64>> = a.iter().cloned().map(Reverse).collect();
+            let b1: VecSet<Reverse<i64>> = b.iter().cloned().map(Reverse).collect();
+            let union: Vec<i64> = a1.union(&b1).iter().map(|x| x.0).collect();
+            let intersection: Vec<i64> = a1.intersection(&b1).iter().map(|x| x.0).collect();
+            let descending = |x: Reference| x.into_iter().rev().collect::<Vec<_>>();
+            union == descending(&a | &b) && intersection == descending(&a & &b)
+        }
+
+        fn extend(a: Reference, b: Vec<i64>) -> bool {
+            let mut a1: Test = a.iter().cloned().collect();
+            a1.extend(b.iter().cloned());
+            let mut expected = a;
+            expected.extend(b);
+            a1.as_slice() == expected.into_iter().collect::<Vec<_>>().as_slice()
+        }
+
+        fn union_with_provenance(a: Reference, b: Reference) -> bool {
+            let a1: Test = a.iter().cloned().collect();
+            let b1: Test = b.iter().cloned().collect();
+            let (r, p) = a1.union_with_provenance(&b1);
+            let expected: Vec<Provenance> = r
+                .iter()
+                .map(|x| match (a.contains(x), b.contains(x)) {
+                    (true, false) => Provenance::A,
+                    (false, true) => Provenance::B,
+                    _ => Provenance::Both,
+                })
+                .collect();
+            r == a1.union(&b1) && p.len() == r.len() && p == expected
+        }
+
+        fn merge_into_reused(pairs: Vec<(Reference, Reference)>) -> bool {
+            let mut out = Vec::new();
+            pairs.into_iter().all(|(a, b)| {
+                let a1: Vec<i64> = a.iter().cloned().collect();
+                let b1: Vec<i64> = b.iter().cloned().collect();
+                merge_into(&a1, &b1, &mut out);
+                out == a.union(&b).cloned().collect::<Vec<_>>()
+            })
+        }
+
+        fn merge_dedup_duplicates(a: Vec<i8>, b: Vec<i8>, repeat: u8) -> bool {
+            // sorted with heavy internal duplication
+            let dup = |x: &[i8]| -> Vec<i64> {
+                let n = |x: i8| (x as u8 % 4) as usize + repeat as usize % 8;
+                let mut r: Vec<i64> = x
+                    .iter()
+                    .flat_map(|x| std::iter::repeat_n(*x as i64, n(*x)))
+                    .collect();
+                r.sort();
+                r
+            };
+            let (a1, b1) = (dup(&a), dup(&b));
+            let a: Reference = a1.iter().cloned().collect();
+            let b: Reference = b1.iter().cloned().collect();
+            let union: Vec<i64> = a.union(&b).cloned().collect();
+            let intersection: Vec<i64> = a.intersection(&b).cloned().collect();
+            merge_dedup(&a1, &b1) == union
+                && DedupMergeState::merge_shortcut(&a1, &b1, SetIntersectionOp) == intersection
+        }
+
+        fn retain(a: Reference, m: i64) -> bool {
+            let m = m.rem_euclid(5) + 1;
+            let mut a1: Test = a.iter().cloned().collect();
+            a1.retain(|x| x % m == 0);
+            let expected: Vec<i64> = a.iter().cloned().filter(|x| x % m == 0).collect();
+            a1.as_slice() == expected.as_slice() && a1.as_slice().windows(2).all(|w| w[0] < w[1])
+        }
+
+        fn drain_filter(a: Reference, m: i64) -> bool {
+            let m = m.rem_euclid(5) + 1;
+            let mut a1: Test = a.iter().cloned().collect();
+            let drained = a1.drain_filter(|x| x % m == 0);
+            let expected_drained: Vec<i64> = a.iter().cloned().filter(|x| x % m == 0).collect();
+            let expected_kept: Vec<i64> = a.iter().cloned().filter(|x| x % m != 0).collect();
+            drained == expected_drained && a1.as_slice() == expected_kept.as_slice()
+        }
+
+        fn merge_append_flat(pairs: Vec<(Reference, Reference)>) -> bool {
+            let mut flat = Vec::new();
+            let ranges: Vec<Range<usize>> = pairs
+                .iter()
+                .map(|(a, b)| {
+                    let a1: Vec<i64> = a.iter().cloned().collect();
+                    let b1: Vec<i64> = b.iter().cloned().collect();
+                    merge_append(&a1, &b1, &mut flat)
+                })
+                .collect();
+            // the ranges are adjacent and cover the whole buffer
+            let adjacent = ranges.windows(2).all(|w| w[0].end == w[1].start)
+                && ranges.last().map(|r| r.end).unwrap_or_default() == flat.len();
+            adjacent
+                && pairs.iter().zip(ranges).all(|((a, b), r)| {
+                    let r = &flat[r];
+                    r.iter().eq(a.union(b)) && r.windows(2).all(|w| w[0] < w[1])
+                })
+        }
+
+        fn with_slice(a: Reference, b: Vec<i64>) -> bool {
+            // sorted, but with duplicates
+            let mut b: Vec<i64> = b;
+            b.sort();
+            let a1: Test = a.iter().cloned().collect();
+            let b1: Test = b.iter().cloned().collect();
+            let union = a1.union_with_slice(&b);
+            let intersection = a1.intersection_with_slice(&b);
+            let difference = a1.difference_with_slice(&b);
+            union == a1.union(&b1) && union.as_slice().windows(2).all(|w| w[0] < w[1])
+                && intersection == a1.intersection(&b1)
+                && difference == a1.difference(&b1)
+        }
+
+        fn merge_tie_break_union(a: Reference, b: Reference) -> bool {
+            let a1: Vec<i64> = a.iter().cloned().collect();
+            let b1: Vec<i64> = b.iter().cloned().collect();
+            let expected: Vec<i64> = a.union(&b).cloned().collect();
+            merge_tie_break(&a1, &b1, Ord::cmp, |_, _| Ordering::Less) == expected
+                && merge_tie_break(&a1, &b1, Ord::cmp, |_, _| Ordering::Greater) == expected
+        }
+
+        fn merge_indices_reconstruct(a: Reference, b: Reference) -> bool {
+            let a1: Vec<i64> = a.iter().cloned().collect();
+            let b1: Vec<i64> = b.iter().cloned().collect();
+            let expected: Vec<i64> = a.union(&b).cloned().collect();
+            let indices = merge_indices(&a1, &b1);
+            let reconstructed: Vec<i64> = indices
+                .iter()
+                .map(|i| match *i {
+                    MergeIndex::A(i) => a1[i],
+                    MergeIndex::B(i) => b1[i],
+                    MergeIndex::Both(i, j) => {
+                        assert_eq!(a1[i], b1[j]);
+                        a1[i]
+                    }
+                })
+                .collect();
+            reconstructed == expected
+        }
+
+        fn union_with_observer(a: Reference, b: Reference) -> bool {
+            let mut a1: Test = a.iter().cloned().collect();
+            let b1: Test = b.iter().cloned().collect();
+            let mut pairs = Vec::new();
+            let changed = a1.union_with_observer(&b1, |x, y| pairs.push((*x, *y)));
+            let expected: Vec<(i64, i64)> = a.intersection(&b).map(|x| (*x, *x)).collect();
+            let grown = !b.is_subset(&a);
+            pairs == expected && a1.as_slice().iter().eq(a.union(&b)) && changed == grown
+        }
+
+        fn union_with_in_range(a: Reference, b: Reference, lo: i64, hi: i64) -> bool {
+            let mut a1: Test = a.iter().cloned().collect();
+            let b1: Test = b.iter().cloned().collect();
+            let changed = a1.union_with_in_range(&b1, &lo, &hi);
+            let expected: Reference = a
+                .iter()
+                .chain(b.iter().filter(|x| lo <= **x && **x < hi))
+                .cloned()
+                .collect();
+            a1.as_slice().iter().eq(expected.iter()) && changed == (expected.len() != a.len())
+        }
+
+        fn into_vec_sorted(a: Vec<i64>) -> bool {
+            let expected: Vec<i64> = a.iter().cloned().collect::<Reference>().into_iter().collect();
+            Test::from(a).into_vec() == expected
+        }
+
+        fn relation_consistent(a: Reference, b: Reference) -> bool {
+            let a1: Test = a.iter().cloned().collect();
+            let b1: Test = b.iter().cloned().collect();
+            let expected = if a == b {
+                SetRelation::Equal
+            } else if a.is_subset(&b) {
+                SetRelation::Subset
+            } else if a.is_superset(&b) {
+                SetRelation::Superset
+            } else if a.is_disjoint(&b) {
+                SetRelation::Disjoint
+            } else {
+                SetRelation::Overlapping
+            };
+            a1.relation(&b1) == expected
+        }
+
+        fn insert_remove_feedback(a: Reference, ops: Vec<(bool, i64)>) -> bool {
+            let mut expected = a.clone();
+            let mut actual: Test = a.into_iter().collect();
+            ops.into_iter().all(|(insert, x)| {
+                let ok = if insert {
+                    actual.insert(x) == expected.insert(x)
+                } else {
+                    actual.remove(&x) == expected.remove(&x)
+                };
+                ok && actual.as_slice().iter().eq(expected.iter())
+            })
+        }
+
+        fn union_and_intersection(a: Reference, b: Reference) -> bool {
+            let a1: Test = a.iter().cloned().collect();
+            let b1: Test = b.iter().cloned().collect();
+            let (union, intersection) = a1.union_and_intersection(&b1);
+            union == a1.union(&b1) && intersection == a1.intersection(&b1)
+        }
+
+        fn union3(a: Reference, b: Reference, c: Reference) -> bool {
+            let a1: Test = a.iter().cloned().collect();
+            let b1: Test = b.iter().cloned().collect();
+            let c1: Test = c.iter().cloned().collect();
+            let expected: Reference = a.iter().chain(&b).chain(&c).cloned().collect();
+            a1.union3(&b1, &c1).as_slice().iter().eq(expected.iter())
+        }
+
+        fn merge_strategies(a: Test, b: Test) -> bool {
+            use MergeStrategy::*;
+            [Binary, Adaptive, Galloping, Linear, Iterative].iter().all(|&s| {
+                a.union_with_strategy(&b, s) == a.union(&b)
+                    && a.intersection_with_strategy(&b, s) == a.intersection(&b)
+                    && a.difference_with_strategy(&b, s) == a.difference(&b)
+                    && a.symmetric_difference_with_strategy(&b, s) == a.symmetric_difference(&b)
+            })
+        }
+
+        fn union_copy(a: Test, b: Test) -> bool {
+            let r = a.union_copy(&b);
+            r == a.union(&b) && r.0.capacity() >= r.len()
+        }
+
+        fn set_merge_op_callbacks(a: Test, b: Test) -> bool {
+            Callbacks(SetUnionOp).apply(&a, &b) == a.union(&b)
+                && Callbacks(SetIntersectionOp).apply(&a, &b) == a.intersection(&b)
+                && Callbacks(SetDiffOp).apply(&a, &b) == a.difference(&b)
+                && Callbacks(SetXorOp).apply(&a, &b) == a.symmetric_difference(&b)
+        }
+
+        fn diff_consistent(a: Reference, b: Reference) -> bool {
+            let a1: Test = a.iter().cloned().collect();
+            let b1: Test = b.iter().cloned().collect();
+            let (removed, added) = a1.diff(&b1);
+            removed == a1.difference(&b1) && added == b1.difference(&a1)
+                && removed.union(&added) == a1.symmetric_difference(&b1)
+        }
+
+        fn ord_consistent(a: Reference, b: Reference) -> bool {
+            let a1: Test = a.iter().cloned().collect();
+            let b1: Test = b.iter().cloned().collect();
+            a1.cmp(&b1) == a.cmp(&b) && a1.partial_cmp(&b1) == a.partial_cmp(&b) && (a1 == b1) == (a == b)
+        }
+
+        fn eq_hash_insertion_order(a: Vec<i64>) -> bool {
+            let forward: Test = a.iter().cloned().collect();
+            let mut backward = Test::empty();
+            for x in a.iter().rev() {
+                backward.insert(*x);
+            }
+            forward == backward && forward.cmp(&backward) == Ordering::Equal && hash(&forward) == hash(&backward)
+        }
+
+        fn split_at_value(a: Reference, pivot: i64) -> bool {
+            let (lo, hi) = Test::from_iter(a.iter().cloned()).split_at_value(&pivot);
+            let expected_hi = a.range(pivot..).cloned().collect::<Vec<_>>();
+            let expected_lo = a.range(..pivot).cloned().collect::<Vec<_>>();
+            lo.as_slice() == expected_lo.as_slice() && hi.as_slice() == expected_hi.as_slice()
+        }
+
+        fn range(a: Reference, lo: i64, hi: i64) -> bool {
+            let a1: Test = a.iter().cloned().collect();
+            let expected: Vec<i64> = if lo < hi { a.range(lo..hi).cloned().collect() } else { Vec::new() };
+            a1.range(&lo, &hi) == expected.as_slice()
+        }
+
+        fn search_indices(a: Reference, x: i64, lo: i64, hi: i64) -> bool {
+            let a1: Test = a.iter().cloned().collect();
+            let slice = a1.as_slice();
+            let found = match a1.binary_search(&x) {
+                Ok(i) => slice[i] == x,
+                Err(i) => !a.contains(&x) && i == a1.rank(&x),
+            };
+            found && a1.position(&x) == slice.iter().position(|y| *y == x)
+                && &slice[a1.range_indices(&lo, &hi)] == a1.range(&lo, &hi)
+        }
+
+        fn resumable_merge_chunks(a: Test, b: Test, n: u8) -> bool {
+            let n = n as usize % 10 + 1;
+            let (union, ul) = resume(&a, &b, SetUnionOp, n);
+            let (intersection, il) = resume(&a, &b, SetIntersectionOp, n);
+            let (difference, dl) = resume(&a, &b, SetDiffOp, n);
+            let (xor, xl) = resume(&a, &b, SetXorOp, n);
+            union == a.union(&b)
+                && intersection == a.intersection(&b)
+                && difference == a.difference(&b)
+                && xor == a.symmetric_difference(&b)
+                && [ul, il, dl, xl].iter().all(|l| bounded(l, n))
+        }
+
+        fn try_from_sorted(a: Vec<i64>) -> bool {
+            let mut sorted = a.clone();
+            sorted.sort();
+            sorted.dedup();
+            match Test::try_from_sorted(a.clone()) {
+                Ok(set) => a == sorted && set.as_slice() == a.as_slice(),
+                Err(NotSortedError { index }) => index > 0 && a[index - 1] >= a[index] && a[..index].windows(2).all(|w| w[0] < w[1]),
+            }
+        }
+
+        fn union_take(a: Test, b: Test, k: usize) -> bool {
+            let k = k % 32;
+            let r = a.union_take(&b, k);
+            let union = a.union(&b);
+            r.len() == k.min(union.len()) && r.as_slice() == &union.as_slice()[..r.len()]
+        }
+
+        fn rank_nth(a: Reference, x: i64) -> bool {
+            let a1: Test = a.iter().cloned().collect();
+            let rank = a1.rank(&x);
+            rank == a.range(..x).count()
+                && a1.nth(rank) == a.range(x..).next()
+                && a1.first() == a.iter().next()
+                && a1.last() == a.iter().next_back()
+        }
+
+        fn complement_within(a: Test, universe: Test) -> bool {
+            let expected: Vec<i64> = universe.iter().filter(|x| !a.contains(x)).cloned().collect();
+            let complement = a.complement_within(&universe);
+            complement.as_slice() == expected.as_slice() && complement.is_disjoint(&a)
+        }
+
+        fn union_cow(a: Test, b: Test) -> bool {
+            *a.union_cow(&b) == a.union(&b)
+        }
+
+        fn jaccard(a: Test, b: Test) -> bool {
+            let union = a.union(&b).len();
+            let expected = if union == 0 { 1.0 } else { a.intersection(&b).len() as f64 / union as f64 };
+            a.jaccard(&b) == expected
+        }
+
+        fn union_count_all(sets: Vec<Reference>) -> bool {
+            let sets1: Vec<Test> = sets.iter().map(|x| x.iter().cloned().collect()).collect();
+            let expected = sets.into_iter().flatten().collect::<Reference>().len();
+            VecSet::union_count_all(&sets1) == expected
+        }
+
+        fn union_all(sets: Vec<Reference>) -> bool {
+            let sets1: Vec<Test> = sets.iter().map(|x| x.iter().cloned().collect()).collect();
+            let expected: Vec<i64> = sets.into_iter().flatten().collect::<Reference>().into_iter().collect();
+            VecSet::union_all(sets1).as_slice() == expected.as_slice()
+        }
+    }
+
+    fn hash<T: Hash>(value: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn sets_as_keys() {
+        let mut map = BTreeMap::new();
+        map.insert(Test::from(vec![2, 1]), "a");
+        map.insert(Test::from(vec![1]), "b");
+        map.insert(Test::from(vec![1, 2]), "c");
+        map.insert(Test::from(vec![]), "d");
+        let entries: Vec<(Vec<i64>, &str)> = map.into_iter().map(|(k, v)| (k.into(), v)).collect();
+        assert_eq!(
+            entries,
+            vec![(vec![], "d"), (vec![1], "b"), (vec![1, 2], "c")]
+        );
     }
 
     bitop_assign_consistent!(Test);
@@ -617,11 +2327,895 @@ mod test {
         v.shrink_to_fit();
         println!("{:?} {}", v, v.capacity());
     }
+
+    #[test]
+    fn custom_cmp_merge() {
+        let a: VecSet<(u32, String)> =
+            vec![(1, "a1".into()), (3, "a3".into()), (5, "a5".into())].into();
+        let b: VecSet<(u32, String)> =
+            vec![(2, "b2".into()), (3, "b3".into()), (6, "b6".into())].into();
+        let union = a.union_by_key(&b, |x| x.0);
+        let keys: Vec<u32> = union.iter().map(|x| x.0).collect();
+        assert_eq!(keys, vec![1, 2, 3, 5, 6]);
+        // on collision the union takes the element from a
+        assert_eq!(union.as_slice()[2].1, "a3");
+        // the plain union compares the strings as well, so there is no collision
+        assert_eq!(a.union(&b).len(), 6);
+        assert_eq!(
+            a.intersection_by_key(&b, |x| x.0).as_slice(),
+            &[(3, "a3".to_string())]
+        );
+        let difference: Vec<u32> = a
+            .difference_by_key(&b, |x| x.0)
+            .iter()
+            .map(|x| x.0)
+            .collect();
+        assert_eq!(difference, vec![1, 5]);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_roundtrip() {
+        let a: Test = vec![3, 1, 2].into();
+        let json = serde_json::to_string(&a).unwrap();
+        assert_eq!(json, "[1,2,3]");
+        let b: Test = serde_json::from_str(&json).unwrap();
+        assert_eq!(a, b);
+        let empty: Test = serde_json::from_str("[]").unwrap();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_unsorted() {
+        // unsorted input with duplicates gets sorted and deduplicated
+        let a: Test = serde_json::from_str("[5,3,5,1,3]").unwrap();
+        let v: Vec<i64> = a.into();
+        assert_eq!(v, vec![1, 3, 5]);
+    }
+
+    #[test]
+    fn extend_cases() {
+        let mut a: Test = vec![1, 3, 5].into();
+        a.extend(Vec::<i64>::new());
+        assert_eq!(a.as_slice(), &[1, 3, 5]);
+        a.extend(vec![4]);
+        assert_eq!(a.as_slice(), &[1, 3, 4, 5]);
+        a.extend(vec![6, 0, 3, 6, 2]);
+        assert_eq!(a.as_slice(), &[0, 1, 2, 3, 4, 5, 6]);
+        a.extend(&[7, 1]);
+        assert_eq!(a.as_slice(), &[0, 1, 2, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn union_with_provenance_cases() {
+        use Provenance::*;
+        let a: Test = vec![1, 2, 3, 7].into();
+        let b: Test = vec![3, 4, 7, 8].into();
+        let (r, p) = a.union_with_provenance(&b);
+        assert_eq!(r.as_slice(), &[1, 2, 3, 4, 7, 8]);
+        assert_eq!(p, vec![A, A, Both, B, Both, B]);
+    }
+
+    #[test]
+    fn retain_cases() {
+        let mut a: Test = (0..100).collect();
+        let capacity = a.capacity();
+        a.retain(|x| x % 10 == 3);
+        assert_eq!(a.as_slice(), &[3, 13, 23, 33, 43, 53, 63, 73, 83, 93]);
+        assert_eq!(a.capacity(), capacity);
+        a.retain(|_| false);
+        assert!(a.is_empty());
+        assert_eq!(a.capacity(), capacity);
+    }
+
+    #[test]
+    fn drain_filter_cases() {
+        let mut a: Test = (0..10).collect();
+        let ptr = a.as_slice().as_ptr();
+        let even = a.drain_filter(|x| x % 2 == 0);
+        assert_eq!(even, vec![0, 2, 4, 6, 8]);
+        assert_eq!(a.as_slice(), &[1, 3, 5, 7, 9]);
+        // the allocation is kept
+        assert_eq!(a.as_slice().as_ptr(), ptr);
+        assert!(a.drain_filter(|_| false).is_empty());
+        assert_eq!(a.drain_filter(|_| true), vec![1, 3, 5, 7, 9]);
+        assert!(a.is_empty());
+    }
+
+    #[test]
+    fn merge_dedup_cases() {
+        assert_eq!(merge_dedup(&[1, 1, 2], &[2, 2, 3]), vec![1, 2, 3]);
+        assert_eq!(merge_dedup(&[1, 1, 1, 1], &[]), vec![1]);
+        assert_eq!(merge_dedup(&[], &[5, 5, 6, 6, 6]), vec![5, 6]);
+        let a: Vec<i64> = (0..1000).map(|x| x / 10).collect();
+        let b: Vec<i64> = (0..1000).map(|x| x / 7 + 50).collect();
+        let expected: Vec<i64> = (0..193).collect();
+        assert_eq!(merge_dedup(&a, &b), expected);
+        assert_eq!(merge_dedup(&b, &a), expected);
+        let intersection = DedupMergeState::merge_shortcut(&a, &b, SetIntersectionOp);
+        assert_eq!(intersection, (50..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn merge_append_csr() {
+        let mut flat = vec![100];
+        let r1 = merge_append(&[1, 3, 5], &[2, 3], &mut flat);
+        let r2 = merge_append(&[], &[], &mut flat);
+        let r3 = merge_append(&[0], &[7, 9], &mut flat);
+        assert_eq!((r1.clone(), r2.clone(), r3.clone()), (1..5, 5..5, 5..8));
+        // the existing content is kept
+        assert_eq!(flat, vec![100, 1, 2, 3, 5, 0, 7, 9]);
+        assert_eq!(&flat[r1], &[1, 2, 3, 5]);
+        assert!(flat[r2].is_empty());
+        assert_eq!(&flat[r3], &[0, 7, 9]);
+    }
+
+    #[test]
+    fn merge_into_reuse() {
+        let mut out: Vec<i64> = Vec::with_capacity(8);
+        let ptr = out.as_ptr();
+        merge_into(&[1, 3, 5], &[2, 3, 6], &mut out);
+        assert_eq!(out, vec![1, 2, 3, 5, 6]);
+        merge_into(&[4], &[], &mut out);
+        assert_eq!(out, vec![4]);
+        merge_into(&[], &[], &mut out);
+        assert!(out.is_empty());
+        merge_into(&[1, 2, 3, 4], &[5, 6, 7, 8], &mut out);
+        assert_eq!(out, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        // enough capacity, so no reallocation
+        assert_eq!(out.as_ptr(), ptr);
+    }
+
+    #[test]
+    fn merge_checked_sorted() {
+        let mut out = Vec::new();
+        merge_checked(&[1, 3, 5], &[2, 3, 6], &mut out);
+        assert_eq!(out, vec![1, 2, 3, 5, 6]);
+        merge_checked(&[], &[], &mut out);
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "first unsorted index in a: Some(2), in b: None")]
+    fn merge_checked_unsorted() {
+        let mut out = Vec::new();
+        merge_checked(&[1, 5, 3], &[2, 4], &mut out);
+    }
+
+    #[test]
+    fn with_slice_cases() {
+        let a: Test = vec![1, 2, 3, 5].into();
+        let b = [2, 2, 4, 5, 5, 5];
+        assert_eq!(a.union_with_slice(&b).as_slice(), &[1, 2, 3, 4, 5]);
+        assert_eq!(a.intersection_with_slice(&b).as_slice(), &[2, 5]);
+        assert_eq!(a.difference_with_slice(&b).as_slice(), &[1, 3]);
+        assert_eq!(a.union_with_slice(&[]), a);
+        assert!(a.intersection_with_slice(&[]).is_empty());
+        assert_eq!(Test::empty().union_with_slice(&b).as_slice(), &[2, 4, 5]);
+    }
+
+    #[test]
+    fn merge_indices_cases() {
+        use MergeIndex::*;
+        let a = ["a", "c", "d"];
+        let b = ["b", "c", "e"];
+        assert_eq!(
+            merge_indices(&a, &b),
+            vec![A(0), B(0), Both(1, 1), A(2), B(2)]
+        );
+        assert_eq!(merge_indices(&a, &[]), vec![A(0), A(1), A(2)]);
+        assert_eq!(merge_indices::<&str>(&[], &[]), vec![]);
+    }
+
+    #[test]
+    fn merge_tie_break_cases() {
+        // (key, version), sorted by key
+        let a = [(1, 5), (2, 1), (4, 3), (6, 2)];
+        let b = [(2, 7), (3, 1), (4, 2), (6, 2)];
+        let by_key = |x: &(i32, i32), y: &(i32, i32)| x.0.cmp(&y.0);
+        // keep the higher version, both if the versions are the same
+        let r = merge_tie_break(&a, &b, by_key, |x, y| y.1.cmp(&x.1));
+        assert_eq!(r, vec![(1, 5), (2, 7), (3, 1), (4, 3), (6, 2), (6, 2)]);
+        // keep the lower version
+        let r = merge_tie_break(&a, &b, by_key, |x, y| x.1.cmp(&y.1));
+        assert_eq!(r, vec![(1, 5), (2, 1), (3, 1), (4, 2), (6, 2), (6, 2)]);
+        // always keep a
+        let r = merge_tie_break(&a, &b, by_key, |_, _| Ordering::Less);
+        assert_eq!(r, vec![(1, 5), (2, 1), (3, 1), (4, 3), (6, 2)]);
+        assert!(merge_tie_break(&[], &[], by_key, |_, _| Ordering::Less).is_empty());
+    }
+
+    #[test]
+    fn contains_cursor_cases() {
+        let a: Test = vec![1, 3, 5, 7, 9].into();
+        let mut cursor = a.contains_cursor();
+        assert!(cursor.contains(&3));
+        assert_eq!(cursor.position(), 1);
+        assert!(!cursor.contains(&4));
+        assert_eq!(cursor.position(), 2);
+        assert!(cursor.contains(&9));
+        assert_eq!(cursor.position(), 4);
+        assert!(!cursor.contains(&10));
+        assert_eq!(cursor.position(), 5);
+        // going backwards
+        assert!(cursor.contains(&1));
+        assert!(!cursor.contains(&0));
+        assert_eq!(cursor.position(), 0);
+        // hints out of range
+        assert!(a.contains_from_hint(&9, 100));
+        assert!(!a.contains_from_hint(&2, 100));
+        assert!(!Test::empty().contains_from_hint(&1, 0));
+    }
+
+    /// a key with a tag that is ignored by the comparison
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    struct Tagged(u32, char);
+
+    impl PartialOrd for Tagged {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Tagged {
+        fn cmp(&self, other: &Self) -> Ordering {
+            self.0.cmp(&other.0)
+        }
+    }
+
+    fn tags<'a>(elements: impl IntoIterator<Item = &'a Tagged>) -> Vec<(u32, char)> {
+        elements.into_iter().map(|x| (x.0, x.1)).collect()
+    }
+
+    #[test]
+    fn constructors() {
+        let a = Test::new();
+        assert!(a.is_empty());
+        assert_eq!(a.len(), 0);
+        assert_eq!(a, Test::default());
+        assert_eq!(a, Test::empty());
+        let mut a = Test::with_capacity(100);
+        assert!(a.is_empty());
+        assert!(a.capacity() >= 100);
+        let ptr = a.as_slice().as_ptr();
+        a.insert(3);
+        a.insert(1);
+        assert_eq!(a.len(), 2);
+        assert!(!a.is_empty());
+        a.clear();
+        assert!(a.is_empty());
+        assert!(a.capacity() >= 100);
+        // the allocation is kept
+        a.insert(2);
+        assert_eq!(a.as_slice().as_ptr(), ptr);
+    }
+
+    #[test]
+    fn relation_cases() {
+        use SetRelation::*;
+        let set = |x: &[i64]| -> Test { x.to_vec().into() };
+        assert_eq!(set(&[1, 2]).relation(&set(&[1, 2])), Equal);
+        assert_eq!(set(&[]).relation(&set(&[])), Equal);
+        assert_eq!(set(&[1]).relation(&set(&[1, 2])), Subset);
+        assert_eq!(set(&[]).relation(&set(&[1])), Subset);
+        assert_eq!(set(&[1, 2]).relation(&set(&[2])), Superset);
+        assert_eq!(set(&[1, 2]).relation(&set(&[2, 3])), Overlapping);
+        assert_eq!(set(&[1, 3]).relation(&set(&[2, 4])), Disjoint);
+        // stops early, but still overlapping
+        let large: Test = (0..1000).collect();
+        assert_eq!(set(&[-1, 0]).relation(&large), Overlapping);
+    }
+
+    #[test]
+    fn insert_remove_cases() {
+        let mut a: Test = vec![1, 3].into();
+        assert!(a.insert(2));
+        // duplicate
+        assert!(!a.insert(2));
+        assert!(a.insert(0));
+        assert!(a.insert(4));
+        assert_eq!(a.as_slice(), &[0, 1, 2, 3, 4]);
+        assert!(a.remove(&2));
+        // absent
+        assert!(!a.remove(&2));
+        assert!(!a.remove(&5));
+        assert_eq!(a.as_slice(), &[0, 1, 3, 4]);
+        let mut e = Test::empty();
+        assert!(!e.remove(&1));
+    }
+
+    #[test]
+    fn union_and_intersection_cases() {
+        let a: VecSet<Tagged> = vec![Tagged(1, 'a'), Tagged(2, 'a'), Tagged(4, 'a')].into();
+        let b: VecSet<Tagged> = vec![Tagged(2, 'b'), Tagged(3, 'b'), Tagged(4, 'b')].into();
+        let (union, intersection) = a.union_and_intersection(&b);
+        // like the separate operations, collisions keep the element of self
+        assert_eq!(tags(&union), vec![(1, 'a'), (2, 'a'), (3, 'b'), (4, 'a')]);
+        assert_eq!(tags(&intersection), vec![(2, 'a'), (4, 'a')]);
+        assert_eq!(tags(&union), tags(&a.union(&b)));
+        assert_eq!(tags(&intersection), tags(&a.intersection(&b)));
+        let (union, intersection) = a.union_and_intersection(&VecSet::empty());
+        assert_eq!(union, a);
+        assert!(intersection.is_empty());
+    }
+
+    #[test]
+    fn union3_cases() {
+        let a: VecSet<Tagged> = vec![Tagged(1, 'a'), Tagged(2, 'a'), Tagged(4, 'a')].into();
+        let b: VecSet<Tagged> = vec![Tagged(2, 'b'), Tagged(3, 'b'), Tagged(5, 'b')].into();
+        let c: VecSet<Tagged> = vec![Tagged(3, 'c'), Tagged(4, 'c'), Tagged(5, 'c')].into();
+        // collisions keep the element of self, then the element of b
+        assert_eq!(
+            tags(&a.union3(&b, &c)),
+            vec![(1, 'a'), (2, 'a'), (3, 'b'), (4, 'a'), (5, 'b')]
+        );
+        assert_eq!(tags(&a.union3(&b, &c)), tags(&a.union(&b).union(&c)));
+        assert_eq!(a.union3(&VecSet::empty(), &VecSet::empty()), a);
+        assert_eq!(VecSet::empty().union3(&VecSet::empty(), &c), c);
+    }
+
+    /// the operations a caller might choose from at runtime
+    enum Choice {
+        Union,
+        Intersection,
+        Difference,
+        Xor,
+    }
+
+    fn choose(choice: Choice) -> Box<dyn SetMergeOp<i64>> {
+        match choice {
+            Choice::Union => Box::new(SetUnionOp),
+            Choice::Intersection => Box::new(SetIntersectionOp),
+            Choice::Difference => Box::new(SetDiffOp),
+            Choice::Xor => Box::new(SetXorOp),
+        }
+    }
+
+    #[test]
+    fn boxed_set_merge_op() {
+        let a: Test = vec![1, 2, 3, 5].into();
+        let b: Test = vec![2, 3, 4].into();
+        let apply = |choice| choose(choice).apply(&a, &b).into_vec();
+        assert_eq!(apply(Choice::Union), vec![1, 2, 3, 4, 5]);
+        assert_eq!(apply(Choice::Intersection), vec![2, 3]);
+        assert_eq!(apply(Choice::Difference), vec![1, 5]);
+        assert_eq!(apply(Choice::Xor), vec![1, 4, 5]);
+        // a pipeline of operations chosen at runtime
+        let pipeline: Vec<(Box<dyn SetMergeOp<i64>>, Test)> = vec![
+            (choose(Choice::Union), b.clone()),
+            (choose(Choice::Difference), vec![1, 2].into()),
+        ];
+        let r = pipeline
+            .iter()
+            .fold(a.clone(), |acc, (op, x)| op.apply(&acc, x));
+        assert_eq!(r.as_slice(), &[3, 4, 5]);
+    }
+
+    /// a union that keeps the element of b on collisions, defined only by its callbacks
+    struct UnionTakeB;
+
+    impl<T: Clone> SetMergeOp<T> for UnionTakeB {
+        fn from_a(&self, m: &mut SetMergeState<'_, T>, n: usize) {
+            m.take_a(n)
+        }
+        fn from_b(&self, m: &mut SetMergeState<'_, T>, n: usize) {
+            m.take_b(n)
+        }
+        fn collision(&self, m: &mut SetMergeState<'_, T>) {
+            m.skip_a(1);
+            m.take_b(1)
+        }
+    }
+
+    /// forwards the callbacks, but uses the provided apply instead of the fast path of the built-in ops
+    struct Callbacks<O>(O);
+
+    impl<T, O: SetMergeOp<T>> SetMergeOp<T> for Callbacks<O> {
+        fn from_a(&self, m: &mut SetMergeState<'_, T>, n: usize) {
+            self.0.from_a(m, n)
+        }
+        fn from_b(&self, m: &mut SetMergeState<'_, T>, n: usize) {
+            self.0.from_b(m, n)
+        }
+        fn collision(&self, m: &mut SetMergeState<'_, T>) {
+            self.0.collision(m)
+        }
+    }
+
+    #[test]
+    fn user_defined_set_merge_op() {
+        let a: VecSet<Tagged> = vec![Tagged(1, 'a'), Tagged(2, 'a'), Tagged(4, 'a')].into();
+        let b: VecSet<Tagged> = vec![Tagged(2, 'b'), Tagged(3, 'b'), Tagged(4, 'b')].into();
+        let op: Box<dyn SetMergeOp<Tagged>> = Box::new(UnionTakeB);
+        let r = op.apply(&a, &b);
+        assert_eq!(tags(&r), vec![(1, 'a'), (2, 'b'), (3, 'b'), (4, 'b')]);
+        assert_eq!(r, a.union_with_policy(&b, CollisionPolicy::TakeB));
+    }
+
+    /// a broken operation that keeps both elements on collision
+    struct UnionTakeBoth;
+
+    impl<T: Clone> SetMergeOp<T> for UnionTakeBoth {
+        fn from_a(&self, m: &mut SetMergeState<'_, T>, n: usize) {
+            m.take_a(n)
+        }
+        fn from_b(&self, m: &mut SetMergeState<'_, T>, n: usize) {
+            m.take_b(n)
+        }
+        fn collision(&self, m: &mut SetMergeState<'_, T>) {
+            m.take_a(1);
+            m.take_b(1)
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic]
+    fn set_merge_op_unsorted_result() {
+        let a: Test = vec![1, 2].into();
+        let b: Test = vec![2, 3].into();
+        UnionTakeBoth.apply(&a, &b);
+    }
+
+    #[test]
+    fn resumable_merge_cases() {
+        let a: Test = (0..100).collect();
+        let b: Test = (50..150).collect();
+        let mut merge = ResumableMerge::new(&a, &b, SetUnionOp);
+        assert!(!merge.is_done());
+        // no budget, no progress
+        assert!(merge.step(0).is_pending());
+        assert!(merge.result().is_empty());
+        assert!(merge.step(10).is_pending());
+        assert_eq!(merge.result(), &(0..10).collect::<Vec<_>>()[..]);
+        assert!(merge.step(1000).is_ready());
+        assert!(merge.is_done());
+        assert_eq!(merge.into_result(), a.union(&b));
+        // skipped elements do not use up the budget
+        let mut merge = ResumableMerge::new(&a, &b, SetIntersectionOp);
+        assert!(merge.step(10).is_pending());
+        assert_eq!(merge.result(), &(50..60).collect::<Vec<_>>()[..]);
+        let mut merge = ResumableMerge::new(&b, &a, SetDiffOp);
+        assert!(merge.step(10).is_pending());
+        assert_eq!(merge.result(), &(100..110).collect::<Vec<_>>()[..]);
+        // resuming in chunks of any size gives the same result
+        for n in [1, 7, 64, 1000] {
+            let (r, lengths) = resume(&a, &b, SetIntersectionOp, n);
+            assert_eq!(r, a.intersection(&b));
+            assert!(bounded(&lengths, n));
+        }
+        // any operation defined by its callbacks can be resumed
+        let (r, lengths) = resume(&a, &b, UnionTakeB, 7);
+        assert_eq!(r, UnionTakeB.apply(&a, &b));
+        assert!(bounded(&lengths, 7));
+        let empty = Test::empty();
+        assert!(ResumableMerge::new(&empty, &empty, SetXorOp)
+            .step(0)
+            .is_ready());
+    }
+
+    #[test]
+    fn diff_cases() {
+        let set = |x: &[i64]| -> Test { x.to_vec().into() };
+        let (removed, added) = set(&[1, 2, 3, 5]).diff(&set(&[2, 3, 4, 6]));
+        assert_eq!(removed.as_slice(), &[1, 5]);
+        assert_eq!(added.as_slice(), &[4, 6]);
+        let (removed, added) = set(&[1, 2]).diff(&set(&[1, 2]));
+        assert!(removed.is_empty() && added.is_empty());
+        let (removed, added) = set(&[]).diff(&set(&[1, 2]));
+        assert!(removed.is_empty());
+        assert_eq!(added.as_slice(), &[1, 2]);
+    }
+
+    #[test]
+    fn union_capacity() {
+        let a: Test = (0..1000).collect();
+        let b: Test = (1000..2000).collect();
+        // the allocation for the worst case is used up by disjoint sets
+        assert_eq!(a.union(&b).capacity(), 2000);
+        assert_eq!(a.union_copy(&b).capacity(), 2000);
+        // but identical sets would leave half of it unused
+        assert!(a.union(&a).capacity() < 1250);
+        assert!(a.union_copy(&a).capacity() < 1250);
+        let c: Test = (100..1100).collect();
+        assert!(a.union(&c).capacity() < 1250);
+    }
+
+    #[test]
+    fn into_vec_and_shrink() {
+        let a: Test = vec![5, 1, 3, 1, 5].into();
+        assert_eq!(a.as_slice(), &[1, 3, 5]);
+        let ptr = a.as_slice().as_ptr();
+        let v = a.into_vec();
+        assert_eq!(v, vec![1, 3, 5]);
+        // no copy
+        assert_eq!(v.as_ptr(), ptr);
+        let mut a: Test = (0..1000).collect();
+        a.intersection_with(&(0..10).collect());
+        assert!(a.capacity() >= 1000);
+        a.shrink_to_fit();
+        assert!(a.capacity() < 1000);
+        assert_eq!(a.into_vec(), (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn collision_policy() {
+        let a: VecSet<Tagged> = vec![Tagged(1, 'a'), Tagged(2, 'a'), Tagged(4, 'a')].into();
+        let b: VecSet<Tagged> = vec![Tagged(2, 'b'), Tagged(3, 'b'), Tagged(4, 'b')].into();
+        let union = |p| tags(&a.union_with_policy(&b, p));
+        let intersection = |p| tags(&a.intersection_with_policy(&b, p));
+        use CollisionPolicy::*;
+        assert_eq!(union(TakeA), vec![(1, 'a'), (2, 'a'), (3, 'b'), (4, 'a')]);
+        assert_eq!(union(TakeB), vec![(1, 'a'), (2, 'b'), (3, 'b'), (4, 'b')]);
+        assert_eq!(
+            tags(&a.union_keep_both(&b)),
+            vec![(1, 'a'), (2, 'a'), (2, 'b'), (3, 'b'), (4, 'a'), (4, 'b')]
+        );
+        assert_eq!(intersection(TakeA), vec![(2, 'a'), (4, 'a')]);
+        assert_eq!(intersection(TakeB), vec![(2, 'b'), (4, 'b')]);
+        assert_eq!(
+            tags(&a.intersection_keep_both(&b)),
+            vec![(2, 'a'), (2, 'b'), (4, 'a'), (4, 'b')]
+        );
+        // the plain operations keep the element of self
+        assert_eq!(tags(&a.union(&b)), union(TakeA));
+        assert_eq!(tags(&a.intersection(&b)), intersection(TakeA));
+    }
+
+    #[test]
+    fn in_place_ops_changed() {
+        let mut a: Test = vec![1, 2, 3].into();
+        let subset: Test = vec![1, 3].into();
+        let disjoint: Test = vec![4, 5].into();
+        assert!(!a.union_with(&subset));
+        assert!(!a.union_with(&Test::empty()));
+        assert!(a.union_with(&vec![3, 4].into()));
+        assert_eq!(a.as_slice(), &[1, 2, 3, 4]);
+        assert!(!a.union_with(&a.clone()));
+        assert!(!a.difference_with(&vec![0, 5].into()));
+        assert!(a.difference_with(&disjoint));
+        assert_eq!(a.as_slice(), &[1, 2, 3]);
+        assert!(!a.intersection_with(&vec![0, 1, 2, 3].into()));
+        assert!(a.intersection_with(&subset));
+        assert_eq!(a, subset);
+        assert!(!a.symmetric_difference_with(&Test::empty()));
+        assert!(a.symmetric_difference_with(&subset));
+        assert!(a.is_empty());
+        // iterate until nothing changes
+        let mut reachable = Test::singleton(1);
+        while reachable.union_with(&reachable.iter().map(|x| (x * 2) % 7).collect()) {}
+        assert_eq!(reachable.as_slice(), &[1, 2, 4]);
+    }
+
+    #[test]
+    fn union_with_observer_cases() {
+        let mut a: VecSet<Tagged> = vec![Tagged(1, 'a'), Tagged(2, 'a'), Tagged(4, 'a')].into();
+        let b: VecSet<Tagged> = vec![Tagged(2, 'b'), Tagged(3, 'b'), Tagged(4, 'b')].into();
+        let mut collisions = Vec::new();
+        assert!(a.union_with_observer(&b, |x, y| collisions.push((*x, *y))));
+        // the observer sees both elements, and the element of self is kept
+        assert_eq!(
+            collisions,
+            vec![
+                (Tagged(2, 'a'), Tagged(2, 'b')),
+                (Tagged(4, 'a'), Tagged(4, 'b'))
+            ]
+        );
+        assert_eq!(tags(&a), vec![(1, 'a'), (2, 'a'), (3, 'b'), (4, 'a')]);
+        let mut count = 0;
+        assert!(!a.union_with_observer(&b, |_, _| count += 1));
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn union_with_in_range_cases() {
+        let mut a: Test = vec![1, 5, 9, 20].into();
+        let b: Test = vec![0, 2, 5, 6, 10, 20, 21].into();
+        assert!(a.union_with_in_range(&b, &2, &10));
+        // 0, 10, 20 and 21 are outside the window, 1 and 20 are preserved
+        assert_eq!(a.as_slice(), &[1, 2, 5, 6, 9, 20]);
+        assert!(!a.union_with_in_range(&b, &2, &10));
+        assert!(!a.union_with_in_range(&b, &7, &10));
+        assert!(!a.union_with_in_range(&b, &10, &2));
+        assert!(a.union_with_in_range(&b, &i64::MIN, &i64::MAX));
+        assert_eq!(a, a.union(&b));
+    }
+
+    #[test]
+    fn operators_cases() {
+        let a: Test = vec![1, 2, 3].into();
+        let b: Test = vec![3, 4].into();
+        assert_eq!(&a | &b, a.union(&b));
+        assert_eq!((&a | &b).as_slice(), &[1, 2, 3, 4]);
+        assert_eq!((a.clone() & b.clone()).as_slice(), &[3]);
+        assert_eq!((a.clone() - b.clone()).as_slice(), &[1, 2]);
+        assert_eq!((a ^ b).as_slice(), &[1, 2, 4]);
+    }
+
+    #[test]
+    fn try_from_sorted_cases() {
+        let a = Test::try_from_sorted(vec![1, 2, 5]).unwrap();
+        assert_eq!(a.as_slice(), &[1, 2, 5]);
+        assert!(Test::try_from_sorted(vec![]).unwrap().is_empty());
+        assert!(Test::try_from_sorted(vec![7]).is_ok());
+        // unsorted at the front
+        assert_eq!(
+            Test::try_from_sorted(vec![3, 1, 2]),
+            Err(NotSortedError { index: 1 })
+        );
+        // duplicates
+        assert_eq!(
+            Test::try_from_sorted(vec![1, 2, 2, 3]),
+            Err(NotSortedError { index: 2 })
+        );
+        assert_eq!(
+            Test::try_from_sorted(vec![1, 2, 3, 0]),
+            Err(NotSortedError { index: 3 })
+        );
+        let b = Test::from_sorted_unchecked(vec![1, 2, 5]);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn split_at_value_cases() {
+        let a: Test = vec![2, 4, 6, 8].into();
+        let split = |pivot| {
+            let (lo, hi) = a.clone().split_at_value(&pivot);
+            (Vec::from(lo), Vec::from(hi))
+        };
+        // below, within, on an element and above
+        assert_eq!(split(0), (vec![], vec![2, 4, 6, 8]));
+        assert_eq!(split(5), (vec![2, 4], vec![6, 8]));
+        assert_eq!(split(6), (vec![2, 4], vec![6, 8]));
+        assert_eq!(split(9), (vec![2, 4, 6, 8], vec![]));
+    }
+
+    #[test]
+    fn search_cases() {
+        let a: Test = vec![2, 4, 6, 8].into();
+        // present, including the boundaries
+        assert_eq!(a.binary_search(&2), Ok(0));
+        assert_eq!(a.binary_search(&6), Ok(2));
+        assert_eq!(a.binary_search(&8), Ok(3));
+        assert_eq!(a.position(&8), Some(3));
+        // absent, before, between and after the elements
+        assert_eq!(a.binary_search(&1), Err(0));
+        assert_eq!(a.binary_search(&5), Err(2));
+        assert_eq!(a.binary_search(&9), Err(4));
+        assert_eq!(a.position(&5), None);
+        assert_eq!(Test::empty().binary_search(&1), Err(0));
+        assert_eq!(a.range_indices(&4, &8), 1..3);
+        assert_eq!(a.range_indices(&3, &9), 1..4);
+        assert_eq!(a.range_indices(&0, &100), 0..4);
+        assert_eq!(a.range_indices(&9, &100), 4..4);
+        assert!(a.range_indices(&8, &4).is_empty());
+    }
+
+    #[test]
+    fn range_cases() {
+        let a: Test = vec![2, 4, 6, 8].into();
+        assert!(a.range(&0, &1).is_empty());
+        assert_eq!(a.range(&0, &100), &[2, 4, 6, 8]);
+        assert_eq!(a.range(&4, &8), &[4, 6]);
+        assert_eq!(a.range(&3, &9), &[4, 6, 8]);
+        assert!(a.range(&9, &100).is_empty());
+        // an empty or inverted range gives an empty slice
+        assert!(a.range(&4, &4).is_empty());
+        assert!(a.range(&8, &2).is_empty());
+    }
+
+    #[test]
+    fn shares_at_least_cases() {
+        let a: Test = vec![1, 2, 3, 4, 5].into();
+        let b: Test = vec![2, 4, 6].into();
+        let empty = Test::empty();
+        assert!(a.shares_at_least(&b, 0));
+        assert!(empty.shares_at_least(&empty, 0));
+        assert!(a.shares_at_least(&b, 1));
+        assert!(!a.shares_at_least(&empty, 1));
+        assert!(a.shares_at_least(&b, 2));
+        assert!(!a.shares_at_least(&b, 3));
+        assert!(!a.shares_at_least(&b, 100));
+    }
+
+    #[test]
+    fn comparisons_cases() {
+        use MergeStrategy::*;
+        let large: Test = (0..100_000).collect();
+        let small: Test = (0..100).map(|x| x * 1000 + 500).collect();
+        let binary = small.union_comparisons(&large, Binary);
+        let linear = small.union_comparisons(&large, Linear);
+        // about m log(n/m) comparisons, while the linear scan compares everything up to the last element of small
+        assert!(binary < 100 * 20, "{} comparisons", binary);
+        assert!(linear > 99_000, "{} comparisons", linear);
+        // the smaller set is bisected, no matter which argument it is
+        assert_eq!(large.union_comparisons(&small, Binary), binary);
+        // closely interleaved sets need up to two comparisons per element, and the sizes are similar enough
+        // for the adaptive merge to switch to the linear scan right away
+        let evens: Test = (0..1000).map(|x| x * 2).collect();
+        let odds: Test = (0..1000).map(|x| x * 2 + 1).collect();
+        let linear = evens.intersection_comparisons(&odds, Linear);
+        assert!(linear <= 2 * 2000, "{} comparisons", linear);
+        assert_eq!(evens.intersection_comparisons(&odds, Adaptive), linear);
+        assert_eq!(
+            evens.symmetric_difference_comparisons(&odds, Linear),
+            linear
+        );
+        // identical sets need one comparison per element
+        assert_eq!(evens.difference_comparisons(&evens, Linear), 1000);
+    }
+
+    #[test]
+    fn shares_at_least_aborts_early() {
+        // the overlap is everything, so reaching the threshold should stop the merge right away
+        let a: Vec<i64> = (0..100_000).collect();
+        let comparisons = |k| {
+            let op = CountingMergeOperation::new(SetIntersectionOp);
+            let mut state = ThresholdMergeState::new(&a, &a, k);
+            op.merge(&mut state);
+            assert!(state.reached());
+            op.comparisons()
+        };
+        let c1 = comparisons(1);
+        let c10 = comparisons(10);
+        let all = comparisons(a.len());
+        assert!(c1 <= 20, "{} comparisons", c1);
+        assert!(c10 <= 200, "{} comparisons", c10);
+        assert!(all >= a.len());
+    }
+
+    #[test]
+    fn into_iter() {
+        use crate::SortedIterator;
+        let a: Test = vec![1, 3, 5].into();
+        let b: Test = vec![3, 4].into();
+        let union: Test = a.clone().into_iter().union(b.clone().into_iter()).collect();
+        assert_eq!(union, a.union(&b));
+        let intersection: Test = a.iter().intersection(b.iter()).cloned().collect();
+        assert_eq!(intersection, a.intersection(&b));
+        let mut elements = Vec::new();
+        for x in &a {
+            elements.push(*x);
+        }
+        for x in a {
+            elements.push(x);
+        }
+        assert_eq!(elements, vec![1, 3, 5, 1, 3, 5]);
+    }
+
+    #[test]
+    fn union_take_cases() {
+        let a: Test = vec![1, 3, 5, 7].into();
+        let b: Test = vec![2, 3, 4, 8].into();
+        assert!(a.union_take(&b, 0).is_empty());
+        assert_eq!(a.union_take(&b, 1).as_slice(), &[1]);
+        assert_eq!(a.union_take(&b, 4).as_slice(), &[1, 2, 3, 4]);
+        assert_eq!(a.union_take(&b, 7), a.union(&b));
+        assert_eq!(a.union_take(&b, 100), a.union(&b));
+        assert_eq!(a.union_take(&Test::empty(), 2).as_slice(), &[1, 3]);
+    }
+
+    #[test]
+    fn accessors() {
+        let empty = Test::empty();
+        assert_eq!(empty.first(), None);
+        assert_eq!(empty.last(), None);
+        assert_eq!(empty.nth(0), None);
+        assert_eq!(empty.rank(&0), 0);
+        let a: Test = vec![7, 3, 5].into();
+        assert_eq!(a.first(), Some(&3));
+        assert_eq!(a.last(), Some(&7));
+        assert_eq!(a.nth(1), Some(&5));
+        assert_eq!(a.nth(2), Some(&7));
+        assert_eq!(a.nth(3), None);
+        assert_eq!(a.rank(&0), 0);
+        assert_eq!(a.rank(&3), 0);
+        assert_eq!(a.rank(&4), 1);
+        assert_eq!(a.rank(&7), 2);
+        assert_eq!(a.rank(&100), 3);
+    }
+
+    #[test]
+    fn complement_within_cases() {
+        let universe: Test = (0..10).collect();
+        let a: Test = vec![1, 3, 5].into();
+        assert_eq!(
+            a.complement_within(&universe).as_slice(),
+            &[0, 2, 4, 6, 7, 8, 9]
+        );
+        // elements outside of the universe are ignored
+        let b: Test = vec![-5, 0, 9, 20].into();
+        assert_eq!(
+            b.complement_within(&universe).as_slice(),
+            &[1, 2, 3, 4, 5, 6, 7, 8]
+        );
+        assert_eq!(Test::empty().complement_within(&universe), universe);
+        assert!(universe.complement_within(&universe).is_empty());
+        assert!(a.complement_within(&Test::empty()).is_empty());
+    }
+
+    #[test]
+    fn union_cow_cases() {
+        let a: Test = vec![1, 2, 3, 4].into();
+        let b: Test = vec![2, 3].into();
+        let c: Test = vec![4, 5].into();
+        let empty = Test::empty();
+        let borrowed =
+            |x: &Cow<Test>, y: &Test| matches!(x, Cow::Borrowed(r) if std::ptr::eq(*r, y));
+        assert!(borrowed(&a.union_cow(&b), &a));
+        assert!(borrowed(&b.union_cow(&a), &a));
+        assert!(borrowed(&a.union_cow(&empty), &a));
+        assert!(borrowed(&empty.union_cow(&a), &a));
+        assert!(borrowed(&a.union_cow(&a), &a));
+        let r = a.union_cow(&c);
+        assert!(matches!(r, Cow::Owned(_)));
+        assert_eq!(r.as_slice(), &[1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn jaccard_cases() {
+        let a: Test = vec![1, 2, 3, 4].into();
+        let b: Test = vec![3, 4, 5, 6].into();
+        let c: Test = vec![7, 8].into();
+        let empty = Test::empty();
+        assert_eq!(a.jaccard(&a), 1.0);
+        assert_eq!(a.jaccard(&c), 0.0);
+        assert_eq!(a.jaccard(&b), 2.0 / 6.0);
+        assert_eq!(b.jaccard(&a), 2.0 / 6.0);
+        assert_eq!(a.jaccard(&empty), 0.0);
+        assert_eq!(empty.jaccard(&empty), 1.0);
+    }
+
+    #[test]
+    fn difference_count_cases() {
+        let a: Test = vec![1, 2, 3, 4].into();
+        let b: Test = vec![3, 4, 5].into();
+        let empty = Test::empty();
+        assert_eq!(a.difference_count(&b), 2);
+        assert_eq!(b.difference_count(&a), 1);
+        assert_eq!(a.symmetric_difference_count(&b), 3);
+        assert_eq!(a.difference_count(&a), 0);
+        assert_eq!(a.symmetric_difference_count(&a), 0);
+        assert_eq!(a.difference_count(&empty), 4);
+        assert_eq!(empty.symmetric_difference_count(&b), 3);
+    }
+
+    #[test]
+    fn union_count_all_cases() {
+        assert_eq!(VecSet::<i64>::union_count_all(&[]), 0);
+        let sets: Vec<Test> = (0..10).map(|i| vec![i, i + 1, 100].into()).collect();
+        assert_eq!(VecSet::union_count_all(&sets), 12);
+        assert_eq!(VecSet::union_count_all(&sets[..1]), 3);
+    }
+
+    #[test]
+    fn union_all_cases() {
+        assert!(VecSet::<i64>::union_all(vec![]).is_empty());
+        let one: Test = vec![1, 2].into();
+        assert_eq!(VecSet::union_all(vec![one.clone()]), one);
+        let sets: Vec<Test> = (0..5).map(|i| vec![i, i + 1, 10].into()).collect();
+        assert_eq!(VecSet::union_all(sets).as_slice(), &[0, 1, 2, 3, 4, 5, 10]);
+    }
+
+    #[test]
+    fn descending_merge() {
+        let a = vec![7, 5, 3, 1];
+        let b = vec![6, 5, 2];
+        let union: Vec<i32> =
+            VecMergeState::merge_shortcut(&a, &b, DescendingMergeOperation(SetUnionOp));
+        let intersection: Vec<i32> =
+            VecMergeState::merge_shortcut(&a, &b, DescendingMergeOperation(SetIntersectionOp));
+        assert_eq!(union, vec![7, 6, 5, 3, 2, 1]);
+        assert_eq!(intersection, vec![5]);
+    }
 }
 
 #[cfg(test)]
 mod test2 {
     use super::*;
+    use crate::merge_state::UnsafeSliceMergeState;
     use crate::obey::*;
     use num_traits::PrimInt;
     use quickcheck::*;
@@ -654,6 +3248,19 @@ mod test2 {
 
     quickcheck! {
 
+        fn inline4_ops(a: Reference, b: Reference) -> bool {
+            let a1: VecSet2<i64, [i64; 4]> = a.iter().cloned().collect();
+            let b1: VecSet2<i64, [i64; 4]> = b.iter().cloned().collect();
+            let mut union = a1.clone();
+            union |= b1.clone();
+            let union1: Vec<i64> = (&a1 | &b1).into();
+            let intersection: Vec<i64> = (&a1 & &b1).into();
+            let union: Vec<i64> = union.into();
+            union == a.union(&b).cloned().collect::<Vec<_>>()
+                && union1 == union
+                && intersection == a.intersection(&b).cloned().collect::<Vec<_>>()
+        }
+
         fn is_disjoint_sample(a: Test, b: Test) -> bool {
             binary_property_test(&a, &b, a.is_disjoint(&b), |a, b| !(a & b))
         }
@@ -745,6 +3352,14 @@ mod test2 {
             let actual = a1.contains(&b);
             expected == actual
         }
+
+        fn extend(a: Reference, b: Vec<i64>) -> bool {
+            let mut a1: Test = a.iter().cloned().collect();
+            a1.extend(b.iter().cloned());
+            let mut expected = a;
+            expected.extend(b);
+            a1.as_slice() == expected.into_iter().collect::<Vec<_>>().as_slice()
+        }
     }
 
     // bitop_assign_consistent!(Test);
@@ -0,0 +1,372 @@
+use crate::binary_merge::{EarlyOut, ShortcutMergeOperation};
+use crate::merge_state::{BoolOpMergeState, MergeStateMut, VecMergeState};
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt::Debug;
+
+/// A total order on values of type `T`, for types without a natural order or to order by a projection
+///
+/// The order must be consistent, like an `Ord` implementation: it must be transitive, and `compare(a, b)`
+/// must be the reverse of `compare(b, a)`. Any function `Fn(&T, &T) -> Ordering` is a comparator.
+pub trait Comparator<T> {
+    fn compare(&self, a: &T, b: &T) -> Ordering;
+}
+
+impl<T, F: Fn(&T, &T) -> Ordering> Comparator<T> for F {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        self(a, b)
+    }
+}
+
+/// A set backed by a sorted `Vec` without duplicates, ordered by a [Comparator] instead of `Ord`
+///
+/// Two elements are duplicates if the comparator considers them equal. All set operations use the
+/// comparator of self, so both operands must be ordered by equivalent comparators.
+#[derive(Clone, Default)]
+pub struct VecSetBy<T, C> {
+    elements: Vec<T>,
+    cmp: C,
+}
+
+struct UnionByOp<'a, C>(&'a C);
+struct IntersectionByOp<'a, C>(&'a C);
+struct DiffByOp<'a, C>(&'a C);
+struct XorByOp<'a, C>(&'a C);
+
+impl<'a, T, C: Comparator<T>, I: MergeStateMut<T, T>> ShortcutMergeOperation<T, T, I>
+    for UnionByOp<'a, C>
+{
+    fn cmp(&self, a: &T, b: &T) -> Ordering {
+        self.0.compare(a, b)
+    }
+    fn from_a(&self, m: &mut I, n: usize) -> EarlyOut {
+        m.advance_a(n, true)
+    }
+    fn from_b(&self, m: &mut I, n: usize) -> EarlyOut {
+        m.advance_b(n, true)
+    }
+    fn collision(&self, m: &mut I) -> EarlyOut {
+        m.advance_a(1, true)?;
+        m.advance_b(1, false)
+    }
+}
+
+impl<'a, T, C: Comparator<T>, I: MergeStateMut<T, T>> ShortcutMergeOperation<T, T, I>
+    for IntersectionByOp<'a, C>
+{
+    fn cmp(&self, a: &T, b: &T) -> Ordering {
+        self.0.compare(a, b)
+    }
+    fn from_a(&self, m: &mut I, n: usize) -> EarlyOut {
+        m.advance_a(n, false)
+    }
+    fn from_b(&self, m: &mut I, n: usize) -> EarlyOut {
+        m.advance_b(n, false)
+    }
+    fn collision(&self, m: &mut I) -> EarlyOut {
+        m.advance_a(1, true)?;
+        m.advance_b(1, false)
+    }
+}
+
+impl<'a, T, C: Comparator<T>, I: MergeStateMut<T, T>> ShortcutMergeOperation<T, T, I>
+    for DiffByOp<'a, C>
+{
+    fn cmp(&self, a: &T, b: &T) -> Ordering {
+        self.0.compare(a, b)
+    }
+    fn from_a(&self, m: &mut I, n: usize) -> EarlyOut {
+        m.advance_a(n, true)
+    }
+    fn from_b(&self, m: &mut I, n: usize) -> EarlyOut {
+        m.advance_b(n, false)
+    }
+    fn collision(&self, m: &mut I) -> EarlyOut {
+        m.advance_a(1, false)?;
+        m.advance_b(1, false)
+    }
+}
+
+impl<'a, T, C: Comparator<T>, I: MergeStateMut<T, T>> ShortcutMergeOperation<T, T, I>
+    for XorByOp<'a, C>
+{
+    fn cmp(&self, a: &T, b: &T) -> Ordering {
+        self.0.compare(a, b)
+    }
+    fn from_a(&self, m: &mut I, n: usize) -> EarlyOut {
+        m.advance_a(n, true)
+    }
+    fn from_b(&self, m: &mut I, n: usize) -> EarlyOut {
+        m.advance_b(n, true)
+    }
+    fn collision(&self, m: &mut I) -> EarlyOut {
+        m.advance_a(1, false)?;
+        m.advance_b(1, false)
+    }
+}
+
+impl<T: Debug, C> Debug for VecSetBy<T, C> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_set().entries(self.elements.iter()).finish()
+    }
+}
+
+impl<T, C: Comparator<T>> PartialEq for VecSetBy<T, C> {
+    /// Two sets are equal if their elements are pairwise equal according to the comparator of self
+    fn eq(&self, that: &Self) -> bool {
+        self.elements.len() == that.elements.len()
+            && self
+                .elements
+                .iter()
+                .zip(that.elements.iter())
+                .all(|(a, b)| self.cmp.compare(a, b) == Ordering::Equal)
+    }
+}
+
+impl<T, C: Comparator<T>> Eq for VecSetBy<T, C> {}
+
+impl<T, C> VecSetBy<T, C> {
+    /// An empty set using the given comparator
+    pub fn new(cmp: C) -> Self {
+        Self {
+            elements: Vec::new(),
+            cmp,
+        }
+    }
+    pub fn comparator(&self) -> &C {
+        &self.cmp
+    }
+    pub fn as_slice(&self) -> &[T] {
+        &self.elements
+    }
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+    /// An iterator that returns the items of this set in the order of the comparator
+    ///
+    /// This is a plain slice iterator, not a `SortedIterator`, since the items are in general not
+    /// sorted by their `Ord`. Use the set operations of this type instead of the iterator combinators.
+    pub fn iter(&self) -> core::slice::Iter<'_, T> {
+        self.elements.iter()
+    }
+    pub fn into_vec(self) -> Vec<T> {
+        self.elements
+    }
+}
+
+impl<T, C: Comparator<T>> VecSetBy<T, C> {
+    /// Creates a set from a vec, sorting it with the comparator and removing duplicates
+    ///
+    /// Of several elements that the comparator considers equal, the first one is kept.
+    pub fn from_vec(vec: Vec<T>, cmp: C) -> Self {
+        let mut elements = vec;
+        elements.sort_by(|a, b| cmp.compare(a, b));
+        elements.dedup_by(|a, b| cmp.compare(a, b) == Ordering::Equal);
+        Self { elements, cmp }
+    }
+
+    /// Inserts a single element, returning true if it was not already contained
+    ///
+    /// An element that the comparator considers equal is replaced.
+    pub fn insert(&mut self, value: T) -> bool {
+        match self
+            .elements
+            .binary_search_by(|x| self.cmp.compare(x, &value))
+        {
+            Ok(index) => {
+                self.elements[index] = value;
+                false
+            }
+            Err(index) => {
+                self.elements.insert(index, value);
+                true
+            }
+        }
+    }
+
+    /// Removes a single element, returning true if it was contained
+    pub fn remove(&mut self, value: &T) -> bool {
+        match self
+            .elements
+            .binary_search_by(|x| self.cmp.compare(x, value))
+        {
+            Ok(index) => {
+                self.elements.remove(index);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.elements
+            .binary_search_by(|x| self.cmp.compare(x, value))
+            .is_ok()
+    }
+
+    pub fn is_disjoint(&self, that: &Self) -> bool {
+        !BoolOpMergeState::merge(&self.elements, &that.elements, IntersectionByOp(&self.cmp))
+    }
+
+    pub fn is_subset(&self, that: &Self) -> bool {
+        !BoolOpMergeState::merge(&self.elements, &that.elements, DiffByOp(&self.cmp))
+    }
+
+    pub fn is_superset(&self, that: &Self) -> bool {
+        that.is_subset(self)
+    }
+}
+
+impl<T: Clone, C: Comparator<T> + Clone> VecSetBy<T, C> {
+    fn with_elements(&self, elements: Vec<T>) -> Self {
+        Self {
+            elements,
+            cmp: self.cmp.clone(),
+        }
+    }
+
+    /// The elements that are in self or that, as a new set
+    ///
+    /// Of two elements that the comparator considers equal, the one from self is kept.
+    pub fn union(&self, that: &Self) -> Self {
+        let op = UnionByOp(&self.cmp);
+        let elements = VecMergeState::merge_shortcut(&self.elements, &that.elements, op);
+        self.with_elements(elements)
+    }
+
+    /// The elements that are in both self and that, as a new set
+    pub fn intersection(&self, that: &Self) -> Self {
+        let op = IntersectionByOp(&self.cmp);
+        let elements = VecMergeState::merge_shortcut(&self.elements, &that.elements, op);
+        self.with_elements(elements)
+    }
+
+    /// The elements that are in self but not in that, as a new set
+    pub fn difference(&self, that: &Self) -> Self {
+        let op = DiffByOp(&self.cmp);
+        let elements = VecMergeState::merge_shortcut(&self.elements, &that.elements, op);
+        self.with_elements(elements)
+    }
+
+    /// The elements that are in exactly one of self and that, as a new set
+    pub fn symmetric_difference(&self, that: &Self) -> Self {
+        let op = XorByOp(&self.cmp);
+        let elements = VecMergeState::merge_shortcut(&self.elements, &that.elements, op);
+        self.with_elements(elements)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::VecSet;
+    use quickcheck_macros::quickcheck;
+    use std::collections::BTreeSet;
+    use std::string::{String, ToString};
+
+    /// orders f64 using `total_cmp`, so NaN and the two zeros are ordered too
+    #[derive(Clone, Copy, Default)]
+    struct TotalOrder;
+
+    impl Comparator<f64> for TotalOrder {
+        fn compare(&self, a: &f64, b: &f64) -> Ordering {
+            a.total_cmp(b)
+        }
+    }
+
+    #[derive(Clone, Copy, Default)]
+    struct CaseInsensitive;
+
+    impl Comparator<String> for CaseInsensitive {
+        fn compare(&self, a: &String, b: &String) -> Ordering {
+            a.to_lowercase().cmp(&b.to_lowercase())
+        }
+    }
+
+    fn floats(x: &[f64]) -> VecSetBy<f64, TotalOrder> {
+        VecSetBy::from_vec(x.to_vec(), TotalOrder)
+    }
+
+    fn strings(x: &[&str]) -> VecSetBy<String, CaseInsensitive> {
+        VecSetBy::from_vec(x.iter().map(|x| x.to_string()).collect(), CaseInsensitive)
+    }
+
+    #[test]
+    fn total_order_f64() {
+        let a = floats(&[3.0, f64::NAN, -0.0, 1.0, 0.0, 1.0]);
+        // -0.0 and 0.0 are distinct, and NaN sorts after everything else
+        assert_eq!(a.len(), 5);
+        assert_eq!(a.as_slice()[..4], [-0.0, 0.0, 1.0, 3.0]);
+        assert!(a.as_slice()[4].is_nan());
+        assert!(a.contains(&f64::NAN));
+        let b = floats(&[0.0, 2.0, f64::NAN]);
+        assert_eq!(a.union(&b), floats(&[-0.0, 0.0, 1.0, 2.0, 3.0, f64::NAN]));
+        assert_eq!(a.intersection(&b), floats(&[0.0, f64::NAN]));
+        assert_eq!(a.difference(&b), floats(&[-0.0, 1.0, 3.0]));
+        assert_eq!(a.symmetric_difference(&b), floats(&[-0.0, 1.0, 2.0, 3.0]));
+        assert!(!a.is_disjoint(&b));
+        assert!(a.intersection(&b).is_subset(&b));
+        assert!(a.union(&b).is_superset(&a));
+    }
+
+    #[test]
+    fn case_insensitive_strings() {
+        let mut a = strings(&["b", "A", "a", "C"]);
+        // the first of the equal elements is kept
+        assert_eq!(a.as_slice(), &["A", "b", "C"]);
+        assert!(a.contains(&"B".to_string()));
+        assert!(!a.insert("c".to_string()));
+        assert!(a.insert("d".to_string()));
+        assert_eq!(a.as_slice(), &["A", "b", "c", "d"]);
+        assert!(a.remove(&"D".to_string()));
+        assert!(!a.remove(&"D".to_string()));
+        let b = strings(&["a", "B", "z"]);
+        // the union keeps the elements of self
+        assert_eq!(a.union(&b).as_slice(), &["A", "b", "c", "z"]);
+        assert_eq!(a.intersection(&b).as_slice(), &["A", "b"]);
+        assert_eq!(a.difference(&b).as_slice(), &["c"]);
+        assert_eq!(a.symmetric_difference(&b).as_slice(), &["c", "z"]);
+        assert_eq!(format!("{:?}", b), r#"{"a", "B", "z"}"#);
+    }
+
+    #[test]
+    fn iter_is_in_comparator_order() {
+        // "B" < "a" by Ord, so merging by Ord would miss the common element
+        let a = strings(&["a", "B"]);
+        let b = strings(&["B"]);
+        assert_eq!(a.iter().collect::<Vec<_>>(), vec!["a", "B"]);
+        let intersection: Vec<&String> = a.iter().filter(|x| b.contains(x)).collect();
+        assert_eq!(intersection, vec!["B"]);
+        assert_eq!(a.intersection(&b), strings(&["B"]));
+    }
+
+    #[test]
+    fn closure_comparator() {
+        // ordered by a projection, here the absolute value
+        let cmp = |a: &i64, b: &i64| a.abs().cmp(&b.abs());
+        let a = VecSetBy::from_vec(vec![-3, 1, 2, -1], cmp);
+        assert_eq!(a.as_slice(), &[1, 2, -3]);
+        let b = VecSetBy::from_vec(vec![3, -4], cmp);
+        assert_eq!(a.union(&b).as_slice(), &[1, 2, -3, -4]);
+    }
+
+    #[quickcheck]
+    fn same_as_vec_set(a: BTreeSet<i64>, b: BTreeSet<i64>) -> bool {
+        // reversed, so the comparator actually matters
+        let cmp = |a: &i64, b: &i64| b.cmp(a);
+        let a1: VecSet<i64> = a.iter().cloned().collect();
+        let b1: VecSet<i64> = b.iter().cloned().collect();
+        let a2 = VecSetBy::from_vec(a.into_iter().collect(), cmp);
+        let b2 = VecSetBy::from_vec(b.into_iter().collect(), cmp);
+        let same = |x: VecSetBy<i64, _>, y: VecSet<i64>| x.into_vec().into_iter().rev().eq(y);
+        same(a2.union(&b2), a1.union(&b1))
+            && same(a2.intersection(&b2), a1.intersection(&b1))
+            && same(a2.difference(&b2), a1.difference(&b1))
+            && same(a2.symmetric_difference(&b2), a1.symmetric_difference(&b1))
+            && a2.is_subset(&b2) == a1.is_subset(&b1)
+            && a2.is_disjoint(&b2) == a1.is_disjoint(&b1)
+    }
+}
@@ -0,0 +1,262 @@
+use crate::iterators::SortedIter;
+use crate::merge_state::{AllocVecMergeState, BoolOpMergeState};
+use crate::vec_set::{SetDiffOp, SetIntersectionOp, SetUnionOp, SetXorOp};
+use alloc::alloc::{Allocator, Global};
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
+/// A set backed by a sorted `Vec` without duplicates, that stores its elements using the allocator `A`
+///
+/// This works like `VecSet`, but the results of the set operations are allocated using the allocator of
+/// the left operand. This requires the `allocator_api` feature, which is only available on nightly.
+///
+/// It only covers the basic operations: construction from a `Vec`, insert, remove and contains, the set
+/// relations, and union, intersection, difference and symmetric difference. Everything else, such as the
+/// operators, the in-place operations and serde support, is only available on `VecSet`. `VecSet` is not
+/// parameterized over the allocator instead, since that would require the unstable `allocator_api` for all
+/// users, and many of its operations are built on merge states that are specific to `Vec<T>`.
+pub struct VecSetIn<T, A: Allocator = Global>(Vec<T, A>);
+
+impl<T: Debug, A: Allocator> Debug for VecSetIn<T, A> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_set().entries(self.0.iter()).finish()
+    }
+}
+
+impl<T: Clone, A: Allocator + Clone> Clone for VecSetIn<T, A> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: PartialEq, A: Allocator, B: Allocator> PartialEq<VecSetIn<T, B>> for VecSetIn<T, A> {
+    fn eq(&self, that: &VecSetIn<T, B>) -> bool {
+        self.0.as_slice() == that.0.as_slice()
+    }
+}
+
+impl<T: Eq, A: Allocator> Eq for VecSetIn<T, A> {}
+
+impl<T, A: Allocator> VecSetIn<T, A> {
+    /// An empty set using the given allocator
+    pub fn new_in(alloc: A) -> Self {
+        Self(Vec::new_in(alloc))
+    }
+    pub fn as_slice(&self) -> &[T] {
+        &self.0
+    }
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    pub fn allocator(&self) -> &A {
+        self.0.allocator()
+    }
+    /// An iterator that returns the items of this set in sorted order
+    pub fn iter(&self) -> SortedIter<core::slice::Iter<'_, T>> {
+        SortedIter::new(self.0.iter())
+    }
+    pub fn into_inner(self) -> Vec<T, A> {
+        self.0
+    }
+}
+
+impl<T: Ord, A: Allocator> From<Vec<T, A>> for VecSetIn<T, A> {
+    fn from(vec: Vec<T, A>) -> Self {
+        let mut vec = vec;
+        vec.sort();
+        vec.dedup();
+        Self(vec)
+    }
+}
+
+impl<T: Ord, A: Allocator> VecSetIn<T, A> {
+    pub fn insert(&mut self, that: T) {
+        match self.0.binary_search(&that) {
+            Ok(index) => self.0[index] = that,
+            Err(index) => self.0.insert(index, that),
+        }
+    }
+
+    pub fn remove(&mut self, that: &T) {
+        if let Ok(index) = self.0.binary_search(that) {
+            self.0.remove(index);
+        };
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.0.binary_search(value).is_ok()
+    }
+
+    pub fn is_disjoint<B: Allocator>(&self, that: &VecSetIn<T, B>) -> bool {
+        !BoolOpMergeState::merge(&self.0, &that.0, SetIntersectionOp)
+    }
+
+    pub fn is_subset<B: Allocator>(&self, that: &VecSetIn<T, B>) -> bool {
+        !BoolOpMergeState::merge(&self.0, &that.0, SetDiffOp)
+    }
+
+    pub fn is_superset<B: Allocator>(&self, that: &VecSetIn<T, B>) -> bool {
+        that.is_subset(self)
+    }
+}
+
+impl<T: Ord + Clone, A: Allocator + Clone> VecSetIn<T, A> {
+    /// The elements that are in self or that, as a new set in the allocator of self
+    pub fn union<B: Allocator>(&self, that: &VecSetIn<T, B>) -> Self {
+        let alloc = self.allocator().clone();
+        Self(AllocVecMergeState::merge(
+            &self.0, &that.0, SetUnionOp, alloc,
+        ))
+    }
+
+    /// The elements that are in both self and that, as a new set in the allocator of self
+    pub fn intersection<B: Allocator>(&self, that: &VecSetIn<T, B>) -> Self {
+        let alloc = self.allocator().clone();
+        Self(AllocVecMergeState::merge(
+            &self.0,
+            &that.0,
+            SetIntersectionOp,
+            alloc,
+        ))
+    }
+
+    /// The elements that are in self but not in that, as a new set in the allocator of self
+    pub fn difference<B: Allocator>(&self, that: &VecSetIn<T, B>) -> Self {
+        let alloc = self.allocator().clone();
+        Self(AllocVecMergeState::merge(
+            &self.0, &that.0, SetDiffOp, alloc,
+        ))
+    }
+
+    /// The elements that are in exactly one of self and that, as a new set in the allocator of self
+    pub fn symmetric_difference<B: Allocator>(&self, that: &VecSetIn<T, B>) -> Self {
+        let alloc = self.allocator().clone();
+        Self(AllocVecMergeState::merge(&self.0, &that.0, SetXorOp, alloc))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::VecSet;
+    use core::alloc::Layout;
+    use core::cell::Cell;
+    use core::ptr::NonNull;
+    use quickcheck_macros::quickcheck;
+    use std::alloc::AllocError;
+    use std::collections::BTreeSet;
+
+    /// a bump allocator that never frees, and counts its allocations
+    struct Bump {
+        buf: NonNull<u8>,
+        size: usize,
+        offset: Cell<usize>,
+        allocations: Cell<usize>,
+    }
+
+    impl Bump {
+        fn new(size: usize) -> Self {
+            let layout = Layout::from_size_align(size, 16).unwrap();
+            let buf = NonNull::new(unsafe { std::alloc::alloc(layout) }).unwrap();
+            Self {
+                buf,
+                size,
+                offset: Cell::new(0),
+                allocations: Cell::new(0),
+            }
+        }
+
+        fn owns<T>(&self, ptr: *const T) -> bool {
+            let start = self.buf.as_ptr() as usize;
+            let ptr = ptr as usize;
+            ptr >= start && ptr < start + self.size
+        }
+    }
+
+    impl Drop for Bump {
+        fn drop(&mut self) {
+            let layout = Layout::from_size_align(self.size, 16).unwrap();
+            unsafe { std::alloc::dealloc(self.buf.as_ptr(), layout) }
+        }
+    }
+
+    unsafe impl Allocator for &Bump {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            let start = (self.offset.get() + layout.align() - 1) & !(layout.align() - 1);
+            let end = start.checked_add(layout.size()).ok_or(AllocError)?;
+            if end > self.size {
+                return Err(AllocError);
+            }
+            self.offset.set(end);
+            self.allocations.set(self.allocations.get() + 1);
+            let ptr = unsafe { self.buf.as_ptr().add(start) };
+            Ok(NonNull::slice_from_raw_parts(
+                NonNull::new(ptr).unwrap(),
+                layout.size(),
+            ))
+        }
+
+        unsafe fn deallocate(&self, _ptr: NonNull<u8>, _layout: Layout) {}
+    }
+
+    fn set_in<'a>(bump: &'a Bump, elements: &[i64]) -> VecSetIn<i64, &'a Bump> {
+        let mut vec = Vec::new_in(bump);
+        vec.extend_from_slice(elements);
+        vec.into()
+    }
+
+    #[test]
+    fn bump_allocator_merges() {
+        let bump = Bump::new(1 << 16);
+        let a = set_in(&bump, &[5, 1, 3, 3]);
+        let b = set_in(&bump, &[2, 3, 4]);
+        assert_eq!(a.as_slice(), &[1, 3, 5]);
+        let allocations = bump.allocations.get();
+        let union = a.union(&b);
+        let intersection = a.intersection(&b);
+        let difference = a.difference(&b);
+        let xor = a.symmetric_difference(&b);
+        assert_eq!(union.as_slice(), &[1, 2, 3, 4, 5]);
+        assert_eq!(intersection.as_slice(), &[3]);
+        assert_eq!(difference.as_slice(), &[1, 5]);
+        assert_eq!(xor.as_slice(), &[1, 2, 4, 5]);
+        // all results were allocated in the bump allocator
+        assert!(bump.allocations.get() > allocations);
+        for r in [&union, &intersection, &difference, &xor] {
+            assert!(bump.owns(r.as_slice().as_ptr()));
+        }
+    }
+
+    #[test]
+    fn mixed_allocators() {
+        let bump = Bump::new(1 << 16);
+        let a = set_in(&bump, &[1, 2, 3]);
+        let b: VecSetIn<i64> = vec![3, 4].into();
+        // the result uses the allocator of the left operand
+        let r = b.union(&a);
+        assert!(!bump.owns(r.as_slice().as_ptr()));
+        let r = a.union(&b);
+        assert!(bump.owns(r.as_slice().as_ptr()));
+        assert_eq!(r, b.union(&a));
+        assert!(!a.is_disjoint(&b));
+        assert!(a.intersection(&b).is_subset(&b));
+    }
+
+    #[quickcheck]
+    fn same_as_vec_set(a: BTreeSet<i64>, b: BTreeSet<i64>) -> bool {
+        let bump = Bump::new(1 << 20);
+        let a1: VecSet<i64> = a.iter().cloned().collect();
+        let b1: VecSet<i64> = b.iter().cloned().collect();
+        let a2 = set_in(&bump, a1.as_slice());
+        let b2 = set_in(&bump, b1.as_slice());
+        a2.union(&b2).as_slice() == a1.union(&b1).as_slice()
+            && a2.intersection(&b2).as_slice() == a1.intersection(&b1).as_slice()
+            && a2.difference(&b2).as_slice() == a1.difference(&b1).as_slice()
+            && a2.symmetric_difference(&b2).as_slice() == a1.symmetric_difference(&b1).as_slice()
+            && a2.is_subset(&b2) == a1.is_subset(&b1)
+            && a2.is_disjoint(&b2) == a1.is_disjoint(&b1)
+    }
+}
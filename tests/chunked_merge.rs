@@ -0,0 +1,63 @@
+//! Merges two sorted files that are read in chunks
+#![cfg(feature = "std")]
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+use vec_collections::union_sorted_lines;
+
+fn temp_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("vec-collections-{}-{}", std::process::id(), name))
+}
+
+fn write_numbers(name: &str, numbers: impl Iterator<Item = u64>) -> PathBuf {
+    let path = temp_file(name);
+    let text: String = numbers.map(|x| format!("{}\n", x)).collect();
+    fs::write(&path, text).unwrap();
+    path
+}
+
+fn union_files(a: &PathBuf, b: &PathBuf, name: &str) -> std::io::Result<PathBuf> {
+    let path = temp_file(name);
+    let a = BufReader::new(File::open(a)?);
+    let b = BufReader::new(File::open(b)?);
+    let out = BufWriter::new(File::create(&path)?);
+    union_sorted_lines(a, b, out)?;
+    Ok(path)
+}
+
+#[test]
+fn union_of_files() {
+    // large enough to span several chunks
+    let a = write_numbers("union-a", (0..200_000).map(|x| x * 2));
+    let b = write_numbers("union-b", (0..100_000).map(|x| x * 3));
+    let r = union_files(&a, &b, "union-r").unwrap();
+    let actual: Vec<u64> = fs::read_to_string(&r)
+        .unwrap()
+        .lines()
+        .map(|x| x.parse().unwrap())
+        .collect();
+    let mut expected: Vec<u64> = (0..200_000)
+        .map(|x| x * 2)
+        .chain((0..100_000).map(|x| x * 3))
+        .collect();
+    expected.sort();
+    expected.dedup();
+    assert_eq!(actual, expected);
+    for path in [a, b, r] {
+        fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn union_of_empty_and_invalid_files() {
+    let a = write_numbers("empty-a", std::iter::empty());
+    let b = write_numbers("empty-b", vec![1, 2].into_iter());
+    let r = union_files(&a, &b, "empty-r").unwrap();
+    assert_eq!(fs::read_to_string(&r).unwrap(), "1\n2\n");
+    fs::write(&a, "1\nfoo\n").unwrap();
+    let err = union_files(&a, &b, "invalid-r").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    for path in [a, b, r, temp_file("invalid-r")] {
+        fs::remove_file(path).unwrap();
+    }
+}
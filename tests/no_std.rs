@@ -0,0 +1,36 @@
+//! Uses the set operations from a `no_std` crate, to make sure that they only need `core` and `alloc`
+//!
+//! To check this against the library built without std, run `cargo test --no-default-features --test no_std`
+#![no_std]
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+use vec_collections::VecSet;
+
+#[test]
+fn set_ops() {
+    let a: VecSet<u32> = vec![5, 1, 3, 1].into();
+    let b: VecSet<u32> = vec![4, 3, 2].into();
+    let union: Vec<u32> = (&a | &b).into();
+    let intersection: Vec<u32> = (&a & &b).into();
+    let difference: Vec<u32> = (&a - &b).into();
+    let xor: Vec<u32> = (&a ^ &b).into();
+    assert_eq!(union, vec![1, 2, 3, 4, 5]);
+    assert_eq!(intersection, vec![3]);
+    assert_eq!(difference, vec![1, 5]);
+    assert_eq!(xor, vec![1, 2, 4, 5]);
+    assert!(a.contains(&5));
+    assert!(!a.is_disjoint(&b));
+    assert_eq!(a.intersection_count(&b), 1);
+}
+
+#[test]
+fn in_place_ops() {
+    let mut a: VecSet<u32> = vec![1, 3, 5].into();
+    let b: VecSet<u32> = vec![2, 3].into();
+    a.union_with(&b);
+    assert_eq!(a.iter().cloned().collect::<Vec<_>>(), vec![1, 2, 3, 5]);
+    a.difference_with(&b);
+    assert_eq!(a.iter().cloned().collect::<Vec<_>>(), vec![1, 5]);
+}
@@ -0,0 +1,37 @@
+//! Names the sorted iterator adapters in signatures and struct fields, to make sure that they are exported
+use std::vec::IntoIter;
+use vec_collections::{
+    assume_sorted_by_item, assume_sorted_by_key, Dedup, EitherOrBoth, PairJoin, SortedIter,
+    SortedPairIter,
+};
+
+/// the distinct elements of a sorted vec
+fn distinct(elements: Vec<u32>) -> SortedIter<Dedup<IntoIter<u32>>> {
+    assume_sorted_by_item(elements).dedup()
+}
+
+type Pairs<V> = IntoIter<(u32, V)>;
+
+/// a join of two sorted key-value streams, kept for later
+struct PendingJoin {
+    join: SortedPairIter<PairJoin<Pairs<char>, Pairs<bool>>>,
+}
+
+#[test]
+fn named_adapters() {
+    let r: Vec<u32> = distinct(vec![1, 1, 2, 3, 3]).collect();
+    assert_eq!(r, vec![1, 2, 3]);
+    let pending = PendingJoin {
+        join: assume_sorted_by_key(vec![(1, 'a'), (2, 'b')])
+            .join(assume_sorted_by_key(vec![(2, true), (3, false)])),
+    };
+    let r: Vec<(u32, EitherOrBoth<char, bool>)> = pending.join.collect();
+    assert_eq!(
+        r,
+        vec![
+            (1, EitherOrBoth::Left('a')),
+            (2, EitherOrBoth::Both('b', true)),
+            (3, EitherOrBoth::Right(false)),
+        ]
+    );
+}